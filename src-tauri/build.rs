@@ -1,3 +1,18 @@
 fn main() {
+    compile_protos().expect("Failed to compile sochdb-server protos");
     tauri_build::build()
 }
+
+/// Generate the gRPC client for `sochdb-server` from the shared proto
+fn compile_protos() -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = prost_build::Config::new();
+    config.protoc_executable(protoc_bin_vendored::protoc_bin_path()?);
+
+    // Server stubs are generated too; the tests use them to stand in for sochdb-server
+    tonic_build::configure().compile_protos_with_config(
+        config,
+        &["../server/proto/sochdb.proto"],
+        &["../server/proto"],
+    )?;
+    Ok(())
+}
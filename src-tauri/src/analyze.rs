@@ -0,0 +1,499 @@
+//! Collection analysis for selectivity estimates
+//!
+//! `analyze` samples each collection and records, per column (top-level
+//! JSON field), a distinct-value estimate, null fraction, average size,
+//! most common values and a numeric histogram, plus how the collection's
+//! keys are distributed by prefix. Results are stored in the database under
+//! [`STATS_PREFIX`] so the schema browser and query planner can read them.
+
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::connection::pool::Session;
+use crate::stats::SampleRng;
+use crate::util::now_millis;
+
+/// Keys Studio writes for itself; never listed as a user collection
+pub const RESERVED_PREFIX: &str = "_studio/";
+
+/// Key prefix analysis results are stored under, inside [`RESERVED_PREFIX`]
+pub const STATS_PREFIX: &str = "_studio/stats/";
+
+/// Fixed seed, so analyzing the same data twice gives the same statistics
+const SAMPLE_SEED: u64 = 0x5EED;
+
+/// Rows sampled per collection unless the caller asks otherwise
+pub const DEFAULT_SAMPLE_SIZE: usize = 10_000;
+
+const HISTOGRAM_BUCKETS: usize = 10;
+const MOST_COMMON_VALUES: usize = 10;
+const TOP_KEY_PREFIXES: usize = 10;
+
+/// Column name used for values that are not JSON objects
+const VALUE_COLUMN: &str = "value";
+
+static NULL: Value = Value::Null;
+
+/// Analysis of one collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionAnalysis {
+    pub collection: String,
+    pub row_count: u64,
+    pub sampled_rows: u64,
+    /// Average size of a whole value in bytes
+    pub avg_value_size: f64,
+    pub columns: Vec<ColumnStatistics>,
+    /// Most common key segments after the collection name, scaled to the
+    /// whole collection
+    pub key_prefixes: Vec<PrefixCount>,
+    pub distinct_key_prefixes: u64,
+    /// Unix timestamp (ms) of the analysis
+    pub analyzed_at: u64,
+}
+
+/// Statistics for one column
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnStatistics {
+    pub name: String,
+    /// JSON type of the non-null values, or "mixed"
+    pub data_type: String,
+    pub distinct_estimate: u64,
+    pub null_fraction: f64,
+    /// Average size of the non-null values in bytes
+    pub avg_size_bytes: f64,
+    pub most_common: Vec<ValueFrequency>,
+    /// Equal-width histogram over numeric values (empty for other types)
+    pub histogram: Vec<HistogramBucket>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValueFrequency {
+    pub value: Value,
+    /// Fraction of all rows holding this value
+    pub frequency: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistogramBucket {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrefixCount {
+    pub prefix: String,
+    pub count: u64,
+}
+
+impl ColumnStatistics {
+    /// Estimated fraction of rows where the column equals `value`
+    pub fn equality_selectivity(&self, value: &Value) -> f64 {
+        if value.is_null() {
+            return self.null_fraction;
+        }
+        if let Some(mcv) = self.most_common.iter().find(|m| &m.value == value) {
+            return mcv.frequency;
+        }
+
+        // Spread what the common values leave over the remaining distinct values
+        let common: f64 = self.most_common.iter().map(|m| m.frequency).sum();
+        let remaining = self.distinct_estimate.saturating_sub(self.most_common.len() as u64).max(1);
+        ((1.0 - self.null_fraction - common) / remaining as f64).clamp(0.0, 1.0)
+    }
+
+    /// Estimated fraction of rows with a numeric value between `low` and
+    /// `high` (`None` leaves that side open), or `None` without a histogram
+    pub fn range_selectivity(&self, low: Option<f64>, high: Option<f64>) -> Option<f64> {
+        let total: u64 = self.histogram.iter().map(|b| b.count).sum();
+        if total == 0 {
+            return None;
+        }
+        let low = low.unwrap_or(f64::NEG_INFINITY);
+        let high = high.unwrap_or(f64::INFINITY);
+
+        // Assume values are spread evenly within each bucket
+        let matched: f64 = self
+            .histogram
+            .iter()
+            .map(|b| {
+                let width = b.upper - b.lower;
+                let overlap = if width <= 0.0 {
+                    if (low..=high).contains(&b.lower) { 1.0 } else { 0.0 }
+                } else {
+                    ((high.min(b.upper) - low.max(b.lower)) / width).clamp(0.0, 1.0)
+                };
+                overlap * b.count as f64
+            })
+            .sum();
+        Some((matched / total as f64 * (1.0 - self.null_fraction)).clamp(0.0, 1.0))
+    }
+}
+
+/// Storage key for a collection's analysis
+pub fn stats_key(collection: &str) -> String {
+    format!("{}{}", STATS_PREFIX, collection)
+}
+
+/// Analyze one collection from its scanned entries
+pub fn analyze_collection(
+    collection: &str,
+    entries: &[(Vec<u8>, Vec<u8>)],
+    sample_size: usize,
+) -> CollectionAnalysis {
+    let mut sampler = Sampler::new(sample_size);
+    for entry in entries {
+        sampler.add(entry.clone());
+    }
+    sampler.analyze(collection)
+}
+
+/// Reservoir sample of one collection, fed entry by entry as it is scanned,
+/// so memory is bounded by the sample size rather than the collection.
+///
+/// Taking every `n`th entry instead would alias with data that repeats
+/// every `n` keys.
+pub struct Sampler {
+    size: usize,
+    rng: SampleRng,
+    row_count: u64,
+    reservoir: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Sampler {
+    pub fn new(sample_size: usize) -> Self {
+        Self {
+            size: sample_size.max(1),
+            rng: SampleRng::new(SAMPLE_SEED),
+            row_count: 0,
+            reservoir: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, entry: (Vec<u8>, Vec<u8>)) {
+        self.row_count += 1;
+        if self.reservoir.len() < self.size {
+            self.reservoir.push(entry);
+            return;
+        }
+        let slot = self.rng.below(self.row_count as usize);
+        if slot < self.size {
+            self.reservoir[slot] = entry;
+        }
+    }
+
+    /// Statistics for the entries added so far
+    pub fn analyze(self, collection: &str) -> CollectionAnalysis {
+        analyze_sample(collection, self.row_count, &self.reservoir)
+    }
+}
+
+/// Analyze a collection of `row_count` entries from a sample of them
+fn analyze_sample(collection: &str, row_count: u64, sample: &[(Vec<u8>, Vec<u8>)]) -> CollectionAnalysis {
+    let sampled_rows = sample.len() as u64;
+    let scale = if sampled_rows == 0 { 0.0 } else { row_count as f64 / sampled_rows as f64 };
+
+    let rows: Vec<Value> = sample
+        .iter()
+        .map(|(_, value)| {
+            serde_json::from_slice(value)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(value).into_owned()))
+        })
+        .collect();
+
+    // Every field seen in any sampled row is a column; rows without it count as null
+    let mut columns: BTreeMap<String, Vec<&Value>> = BTreeMap::new();
+    for row in &rows {
+        match row.as_object() {
+            Some(obj) => {
+                for name in obj.keys() {
+                    columns.entry(name.clone()).or_default();
+                }
+            }
+            None => {
+                columns.entry(VALUE_COLUMN.to_string()).or_default();
+            }
+        }
+    }
+    for row in &rows {
+        for (name, values) in columns.iter_mut() {
+            let value = match row.as_object() {
+                Some(obj) => obj.get(name).unwrap_or(&NULL),
+                None if name == VALUE_COLUMN => row,
+                None => &NULL,
+            };
+            values.push(value);
+        }
+    }
+
+    let avg_value_size = if sampled_rows == 0 {
+        0.0
+    } else {
+        sample.iter().map(|(_, v)| v.len()).sum::<usize>() as f64 / sampled_rows as f64
+    };
+
+    // Distribution of the key segment after the collection name
+    let mut prefixes: HashMap<String, u64> = HashMap::new();
+    for (key, _) in sample {
+        let key = String::from_utf8_lossy(key);
+        let segment = key
+            .trim_start_matches('/')
+            .split('/')
+            .nth(1)
+            .unwrap_or_default()
+            .to_string();
+        *prefixes.entry(segment).or_default() += 1;
+    }
+    let distinct_key_prefixes = distinct_estimate(&prefixes, sampled_rows, row_count);
+    let mut key_prefixes: Vec<PrefixCount> = prefixes
+        .into_iter()
+        .map(|(prefix, count)| PrefixCount {
+            prefix,
+            count: (count as f64 * scale).round() as u64,
+        })
+        .collect();
+    key_prefixes.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.prefix.cmp(&b.prefix)));
+    key_prefixes.truncate(TOP_KEY_PREFIXES);
+
+    CollectionAnalysis {
+        collection: collection.to_string(),
+        row_count,
+        sampled_rows,
+        avg_value_size,
+        columns: columns
+            .into_iter()
+            .map(|(name, values)| column_statistics(name, &values, row_count))
+            .collect(),
+        key_prefixes,
+        distinct_key_prefixes,
+        analyzed_at: now_millis(),
+    }
+}
+
+/// Load the stored analysis of a collection, or `None` if it has not been
+/// analyzed
+pub async fn load_analysis(session: &Session, collection: &str) -> Result<Option<CollectionAnalysis>, String> {
+    let result = session
+        .call_tool("sochdb_get", json!({ "path": stats_key(collection) }))
+        .await?;
+    let text = result["content"][0]["text"].as_str().unwrap_or("null");
+    if result["isError"].as_bool().unwrap_or(false) {
+        return Err(format!("Failed to load statistics for {}: {}", collection, text));
+    }
+    serde_json::from_str(text)
+        .map_err(|e| format!("Stored statistics for {} are unreadable: {}", collection, e))
+}
+
+/// Store a collection's analysis under [`STATS_PREFIX`]
+pub async fn store_analysis(session: &Session, analysis: &CollectionAnalysis) -> Result<(), String> {
+    let value = serde_json::to_value(analysis).map_err(|e| e.to_string())?;
+    session
+        .call_tool(
+            "sochdb_put",
+            json!({ "path": stats_key(&analysis.collection), "value": value }),
+        )
+        .await
+        .map(|_| ())
+}
+
+fn column_statistics(name: String, values: &[&Value], row_count: u64) -> ColumnStatistics {
+    let sampled = values.len() as u64;
+    let non_null: Vec<&Value> = values.iter().copied().filter(|v| !v.is_null()).collect();
+
+    let mut types: Vec<&str> = non_null.iter().map(|v| json_type(v)).collect();
+    types.sort_unstable();
+    types.dedup();
+    let data_type = match types.as_slice() {
+        [] => "null",
+        [single] => *single,
+        _ => "mixed",
+    }
+    .to_string();
+
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    let mut originals: HashMap<String, &Value> = HashMap::new();
+    let mut total_size = 0usize;
+    for value in &non_null {
+        let text = value.to_string();
+        total_size += text.len();
+        *counts.entry(text.clone()).or_default() += 1;
+        originals.entry(text).or_insert(value);
+    }
+
+    // Values seen once in the sample are unlikely to be common
+    let mut most_common: Vec<(&String, u64)> = counts
+        .iter()
+        .filter(|(_, count)| **count > 1)
+        .map(|(text, count)| (text, *count))
+        .collect();
+    most_common.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    most_common.truncate(MOST_COMMON_VALUES);
+
+    let numbers: Vec<f64> = non_null.iter().filter_map(|v| v.as_f64()).collect();
+
+    ColumnStatistics {
+        name,
+        data_type,
+        distinct_estimate: distinct_estimate(&counts, sampled, row_count),
+        null_fraction: fraction(sampled - non_null.len() as u64, sampled),
+        avg_size_bytes: if non_null.is_empty() { 0.0 } else { total_size as f64 / non_null.len() as f64 },
+        most_common: most_common
+            .into_iter()
+            .map(|(text, count)| ValueFrequency {
+                value: originals[text].clone(),
+                frequency: fraction(count, sampled),
+            })
+            .collect(),
+        histogram: if numbers.len() == non_null.len() {
+            histogram(&numbers)
+        } else {
+            vec![]
+        },
+    }
+}
+
+/// Guaranteed-error estimator: values seen once in the sample stand for
+/// `sqrt(total / sampled)` values each; repeated values are counted once.
+fn distinct_estimate(counts: &HashMap<String, u64>, sampled: u64, total: u64) -> u64 {
+    if sampled == 0 {
+        return 0;
+    }
+    let singletons = counts.values().filter(|c| **c == 1).count() as f64;
+    let repeated = counts.values().filter(|c| **c > 1).count() as f64;
+    let estimate = (total as f64 / sampled as f64).sqrt() * singletons + repeated;
+    (estimate.round() as u64).min(total)
+}
+
+fn histogram(values: &[f64]) -> Vec<HistogramBucket> {
+    let Some(min) = values.iter().copied().reduce(f64::min) else {
+        return vec![];
+    };
+    let max = values.iter().copied().fold(min, f64::max);
+    if min == max {
+        return vec![HistogramBucket { lower: min, upper: max, count: values.len() as u64 }];
+    }
+
+    let width = (max - min) / HISTOGRAM_BUCKETS as f64;
+    let mut buckets: Vec<HistogramBucket> = (0..HISTOGRAM_BUCKETS)
+        .map(|i| HistogramBucket {
+            lower: min + width * i as f64,
+            upper: if i + 1 == HISTOGRAM_BUCKETS { max } else { min + width * (i + 1) as f64 },
+            count: 0,
+        })
+        .collect();
+    for value in values {
+        let index = (((value - min) / width) as usize).min(HISTOGRAM_BUCKETS - 1);
+        buckets[index].count += 1;
+    }
+    buckets
+}
+
+/// JSON type name of a value, as reported in column statistics
+pub fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn fraction(count: u64, total: u64) -> f64 {
+    if total == 0 { 0.0 } else { count as f64 / total as f64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(n: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+        (0..n)
+            .map(|i| {
+                let key = format!("users/{}/profile", if i % 3 == 0 { "eu" } else { "us" });
+                let mut value = json!({ "id": i, "role": if i % 10 == 0 { "admin" } else { "user" } });
+                if i % 2 == 0 {
+                    value["email"] = json!(format!("user{}@example.com", i));
+                }
+                (key.into_bytes(), value.to_string().into_bytes())
+            })
+            .collect()
+    }
+
+    fn column<'a>(analysis: &'a CollectionAnalysis, name: &str) -> &'a ColumnStatistics {
+        analysis.columns.iter().find(|c| c.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_analyze_columns() {
+        let analysis = analyze_collection("users", &rows(100), DEFAULT_SAMPLE_SIZE);
+        assert_eq!(analysis.row_count, 100);
+        assert_eq!(analysis.sampled_rows, 100);
+
+        let id = column(&analysis, "id");
+        assert_eq!(id.data_type, "number");
+        assert_eq!(id.distinct_estimate, 100);
+        assert_eq!(id.histogram.len(), HISTOGRAM_BUCKETS);
+        assert_eq!(id.histogram.iter().map(|b| b.count).sum::<u64>(), 100);
+
+        let email = column(&analysis, "email");
+        assert_eq!(email.null_fraction, 0.5);
+        assert!(email.histogram.is_empty());
+
+        let role = column(&analysis, "role");
+        assert_eq!(role.distinct_estimate, 2);
+        assert_eq!(role.most_common[0].value, json!("user"));
+        assert_eq!(role.equality_selectivity(&json!("admin")), 0.1);
+        assert!(role.range_selectivity(Some(0.0), None).is_none());
+
+        // ids 0..=99 in ten buckets of 9.9
+        let below_half = id.range_selectivity(None, Some(49.5)).unwrap();
+        assert!((below_half - 0.5).abs() < 1e-9);
+        assert_eq!(id.range_selectivity(Some(200.0), None), Some(0.0));
+    }
+
+    #[test]
+    fn test_analyze_samples_and_scales() {
+        let analysis = analyze_collection("users", &rows(1000), 100);
+        assert_eq!(analysis.row_count, 1000);
+        assert_eq!(analysis.sampled_rows, 100);
+
+        // Sampled ids are all distinct, so they are extrapolated
+        assert!(column(&analysis, "id").distinct_estimate > 100);
+        // ...while low-cardinality columns are not. Every 10th row is an
+        // admin, which a sample of every 10th row would miss.
+        assert_eq!(column(&analysis, "role").distinct_estimate, 2);
+
+        assert_eq!(analysis.distinct_key_prefixes, 2);
+        assert_eq!(analysis.key_prefixes[0].prefix, "us");
+        assert!((550..=780).contains(&analysis.key_prefixes[0].count));
+    }
+
+    #[test]
+    fn test_analyze_non_object_values() {
+        let entries = vec![
+            (b"notes/1".to_vec(), b"hello".to_vec()),
+            (b"notes/2".to_vec(), b"42".to_vec()),
+        ];
+        let analysis = analyze_collection("notes", &entries, DEFAULT_SAMPLE_SIZE);
+        assert_eq!(analysis.columns.len(), 1);
+        assert_eq!(analysis.columns[0].name, VALUE_COLUMN);
+        assert_eq!(analysis.columns[0].data_type, "mixed");
+    }
+
+    #[test]
+    fn test_sampler_memory_is_bounded() {
+        let mut sampler = Sampler::new(100);
+        for (i, entry) in rows(10).into_iter().cycle().take(50_000).enumerate() {
+            sampler.add(entry);
+            assert_eq!(sampler.reservoir.len(), (i + 1).min(100));
+        }
+        assert!(sampler.reservoir.capacity() <= 128);
+
+        let analysis = sampler.analyze("users");
+        assert_eq!(analysis.row_count, 50_000);
+        assert_eq!(analysis.sampled_rows, 100);
+    }
+}
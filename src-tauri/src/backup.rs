@@ -0,0 +1,657 @@
+//! Online backup and restore
+//!
+//! A backup is a logical copy of every key in one read-only snapshot, taken
+//! while the database stays open. It is written either as a directory
+//! holding `manifest.json` and `data.sdb`, or as a single `.tar.gz` archive
+//! of those two files. The manifest records the entry count and a SHA-256
+//! of the data file; restore checks both before writing anything. Both
+//! directions stream entries, so neither holds the database in memory.
+//!
+//! `data.sdb` starts with [`DATA_MAGIC`] followed by one record per entry:
+//! a little-endian `u32` key length, the key, a `u32` value length and the
+//! value.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sochdb::connection::EmbeddedConnection;
+
+use crate::connection::pool::Session;
+use crate::jobs::JobContext;
+use crate::util::now_millis;
+
+/// Version of the backup layout written by this build
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const DATA_FILE: &str = "data.sdb";
+const DATA_MAGIC: &[u8; 8] = b"SOCHBAK\0";
+
+/// A key and its value
+pub type Entry = (Vec<u8>, Vec<u8>);
+
+/// Entries between progress reports and cancellation checks
+const PROGRESS_INTERVAL: usize = 10_000;
+
+/// Entries written per transaction during restore
+const RESTORE_BATCH_SIZE: usize = 10_000;
+
+/// How a backup is laid out on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupFormat {
+    /// A directory with `manifest.json` and `data.sdb`
+    #[default]
+    Directory,
+    /// A single gzip-compressed tar archive of the same two files
+    Archive,
+}
+
+/// Description of a backup, stored alongside its data
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    /// Unix timestamp (ms) the snapshot was taken
+    pub created_at: u64,
+    /// Database the backup was taken from
+    pub source_path: String,
+    pub engine_version: Option<String>,
+    pub database_format_version: Option<u32>,
+    pub entry_count: u64,
+    /// Size of `data.sdb`
+    pub data_bytes: u64,
+    /// SHA-256 of `data.sdb`, hex encoded
+    pub data_sha256: String,
+}
+
+/// Outcome of a backup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupResult {
+    pub path: String,
+    pub format: BackupFormat,
+    pub manifest: BackupManifest,
+    /// Size of the backup on disk
+    pub size_bytes: u64,
+    pub duration_ms: f64,
+}
+
+/// Outcome of a restore
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreResult {
+    pub path: String,
+    pub manifest: BackupManifest,
+    pub entries_restored: u64,
+    /// Session the restored database was opened as, if requested
+    pub connection_id: Option<String>,
+    pub duration_ms: f64,
+}
+
+/// Back up `session`'s database to `target`
+pub fn backup(session: &Session, target: &Path, format: BackupFormat, ctx: &JobContext) -> Result<BackupResult, String> {
+    ensure_absent(target)?;
+    let kernel = session.embedded("backup")?.connection().kernel();
+    let info = kernel.database_info();
+    let start = Instant::now();
+
+    // Everything comes from one snapshot, so concurrent writes are either
+    // entirely in the backup or entirely out of it
+    ctx.progress(None, "Reading snapshot");
+    let created_at = now_millis();
+
+    let manifest = BackupManifest {
+        format_version: BACKUP_FORMAT_VERSION,
+        created_at,
+        source_path: session.path.clone(),
+        engine_version: Some(info.engine_version),
+        database_format_version: Some(info.format_version),
+        entry_count: 0,
+        data_bytes: 0,
+        data_sha256: String::new(),
+    };
+    // The snapshot stays open while entries are written out
+    let txn = kernel.begin_read_only_fast();
+    let result = (|| -> Result<BackupManifest, String> {
+        let entries = kernel
+            .scan_iter_unchecked(txn, b"")
+            .map_err(|e| format!("Snapshot scan failed: {}", e))?;
+        write_backup(
+            entries.map(|entry| entry.map_err(|e| format!("Snapshot scan failed: {}", e))),
+            manifest,
+            target,
+            format,
+            |written| {
+                ctx.progress(None, format!("Wrote {} entries", written));
+                !ctx.is_cancelled()
+            },
+        )
+    })();
+    kernel.abort_read_only_fast(txn);
+    let manifest = result?;
+
+    Ok(BackupResult {
+        path: target.display().to_string(),
+        format,
+        manifest,
+        size_bytes: disk_size(target)?,
+        duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+    })
+}
+
+/// Restore the backup at `source` into a new database at `target`
+pub fn restore(source: &Path, target: &Path, ctx: &JobContext) -> Result<(BackupManifest, u64), String> {
+    ensure_absent(target)?;
+
+    ctx.progress(None, "Validating backup");
+    let manifest = read_backup(source)?;
+
+    let result = with_data(source, |data| write_database(target, DataReader::new(data), manifest.entry_count, ctx));
+    if result.is_err() {
+        let _ = fs::remove_dir_all(target);
+    }
+    result.map(|restored| (manifest, restored))
+}
+
+/// Write entries into a fresh database, in batches of [`RESTORE_BATCH_SIZE`]
+fn write_database(target: &Path, mut entries: DataReader<impl Read>, total: u64, ctx: &JobContext) -> Result<u64, String> {
+    let conn = EmbeddedConnection::open(target)
+        .map_err(|e| format!("Failed to create database: {}", e))?;
+    let kernel = conn.kernel();
+
+    let mut restored = 0;
+    loop {
+        if ctx.is_cancelled() {
+            return Err(format!("Restore cancelled after {} of {} entries", restored, total));
+        }
+
+        let txn = kernel.begin_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        let mut batch = 0;
+        while batch < RESTORE_BATCH_SIZE {
+            let (key, value) = match entries.next_entry() {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(e) => {
+                    kernel.abort(txn);
+                    return Err(e);
+                }
+            };
+            if let Err(e) = kernel.put(txn, &key, &value) {
+                kernel.abort(txn);
+                return Err(format!("Failed to write {}: {}", String::from_utf8_lossy(&key), e));
+            }
+            batch += 1;
+        }
+        kernel.commit(txn).map_err(|e| format!("Failed to commit: {}", e))?;
+        if batch == 0 {
+            break;
+        }
+
+        restored += batch as u64;
+        ctx.progress(
+            Some(restored as f64 / total.max(1) as f64),
+            format!("Restored {} of {} entries", restored, total),
+        );
+    }
+
+    kernel.fsync().map_err(|e| format!("fsync failed: {}", e))?;
+    Ok(restored)
+}
+
+/// Write `entries` and their manifest to `target`.
+///
+/// The backup is assembled in a staging directory next to `target` and only
+/// moved into place once complete. `report` is called every
+/// [`PROGRESS_INTERVAL`] entries; returning `false` abandons the backup.
+fn write_backup(
+    entries: impl Iterator<Item = Result<Entry, String>>,
+    mut manifest: BackupManifest,
+    target: &Path,
+    format: BackupFormat,
+    report: impl Fn(usize) -> bool,
+) -> Result<BackupManifest, String> {
+    let staging = staging_path(target);
+    let _ = fs::remove_dir_all(&staging);
+    fs::create_dir_all(&staging)
+        .map_err(|e| format!("Failed to create {}: {}", staging.display(), e))?;
+
+    let result = (|| -> Result<BackupManifest, String> {
+        let (entry_count, data_bytes, data_sha256) = write_data(&staging.join(DATA_FILE), entries, report)?;
+        manifest.entry_count = entry_count;
+        manifest.data_bytes = data_bytes;
+        manifest.data_sha256 = data_sha256;
+
+        let json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+        fs::write(staging.join(MANIFEST_FILE), json)
+            .map_err(|e| format!("Failed to write manifest: {}", e))?;
+
+        match format {
+            BackupFormat::Directory => fs::rename(&staging, target)
+                .map_err(|e| format!("Failed to move backup into place: {}", e))?,
+            BackupFormat::Archive => write_archive(&staging, target)?,
+        }
+        Ok(manifest)
+    })();
+
+    let _ = fs::remove_dir_all(&staging);
+    if result.is_err() {
+        let _ = fs::remove_file(target);
+    }
+    result
+}
+
+/// Write the data file, returning its entry count, size and SHA-256
+fn write_data(
+    path: &Path,
+    entries: impl Iterator<Item = Result<Entry, String>>,
+    report: impl Fn(usize) -> bool,
+) -> Result<(u64, u64, String), String> {
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut out = HashingWriter::new(BufWriter::new(file));
+    let io_err = |e: io::Error| format!("Failed to write backup data: {}", e);
+
+    out.write_all(DATA_MAGIC).map_err(io_err)?;
+    let mut count = 0;
+    for entry in entries {
+        let (key, value) = entry?;
+        write_field(&mut out, &key).map_err(io_err)?;
+        write_field(&mut out, &value).map_err(io_err)?;
+        count += 1;
+        if count % PROGRESS_INTERVAL == 0 && !report(count) {
+            return Err(format!("Backup cancelled after {} entries", count));
+        }
+    }
+    report(count);
+
+    out.flush().map_err(io_err)?;
+    let (bytes, sha256) = out.finish();
+    Ok((count as u64, bytes, sha256))
+}
+
+fn write_field(out: &mut impl Write, field: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(field.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "entry larger than 4 GiB"))?;
+    out.write_all(&len.to_le_bytes())?;
+    out.write_all(field)
+}
+
+/// Pack the staging directory into a `.tar.gz` at `target`
+fn write_archive(staging: &Path, target: &Path) -> Result<(), String> {
+    let file = File::create(target).map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(BufWriter::new(file), Compression::default()));
+    for name in [MANIFEST_FILE, DATA_FILE] {
+        archive
+            .append_path_with_name(staging.join(name), name)
+            .map_err(|e| format!("Failed to archive {}: {}", name, e))?;
+    }
+    archive
+        .into_inner()
+        .and_then(|gz| gz.finish())
+        .and_then(|mut out| out.flush())
+        .map_err(|e| format!("Failed to write archive: {}", e))
+}
+
+/// Read and validate the backup at `source` (a directory or an archive).
+///
+/// The data is checked in one streaming pass; nothing is kept but the
+/// running checksum.
+pub fn read_backup(source: &Path) -> Result<BackupManifest, String> {
+    let manifest = read_manifest(source)?;
+    if manifest.format_version > BACKUP_FORMAT_VERSION {
+        return Err(format!(
+            "Backup format {} is newer than this version of Studio supports ({})",
+            manifest.format_version, BACKUP_FORMAT_VERSION
+        ));
+    }
+
+    let (decoded, data_bytes, data_sha256) = with_data(source, |data| {
+        let mut entries = DataReader::new(HashingReader::new(data));
+        let decoded = (|| -> Result<u64, String> {
+            let mut count = 0u64;
+            while entries.next_entry()?.is_some() {
+                count += 1;
+            }
+            Ok(count)
+        })();
+        // Hash whatever decoding stopped short of
+        let mut data = entries.into_inner();
+        io::copy(&mut data, &mut io::sink()).map_err(|e| format!("Failed to read backup data: {}", e))?;
+        let (bytes, sha256) = data.finish();
+        Ok((decoded, bytes, sha256))
+    })?;
+
+    if data_bytes != manifest.data_bytes {
+        return Err(format!(
+            "Backup data is {} bytes, manifest says {}",
+            data_bytes, manifest.data_bytes
+        ));
+    }
+    if data_sha256 != manifest.data_sha256 {
+        return Err("Backup data checksum does not match the manifest".to_string());
+    }
+    let entry_count = decoded?;
+    if entry_count != manifest.entry_count {
+        return Err(format!(
+            "Backup holds {} entries, manifest says {}",
+            entry_count, manifest.entry_count
+        ));
+    }
+    Ok(manifest)
+}
+
+fn read_manifest(source: &Path) -> Result<BackupManifest, String> {
+    let manifest = if source.is_dir() {
+        fs::read(source.join(MANIFEST_FILE)).map_err(|e| format!("Failed to read manifest: {}", e))?
+    } else {
+        let mut manifest = Vec::new();
+        with_archive_file(source, MANIFEST_FILE, |file| {
+            file.read_to_end(&mut manifest).map_err(|e| format!("Failed to read manifest: {}", e))
+        })?;
+        manifest
+    };
+    serde_json::from_slice(&manifest).map_err(|e| format!("Invalid manifest: {}", e))
+}
+
+/// Call `f` with a reader over the backup's data file
+fn with_data<T>(source: &Path, f: impl FnOnce(&mut dyn Read) -> Result<T, String>) -> Result<T, String> {
+    if source.is_dir() {
+        let path = source.join(DATA_FILE);
+        let file = File::open(&path).map_err(|e| format!("Failed to read backup data: {}", e))?;
+        f(&mut BufReader::new(file))
+    } else {
+        with_archive_file(source, DATA_FILE, f)
+    }
+}
+
+/// Call `f` with a reader over the file called `name` in the archive at
+/// `path`, decompressing only as far as that file
+fn with_archive_file<T>(
+    path: &Path,
+    name: &str,
+    f: impl FnOnce(&mut dyn Read) -> Result<T, String>,
+) -> Result<T, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(BufReader::new(file)));
+    let io_err = |e: io::Error| format!("Failed to read archive: {}", e);
+
+    for entry in archive.entries().map_err(io_err)? {
+        let mut entry = entry.map_err(io_err)?;
+        if entry.path().map_err(io_err)?.as_os_str() == name {
+            return f(&mut entry);
+        }
+    }
+    Err(format!("{} is missing from the archive", name))
+}
+
+/// Decodes `data.sdb` one entry at a time
+struct DataReader<R> {
+    inner: R,
+    started: bool,
+}
+
+impl<R: Read> DataReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, started: false }
+    }
+
+    /// The next entry, or `None` at the end of the data
+    fn next_entry(&mut self) -> Result<Option<Entry>, String> {
+        if !self.started {
+            let mut magic = [0; DATA_MAGIC.len()];
+            if read_up_to(&mut self.inner, &mut magic)? != magic.len() || &magic != DATA_MAGIC {
+                return Err("Backup data has an unknown format".to_string());
+            }
+            self.started = true;
+        }
+
+        let Some(key) = self.read_field(true)? else {
+            return Ok(None);
+        };
+        let value = self.read_field(false)?.ok_or_else(truncated)?;
+        Ok(Some((key, value)))
+    }
+
+    /// Read one length-prefixed field. With `at_boundary`, a clean end of
+    /// the data before the length is `None` rather than truncation.
+    fn read_field(&mut self, at_boundary: bool) -> Result<Option<Vec<u8>>, String> {
+        let mut len = [0; 4];
+        match read_up_to(&mut self.inner, &mut len)? {
+            0 if at_boundary => return Ok(None),
+            4 => {}
+            _ => return Err(truncated()),
+        }
+        let len = u32::from_le_bytes(len) as u64;
+
+        // Grown as data arrives, so a corrupt length cannot allocate 4 GiB up front
+        let mut field = Vec::new();
+        (&mut self.inner)
+            .take(len)
+            .read_to_end(&mut field)
+            .map_err(|e| format!("Failed to read backup data: {}", e))?;
+        if field.len() as u64 != len {
+            return Err(truncated());
+        }
+        Ok(Some(field))
+    }
+
+    fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// Fill as much of `buf` as the data allows, returning how much was read
+fn read_up_to(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize, String> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(format!("Failed to read backup data: {}", e)),
+        }
+    }
+    Ok(filled)
+}
+
+fn truncated() -> String {
+    "Backup data is truncated".to_string()
+}
+
+/// Refuse to overwrite anything at `path`
+fn ensure_absent(path: &Path) -> Result<(), String> {
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()));
+    }
+    Ok(())
+}
+
+fn staging_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    target.with_file_name(name)
+}
+
+/// Size of a file, or of the files directly inside a directory
+fn disk_size(path: &Path) -> Result<u64, String> {
+    let size = if path.is_dir() {
+        fs::read_dir(path)
+            .and_then(|entries| entries.map(|e| e?.metadata().map(|m| m.len())).sum())
+    } else {
+        fs::metadata(path).map(|m| m.len())
+    };
+    size.map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Writer that hashes and counts everything written through it
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, hasher: Sha256::new(), bytes: 0 }
+    }
+
+    /// Bytes written and their hex-encoded SHA-256
+    fn finish(self) -> (u64, String) {
+        (self.bytes, hex(&self.hasher.finalize()))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader that hashes and counts everything read through it
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, hasher: Sha256::new(), bytes: 0 }
+    }
+
+    /// Bytes read and their hex-encoded SHA-256
+    fn finish(self) -> (u64, String) {
+        (self.bytes, hex(&self.hasher.finalize()))
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.bytes += n as u64;
+        Ok(n)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<Entry> {
+        (0..25_000)
+            .map(|i| (format!("users/{}", i).into_bytes(), format!("{{\"n\":{}}}", i).into_bytes()))
+            .chain([(b"empty".to_vec(), Vec::new())])
+            .collect()
+    }
+
+    fn manifest() -> BackupManifest {
+        BackupManifest {
+            format_version: BACKUP_FORMAT_VERSION,
+            created_at: 1,
+            source_path: "/data/db".to_string(),
+            engine_version: Some("0.5.0".to_string()),
+            database_format_version: Some(1),
+            entry_count: 0,
+            data_bytes: 0,
+            data_sha256: String::new(),
+        }
+    }
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sochdb-studio-backup-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(target: &Path, format: BackupFormat) -> Result<BackupManifest, String> {
+        write_backup(entries().into_iter().map(Ok), manifest(), target, format, |_| true)
+    }
+
+    fn read_entries(source: &Path) -> Vec<Entry> {
+        with_data(source, |data| {
+            let mut reader = DataReader::new(data);
+            let mut entries = Vec::new();
+            while let Some(entry) = reader.next_entry()? {
+                entries.push(entry);
+            }
+            Ok(entries)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = scratch("round-trip");
+        for (name, format) in [("dir", BackupFormat::Directory), ("backup.tar.gz", BackupFormat::Archive)] {
+            let target = dir.join(name);
+            let written = write(&target, format).unwrap();
+            assert_eq!(written.entry_count, 25_001);
+            assert!(!staging_path(&target).exists());
+
+            assert_eq!(read_backup(&target).unwrap(), written);
+            assert_eq!(read_entries(&target), entries());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corruption_is_detected() {
+        let dir = scratch("corrupt");
+        let target = dir.join("dir");
+        write(&target, BackupFormat::Directory).unwrap();
+
+        let data_path = target.join(DATA_FILE);
+        let mut data = fs::read(&data_path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        fs::write(&data_path, &data).unwrap();
+        assert!(read_backup(&target).unwrap_err().contains("checksum"));
+
+        data.truncate(last);
+        fs::write(&data_path, &data).unwrap();
+        assert!(read_backup(&target).unwrap_err().contains("bytes"));
+
+        // A truncated file with a matching manifest still fails to decode
+        let mut manifest: BackupManifest = serde_json::from_slice(&fs::read(target.join(MANIFEST_FILE)).unwrap()).unwrap();
+        manifest.data_bytes = data.len() as u64;
+        manifest.data_sha256 = hex(&Sha256::digest(&data));
+        fs::write(target.join(MANIFEST_FILE), serde_json::to_vec(&manifest).unwrap()).unwrap();
+        assert!(read_backup(&target).unwrap_err().contains("truncated"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cancelled_backup_leaves_nothing_behind() {
+        let dir = scratch("cancel");
+        let target = dir.join("backup.tar.gz");
+        let result = write_backup(
+            entries().into_iter().map(Ok),
+            manifest(),
+            &target,
+            BackupFormat::Archive,
+            |written| written < 20_000,
+        );
+
+        assert!(result.unwrap_err().contains("cancelled"));
+        assert!(!target.exists());
+        assert!(!staging_path(&target).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,185 +2,408 @@
 
 use serde::{Deserialize, Serialize};
 use tauri::State;
-use std::sync::Arc;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
+use crate::backup::BackupFormat;
+use crate::connection::backend::{DatabaseMetadata, DiskUsage};
+use crate::connection::manager::ConnectionType;
+use crate::connection::pool::SessionInfo;
+use crate::jobs::Job;
+use crate::maintenance::MaintenanceTask;
+use crate::metrics::{self, MetricsPoint};
 use crate::state::AppState;
+use crate::stats::{CachedStats, Confidence, PrefixNode, DEFAULT_PREFIX_DEPTH};
+use crate::util::now_millis;
 
 /// Database statistics
+///
+/// Fields the backend cannot report are `null` rather than zero.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseStats {
-    pub memtable_size_bytes: usize,
-    pub wal_size_bytes: usize,
-    pub total_tables: usize,
-    pub total_rows: usize,
-    pub active_transactions: usize,
-    pub last_checkpoint_lsn: u64,
+    pub memtable_size_bytes: Option<u64>,
+    pub wal_size_bytes: Option<u64>,
+    /// Collection and key counts from the cached scan (`null` until the
+    /// first background scan finishes)
+    pub total_tables: Option<usize>,
+    pub total_rows: Option<u64>,
+    /// Unix timestamp (ms) the cached counts were computed
+    pub counts_computed_at: Option<u64>,
+    /// A background scan is updating the counts
+    pub counts_refreshing: bool,
+    pub active_transactions: Option<u64>,
+    /// LSN of the most recent WAL checkpoint (`null` if none)
+    pub last_checkpoint_lsn: Option<u64>,
+    /// Unix timestamp (ms) of the most recent WAL checkpoint
+    pub last_checkpoint_at: Option<u64>,
     pub uptime_seconds: u64,
     pub version: String,
-    pub active_snapshots: usize,
-    pub min_active_timestamp: u64,
-    pub garbage_versions: usize,
+    pub active_snapshots: Option<u64>,
+    pub min_active_timestamp: Option<u64>,
+    pub garbage_versions: Option<u64>,
+}
+
+/// Disk and logical space usage of a database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageBreakdown {
+    /// Bytes on disk by component (`null` for remote connections)
+    pub disk: Option<DiskUsage>,
+    /// Logical size (keys plus values) by key prefix, from the cached
+    /// collection counts (`null` until they are first computed)
+    pub prefixes: Option<PrefixNode>,
+    /// How far the prefix sizes can be trusted
+    pub confidence: Option<Confidence>,
+    /// Unix timestamp (ms) the prefix sizes were computed
+    pub computed_at: Option<u64>,
+    /// A background scan is updating the prefix sizes
+    pub refreshing: bool,
 }
 
 /// Connection information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectionInfo {
+    pub connection_id: String,
     pub path: String,
+    pub connection_type: ConnectionType,
     pub connected: bool,
+    pub read_only: bool,
+    /// Storage engine version, or "unknown" if the backend cannot report it
     pub version: String,
     pub uptime_seconds: u64,
+    #[serde(flatten)]
+    pub metadata: DatabaseMetadata,
 }
 
 /// Connect to a SochDB database
+///
+/// Opens a new session alongside any existing ones and makes it active.
+/// The session is keyed by `connection_id`, or by the path if none is given.
+/// Connecting with a saved connection's id records its "last opened" time.
+/// `connection_type` defaults to a local file; for `tcpip` the path is the
+/// server's `host:port` and for `unixsocket` the socket path.
 #[tauri::command]
 pub async fn connect(
+    app: tauri::AppHandle,
     state: State<'_, Arc<AppState>>,
     path: String,
+    connection_id: Option<String>,
+    connection_type: Option<ConnectionType>,
 ) -> Result<ConnectionInfo, String> {
-    let path_buf = PathBuf::from(&path);
+    open_session(&app, &state, path, connection_id, connection_type, false).await
+}
+
+/// Connect to a SochDB database in read-only mode
+///
+/// The database is opened without taking the writer lock, so a database
+/// that a live service is writing to can be inspected. Mutating MCP tools,
+/// SQL writes and admin operations are refused on this session.
+#[tauri::command]
+pub async fn connect_read_only(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    path: String,
+    connection_id: Option<String>,
+    connection_type: Option<ConnectionType>,
+) -> Result<ConnectionInfo, String> {
+    open_session(&app, &state, path, connection_id, connection_type, true).await
+}
+
+async fn open_session(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    path: String,
+    connection_id: Option<String>,
+    connection_type: Option<ConnectionType>,
+    read_only: bool,
+) -> Result<ConnectionInfo, String> {
+    let id = connection_id.unwrap_or_else(|| path.clone());
+    let connection_type = connection_type.unwrap_or(ConnectionType::LocalFile);
     
     // Use unified AppState connect
-    state.connect(path_buf).await?;
-    let uptime_seconds = state.uptime_seconds().await;
+    let session = state.connect(id, connection_type, path, read_only).await?;
+    if let Err(e) = crate::commands::connections::mark_opened(app, state, &session.id).await {
+        eprintln!("Failed to record last opened time for {}: {}", session.id, e);
+    }
+
+    let metadata = session.backend.metadata().await.unwrap_or_else(|e| {
+        eprintln!("Failed to read database metadata for {}: {}", session.id, e);
+        DatabaseMetadata::default()
+    });
 
     Ok(ConnectionInfo {
-        path,
+        connection_id: session.id.clone(),
+        path: session.path.clone(),
+        connection_type,
         connected: true,
-        version: "0.1.0".to_string(),
-        uptime_seconds,
+        read_only,
+        version: metadata.engine_version.clone().unwrap_or_else(|| "unknown".to_string()),
+        uptime_seconds: session.uptime_seconds(),
+        metadata,
     })
 }
 
-/// Disconnect from the current database
+/// Disconnect a session (the active one if no id is given)
 #[tauri::command]
-pub async fn disconnect(state: State<'_, Arc<AppState>>) -> Result<(), String> {
-    state.disconnect().await;
+pub async fn disconnect(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+) -> Result<(), String> {
+    state.disconnect(connection_id.as_deref()).await;
+    Ok(())
+}
+
+/// List open database sessions
+#[tauri::command]
+pub async fn list_sessions(state: State<'_, Arc<AppState>>) -> Result<Vec<SessionInfo>, String> {
+    Ok(state.list_sessions().await)
+}
+
+/// Make an open session the active one
+#[tauri::command]
+pub async fn switch_session(
+    state: State<'_, Arc<AppState>>,
+    connection_id: String,
+) -> Result<(), String> {
+    state.switch_session(&connection_id).await
+}
+
+/// Close a specific session
+#[tauri::command]
+pub async fn close_session(
+    state: State<'_, Arc<AppState>>,
+    connection_id: String,
+) -> Result<(), String> {
+    state.session(Some(&connection_id)).await?;
+    state.disconnect(Some(&connection_id)).await;
     Ok(())
 }
 
 /// Get current database statistics
 #[tauri::command]
-pub async fn get_stats(state: State<'_, Arc<AppState>>) -> Result<DatabaseStats, String> {
-    let status = state.get_status().await;
-    let uptime_seconds = state.uptime_seconds().await;
-    
-    if !status.db_connected {
-        return Err("No active connection".to_string());
-    }
-    
-    // Get real stats from MCP server's database connection
-    let mcp_lock = state.mcp_server.read().await;
-    let mcp = mcp_lock.as_ref()
-        .ok_or_else(|| "MCP server not initialized".to_string())?;
+pub async fn get_stats(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+) -> Result<DatabaseStats, String> {
+    let session = state.session(connection_id.as_deref()).await?;
     
-    let db_stats = mcp.db_stats();
-    let conn = mcp.connection();
+    let stats = session.backend.stats().await?;
+    let last_checkpoint = session.backend.last_checkpoint().await?;
     
-    // Count collections from a true root scan. A normal "/" prefix scan is
-    // rejected by the storage layer's minimum-prefix safety check.
-    let kernel = conn.kernel();
-    let txn = kernel.begin_read_only_fast();
-    let scan_result = kernel.scan_unchecked(txn, b"").unwrap_or_default();
-    let mut table_set = std::collections::HashSet::new();
-    let mut total_rows = 0usize;
-    for (key, _) in &scan_result {
-        let path = String::from_utf8_lossy(key);
-        let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
-        if let Some(first) = parts.first() {
-            if !first.is_empty() {
-                table_set.insert(first.to_string());
-            }
-        }
-        total_rows += 1;
-    }
-    kernel.abort_read_only_fast(txn);
+    // Counting collections scans the whole database, so serve cached counts
+    let counts = state.collection_stats(&session).await;
+    let snapshot = counts.snapshot.as_ref();
     
     Ok(DatabaseStats {
-        memtable_size_bytes: db_stats.bytes_written as usize,
-        wal_size_bytes: db_stats.bytes_read as usize,
-        total_tables: table_set.len(),
-        total_rows,
-        active_transactions: db_stats.transactions_started.saturating_sub(
-            db_stats.transactions_committed + db_stats.transactions_aborted
-        ) as usize,
-        last_checkpoint_lsn: 0, // Not exposed in Stats yet
-        uptime_seconds,
+        memtable_size_bytes: stats.memtable_size_bytes,
+        wal_size_bytes: stats.wal_size_bytes,
+        total_tables: snapshot.map(|s| s.collections.len()),
+        total_rows: snapshot.map(|s| s.total_keys),
+        counts_computed_at: snapshot.map(|s| s.computed_at),
+        counts_refreshing: counts.refreshing,
+        active_transactions: stats.active_transactions(),
+        last_checkpoint_lsn: last_checkpoint.map(|c| c.lsn),
+        last_checkpoint_at: last_checkpoint.map(|c| c.completed_at),
+        uptime_seconds: session.uptime_seconds(),
         version: env!("CARGO_PKG_VERSION").to_string(),
-        active_snapshots: 0, // TODO: Expose from MVCC
-        min_active_timestamp: 0, // TODO: Expose from MVCC
-        garbage_versions: 0, // TODO: Expose from GC
+        active_snapshots: stats.active_snapshots,
+        min_active_timestamp: stats.min_active_timestamp,
+        garbage_versions: stats.garbage_versions,
     })
 }
 
-/// Force a WAL checkpoint
+/// Get cached per-collection key counts and sizes
+///
+/// With `refresh`, a background rescan is started even if the cache is fresh.
 #[tauri::command]
-pub async fn checkpoint(state: State<'_, Arc<AppState>>) -> Result<u64, String> {
-    let status = state.get_status().await;
-    if !status.db_connected {
-        return Err("No active connection".to_string());
+pub async fn get_collection_stats(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+    refresh: Option<bool>,
+) -> Result<CachedStats, String> {
+    let session = state.session(connection_id.as_deref()).await?;
+    if refresh.unwrap_or(false) {
+        state.refresh_collection_stats(session.clone()).await;
     }
-    
-    // Get connection from MCP server and force checkpoint
-    let mcp_lock = state.mcp_server.read().await;
-    let mcp = mcp_lock.as_ref()
-        .ok_or_else(|| "MCP server not initialized".to_string())?;
-    
-    // Force sync - this ensures all WAL entries are flushed
-    mcp.connection().kernel().fsync()
-        .map_err(|e| format!("Checkpoint failed: {}", e))?;
-    
-    // Return the current LSN (bytes written as proxy)
-    let stats = mcp.db_stats();
-    Ok(stats.bytes_written)
+    Ok(state.collection_stats(&session).await)
 }
 
-/// Run garbage collection
+/// Get chart points for a connection's recent metrics
+///
+/// Covers the last `range` seconds (default 15 minutes, up to 6 hours) with
+/// one point per `resolution` seconds (default and minimum 5): byte and
+/// transaction rates, abort ratio, and memtable, WAL and open-transaction
+/// gauges.
 #[tauri::command]
-pub async fn gc(state: State<'_, Arc<AppState>>) -> Result<usize, String> {
-    let status = state.get_status().await;
-    if !status.db_connected {
-        return Err("No active connection".to_string());
-    }
-    
-    // Get connection from MCP server and run GC
-    let mcp_lock = state.mcp_server.read().await;
-    let mcp = mcp_lock.as_ref()
-        .ok_or_else(|| "MCP server not initialized".to_string())?;
-    
-    let reclaimed = mcp.connection().gc();
-    Ok(reclaimed)
+pub async fn get_metrics_history(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+    range: Option<u64>,
+    resolution: Option<u64>,
+) -> Result<Vec<MetricsPoint>, String> {
+    let session = state.session(connection_id.as_deref()).await?;
+    let range = range.map(Duration::from_secs).unwrap_or(metrics::DEFAULT_RANGE);
+    let resolution = resolution.map(Duration::from_secs).unwrap_or(metrics::SAMPLE_INTERVAL);
+    Ok(state.metrics.read().await.points(&session.id, now_millis(), range, resolution))
 }
 
-/// Analyze table statistics
-#[allow(dead_code)]
+/// Get bytes on disk per storage component and logical size per key prefix
+///
+/// The prefix tree goes `max_depth` levels deep (default and maximum 3).
+/// It is built alongside the cached collection counts, so it is served from
+/// the cache and refreshed in the background like them.
 #[tauri::command]
-pub async fn analyze() -> Result<(), String> {
-    Ok(())
+pub async fn get_storage_breakdown(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+    max_depth: Option<usize>,
+) -> Result<StorageBreakdown, String> {
+    let session = state.session(connection_id.as_deref()).await?;
+    let disk = session.backend.disk_usage().await?;
+
+    let counts = state.collection_stats(&session).await;
+    let snapshot = counts.snapshot.as_ref();
+    let max_depth = max_depth.unwrap_or(DEFAULT_PREFIX_DEPTH).min(DEFAULT_PREFIX_DEPTH);
+
+    Ok(StorageBreakdown {
+        disk,
+        prefixes: snapshot.map(|s| s.prefixes.truncated(max_depth)),
+        confidence: snapshot.map(|s| s.confidence),
+        computed_at: snapshot.map(|s| s.computed_at),
+        refreshing: counts.refreshing,
+    })
 }
 
-/// Compact SST files
+/// Start a WAL checkpoint job
+///
+/// Flushes the memtable to disk and truncates the WAL segments the flushed
+/// data makes redundant. The job's result is a `CheckpointResult`.
 #[tauri::command]
-pub async fn compact(state: State<'_, Arc<AppState>>) -> Result<(), String> {
-    let status = state.get_status().await;
-    if !status.db_connected {
-        return Err("No active connection".to_string());
-    }
-    
-    // Get connection from MCP server
-    let mcp_lock = state.mcp_server.read().await;
-    let mcp = mcp_lock.as_ref()
-        .ok_or_else(|| "MCP server not initialized".to_string())?;
-    
-    // Run GC which cleans up old versions (compaction is automatic via LSM)
-    let _ = mcp.connection().gc();
-    
-    // Force sync to persist any changes
-    mcp.connection().kernel().fsync()
-        .map_err(|e| format!("Compact sync failed: {}", e))?;
-    
-    Ok(())
+pub async fn checkpoint(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+) -> Result<Job, String> {
+    state.submit_maintenance(connection_id.as_deref(), MaintenanceTask::Checkpoint).await
 }
+
+/// Start a garbage collection job
+///
+/// The job's result is a `GcResult` with the number of versions reclaimed.
+#[tauri::command]
+pub async fn gc(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+) -> Result<Job, String> {
+    state.submit_maintenance(connection_id.as_deref(), MaintenanceTask::Gc).await
+}
+
+/// Start a job analyzing collection statistics
+///
+/// Samples each collection (or only `collections`) and stores column
+/// cardinality, null fractions, histograms, value sizes and key-prefix
+/// distribution under the reserved stats prefix. The job's result is the
+/// list of `CollectionAnalysis` results.
+#[tauri::command]
+pub async fn analyze(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+    collections: Option<Vec<String>>,
+    sample_size: Option<usize>,
+) -> Result<Job, String> {
+    let task = MaintenanceTask::Analyze { collections, sample_size };
+    state.submit_maintenance(connection_id.as_deref(), task).await
+}
+
+/// Start a manual LSM compaction job
+///
+/// Compacts the whole database, or only the keys in `[start_key, end_key)`
+/// and/or the SST files on `level`, rewriting live versions and dropping
+/// deleted and superseded ones. The job's result is a `CompactionResult`.
+#[tauri::command]
+pub async fn compact(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+    start_key: Option<String>,
+    end_key: Option<String>,
+    level: Option<u32>,
+) -> Result<Job, String> {
+    let task = MaintenanceTask::Compact { start_key, end_key, level };
+    state.submit_maintenance(connection_id.as_deref(), task).await
+}
+
+/// Start a job backing up a database
+///
+/// Copies every key from one read-only snapshot to `target_path` while the
+/// database stays open, as a directory (the default) or a single `.tar.gz`
+/// archive, with a manifest holding the entry count and a SHA-256 of the
+/// data. The job's result is a `BackupResult`.
+#[tauri::command]
+pub async fn backup_database(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+    target_path: String,
+    format: Option<BackupFormat>,
+) -> Result<Job, String> {
+    state
+        .submit_backup(connection_id.as_deref(), PathBuf::from(target_path), format.unwrap_or_default())
+        .await
+}
+
+/// Start a job restoring a backup into a new database
+///
+/// The backup is validated against its manifest before anything is written.
+/// `target_path` must not exist yet. With `connect`, the restored database is
+/// opened as a session (under `connection_id`, or its path). The job's result
+/// is a `RestoreResult`.
+#[tauri::command]
+pub async fn restore_database(
+    state: State<'_, Arc<AppState>>,
+    backup_path: String,
+    target_path: String,
+    connect: Option<bool>,
+    connection_id: Option<String>,
+) -> Result<Job, String> {
+    let connect_as = connect
+        .unwrap_or(false)
+        .then(|| connection_id.unwrap_or_else(|| target_path.clone()));
+    state.submit_restore(PathBuf::from(backup_path), PathBuf::from(target_path), connect_as)
+}
+
+/// Start a job checking a database for corruption
+///
+/// Reads every WAL segment and SST file, validating checksums, LSN and key
+/// order, and checks that every index entry points to an existing row. The
+/// job's result is a `VerifyReport` listing each problem with its severity,
+/// file and offset.
+#[tauri::command]
+pub async fn verify_database(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+) -> Result<Job, String> {
+    state.submit_verify(connection_id.as_deref()).await
+}
+
+/// List admin jobs, newest first
+#[tauri::command]
+pub async fn list_jobs(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+) -> Result<Vec<Job>, String> {
+    Ok(state.jobs.list(connection_id.as_deref()))
+}
+
+/// Get an admin job by id
+#[tauri::command]
+pub async fn get_job(state: State<'_, Arc<AppState>>, job_id: String) -> Result<Job, String> {
+    state.jobs.get(&job_id).ok_or_else(|| format!("Unknown job: {}", job_id))
+}
+
+/// Request cancellation of a running admin job
+#[tauri::command]
+pub async fn cancel_job(state: State<'_, Arc<AppState>>, job_id: String) -> Result<Job, String> {
+    state.jobs.cancel(&job_id)
+}
+
 /// Get the current command policy
 #[tauri::command]
 pub async fn get_policy(state: State<'_, Arc<AppState>>) -> Result<crate::policy::CommandPolicy, String> {
@@ -197,19 +420,33 @@ pub async fn set_policy(
     Ok(())
 }
 
+/// Get the policy in effect for the active session.
+///
+/// A read-only session forces `ConnectionMode::ReadOnly` regardless of the
+/// configured mode.
+async fn effective_policy(state: &AppState) -> crate::policy::CommandPolicy {
+    let mut policy = state.get_policy().await;
+    if let Ok(session) = state.session(None).await {
+        if session.read_only {
+            policy.mode = crate::policy::ConnectionMode::ReadOnly;
+        }
+    }
+    policy
+}
+
 /// Check if a command is allowed under current policy
 #[tauri::command]
 pub async fn check_command(
     state: State<'_, Arc<AppState>>,
     command: String,
 ) -> Result<crate::policy::PolicyCheck, String> {
-    let policy = state.get_policy().await;
+    let policy = effective_policy(&state).await;
     Ok(crate::policy::check_command(&policy, &command))
 }
 
 /// Get list of commands allowed under current policy
 #[tauri::command]
 pub async fn get_allowed_commands(state: State<'_, Arc<AppState>>) -> Result<Vec<String>, String> {
-    let policy = state.get_policy().await;
+    let policy = effective_policy(&state).await;
     Ok(policy.get_allowed_commands().iter().map(|s| s.to_string()).collect())
 }
@@ -0,0 +1,266 @@
+//! Saved connection commands
+//!
+//! Saved connections are persisted through `tauri-plugin-store` and mirrored
+//! in the `ConnectionManager` so the sidebar survives restarts.
+
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+
+use crate::connection::manager::{ConnectionManager, ConnectionType, SavedConnection};
+use crate::state::AppState;
+use crate::util::now_millis;
+
+const STORE_FILE: &str = "connections.json";
+
+/// Fields a user can set on a saved connection
+#[derive(Debug, Deserialize)]
+pub struct SaveConnectionRequest {
+    pub name: String,
+    pub path: String,
+    pub connection_type: ConnectionType,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Queries running longer than this many ms are cancelled
+    #[serde(default)]
+    pub statement_timeout_ms: Option<u64>,
+}
+
+/// Load saved connections from the store
+pub fn load_saved_connections(app: &tauri::AppHandle) -> Result<Vec<SavedConnection>, String> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(STORE_FILE)
+        .map_err(|e| format!("Failed to open store: {}", e))?;
+
+    match store.get("connections") {
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|e| format!("Failed to parse saved connections: {}", e)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Saved connections and their revision, copied under the manager lock
+type Snapshot = (Vec<SavedConnection>, u64);
+
+fn snapshot(manager: &ConnectionManager) -> Snapshot {
+    (manager.saved_connections().to_vec(), manager.saved_revision())
+}
+
+/// Revision of the last saved connections written to the store
+static SAVED_REVISION: parking_lot::Mutex<u64> = parking_lot::const_mutex(0);
+
+/// Write saved connections to the store, off the async runtime.
+///
+/// Callers save a copy after releasing the manager lock, which every session
+/// lookup takes, so copies can arrive out of order; one older than the last
+/// write is skipped.
+async fn persist(app: &tauri::AppHandle, (connections, revision): Snapshot) -> Result<(), String> {
+    let app = app.clone();
+    tokio::task::spawn_blocking(move || {
+        use tauri_plugin_store::StoreExt;
+
+        let mut saved = SAVED_REVISION.lock();
+        if revision <= *saved {
+            return Ok(());
+        }
+
+        let store = app.store(STORE_FILE)
+            .map_err(|e| format!("Failed to open store: {}", e))?;
+
+        store.set("connections", json!(connections));
+        store.save().map_err(|e| format!("Failed to save: {}", e))?;
+        *saved = revision;
+
+        Ok(())
+    })
+    .await
+    .map_err(|e| format!("Failed to save: {}", e))?
+}
+
+/// Record that a saved connection was opened, if `id` is one
+pub async fn mark_opened(app: &tauri::AppHandle, state: &AppState, id: &str) -> Result<(), String> {
+    let snapshot = {
+        let mut manager = state.connections.write().await;
+        if !manager.touch_saved_connection(id, now_millis()) {
+            return Ok(());
+        }
+        snapshot(&manager)
+    };
+    persist(app, snapshot).await
+}
+
+/// Generate an id that is not yet used by any saved connection
+fn next_id(manager: &ConnectionManager) -> String {
+    let base = format!("conn-{}", now_millis());
+    let mut id = base.clone();
+    let mut n = 1;
+    while manager.saved_connection(&id).is_some() {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    id
+}
+
+/// List saved connections in sidebar order
+#[tauri::command]
+pub async fn list_saved_connections(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<SavedConnection>, String> {
+    Ok(state.connections.read().await.saved_connections().to_vec())
+}
+
+/// Add a saved connection
+#[tauri::command]
+pub async fn add_saved_connection(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    request: SaveConnectionRequest,
+) -> Result<SavedConnection, String> {
+    let mut manager = state.connections.write().await;
+    let conn = SavedConnection {
+        id: next_id(&manager),
+        name: request.name,
+        path: request.path,
+        connection_type: request.connection_type,
+        group: request.group,
+        tags: request.tags,
+        color: request.color,
+        last_opened_at: None,
+        statement_timeout_ms: request.statement_timeout_ms,
+    };
+    manager.add_saved_connection(conn.clone())?;
+    let snapshot = snapshot(&manager);
+    drop(manager);
+    persist(&app, snapshot).await?;
+    Ok(conn)
+}
+
+/// Edit a saved connection
+#[tauri::command]
+pub async fn update_saved_connection(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    request: SaveConnectionRequest,
+) -> Result<SavedConnection, String> {
+    let mut manager = state.connections.write().await;
+    let last_opened_at = manager
+        .saved_connection(&id)
+        .ok_or_else(|| format!("Unknown saved connection: {}", id))?
+        .last_opened_at;
+    let conn = SavedConnection {
+        id,
+        name: request.name,
+        path: request.path,
+        connection_type: request.connection_type,
+        group: request.group,
+        tags: request.tags,
+        color: request.color,
+        last_opened_at,
+        statement_timeout_ms: request.statement_timeout_ms,
+    };
+    manager.update_saved_connection(conn.clone())?;
+    let snapshot = snapshot(&manager);
+    drop(manager);
+    persist(&app, snapshot).await?;
+    Ok(conn)
+}
+
+/// Delete a saved connection
+#[tauri::command]
+pub async fn delete_saved_connection(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<(), String> {
+    let snapshot = {
+        let mut manager = state.connections.write().await;
+        manager.remove_saved_connection(&id);
+        snapshot(&manager)
+    };
+    persist(&app, snapshot).await
+}
+
+/// Duplicate a saved connection
+#[tauri::command]
+pub async fn duplicate_saved_connection(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<SavedConnection, String> {
+    let (copy, snapshot) = {
+        let mut manager = state.connections.write().await;
+        let new_id = next_id(&manager);
+        let copy = manager.duplicate_saved_connection(&id, new_id)?;
+        (copy, snapshot(&manager))
+    };
+    persist(&app, snapshot).await?;
+    Ok(copy)
+}
+
+/// Reorder saved connections
+#[tauri::command]
+pub async fn reorder_saved_connections(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    ids: Vec<String>,
+) -> Result<(), String> {
+    let snapshot = {
+        let mut manager = state.connections.write().await;
+        manager.reorder_saved_connections(&ids)?;
+        snapshot(&manager)
+    };
+    persist(&app, snapshot).await
+}
+
+/// Move saved connections into a group (or out of any group)
+#[tauri::command]
+pub async fn group_saved_connections(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    ids: Vec<String>,
+    group: Option<String>,
+) -> Result<(), String> {
+    let group = group.filter(|g| !g.trim().is_empty());
+    let snapshot = {
+        let mut manager = state.connections.write().await;
+        manager.set_group(&ids, group)?;
+        snapshot(&manager)
+    };
+    persist(&app, snapshot).await
+}
+
+/// Set the statement timeout of an open session and, if it is a saved
+/// connection, of future sessions too. `None` removes the timeout.
+#[tauri::command]
+pub async fn set_statement_timeout(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    connection_id: String,
+    timeout_ms: Option<u64>,
+) -> Result<(), String> {
+    if timeout_ms == Some(0) {
+        return Err("Statement timeout must be at least 1 ms".to_string());
+    }
+
+    let mut manager = state.connections.write().await;
+    let session = manager.pool().get(&connection_id).await;
+    if let Some(session) = &session {
+        session.set_statement_timeout(timeout_ms.map(Duration::from_millis));
+    }
+    if manager.set_statement_timeout(&connection_id, timeout_ms) {
+        let snapshot = snapshot(&manager);
+        drop(manager);
+        persist(&app, snapshot).await?;
+    } else if session.is_none() {
+        return Err(format!("Unknown connection: {}", connection_id));
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+//! Cursor commands for large query results
+//!
+//! `open_cursor` starts a query and returns its columns; rows are then
+//! pulled with `fetch_cursor` or pushed over a channel by `stream_cursor`.
+//! Reads that cannot stream off key scans need a LIMIT.
+//! A cursor is closed once its last batch is handed out.
+
+use std::sync::Arc;
+use tauri::ipc::Channel;
+use tauri::State;
+
+use crate::commands::query;
+use crate::cursors::{Cursor, CursorBatch, CursorInfo, DEFAULT_BATCH_SIZE, MAX_ENGINE_ROWS};
+use crate::parser::sql;
+use crate::planner::{self, Catalog};
+use crate::state::AppState;
+
+/// Open a cursor over a read query's result
+#[tauri::command]
+pub async fn open_cursor(
+    state: State<'_, Arc<AppState>>,
+    query: String,
+    connection_id: Option<String>,
+) -> Result<CursorInfo, String> {
+    let session = state.session(connection_id.as_deref()).await?;
+    if !sql::parameter_names(&query).is_empty() {
+        return Err("Bind the query's parameters before opening a cursor".to_string());
+    }
+
+    // Statements Studio cannot plan have no known bound and are refused below
+    let plan = planner::plan(&query, &Catalog::default()).ok();
+    if plan.as_ref().is_some_and(|p| !p.is_read()) {
+        return Err("Cursors only run read statements; use execute_query for writes".to_string());
+    }
+
+    let _script = session.statement_guard()?;
+    let id = state.cursors.read().await.next_id();
+    let cursor = match plan.as_ref().and_then(|p| p.scan_spec()) {
+        Some(spec) => {
+            let open = Cursor::scan(id, session.clone(), query.clone(), spec);
+            state.running_queries.run(&session.id, &query, session.statement_timeout(), open).await?
+        }
+        None => {
+            // The engine answers in one response, so only bounded reads are paged
+            if !plan.as_ref().and_then(|p| p.row_limit()).is_some_and(|n| n <= MAX_ENGINE_ROWS) {
+                return Err(format!(
+                    "Only plain scans stream through a cursor; add a LIMIT of at most {} or use execute_query",
+                    MAX_ENGINE_ROWS
+                ));
+            }
+            let result = query::run_statement(&state, &session, &query).await?;
+            if result.rows.len() > MAX_ENGINE_ROWS {
+                return Err(format!("The query returned more than {} rows", MAX_ENGINE_ROWS));
+            }
+            Cursor::rows(id, session.id.clone(), query, result.columns, result.rows)
+        }
+    };
+    let info = cursor.info().clone();
+    state.cursors.write().await.insert(cursor)?;
+    Ok(info)
+}
+
+/// Fetch the next `count` rows of a cursor
+#[tauri::command]
+pub async fn fetch_cursor(
+    state: State<'_, Arc<AppState>>,
+    cursor_id: String,
+    count: Option<usize>,
+) -> Result<CursorBatch, String> {
+    let cursor = state.cursors.read().await.get(&cursor_id)?;
+    let batch = fetch(&state, &mut *cursor.lock().await, count.unwrap_or(DEFAULT_BATCH_SIZE)).await;
+    finish(&state, &cursor_id, batch).await
+}
+
+/// Send a cursor's remaining rows over `on_batch`, `batch_size` at a time.
+///
+/// Returns the number of rows sent once the last batch is delivered.
+#[tauri::command]
+pub async fn stream_cursor(
+    state: State<'_, Arc<AppState>>,
+    cursor_id: String,
+    batch_size: Option<usize>,
+    on_batch: Channel<CursorBatch>,
+) -> Result<u64, String> {
+    let cursor = state.cursors.read().await.get(&cursor_id)?;
+    let mut cursor = cursor.lock().await;
+    loop {
+        let batch = fetch(&state, &mut cursor, batch_size.unwrap_or(DEFAULT_BATCH_SIZE)).await;
+        let batch = finish(&state, &cursor_id, batch).await?;
+        let (fetched, done) = (batch.fetched, batch.done);
+        if let Err(e) = on_batch.send(batch) {
+            let _ = state.cursors.write().await.remove(&cursor_id);
+            return Err(format!("Failed to send rows: {}", e));
+        }
+        if done {
+            return Ok(fetched);
+        }
+    }
+}
+
+/// Close a cursor before reading all of it
+#[tauri::command]
+pub async fn close_cursor(state: State<'_, Arc<AppState>>, cursor_id: String) -> Result<(), String> {
+    state.cursors.write().await.remove(&cursor_id)
+}
+
+/// Read the next batch as a listed query, so a slow scan can be cancelled
+/// or time out. A stopped fetch fails the cursor.
+async fn fetch(state: &AppState, cursor: &mut Cursor, count: usize) -> Result<CursorBatch, String> {
+    let info = cursor.info().clone();
+    let session = state.session(Some(&info.connection_id)).await?;
+    let _script = session.statement_guard()?;
+    state
+        .running_queries
+        .run(&info.connection_id, &info.query, session.statement_timeout(), cursor.fetch(count))
+        .await
+}
+
+/// Drop the cursor once it is exhausted or has failed
+async fn finish(
+    state: &AppState,
+    cursor_id: &str,
+    batch: Result<CursorBatch, String>,
+) -> Result<CursorBatch, String> {
+    if !batch.as_ref().is_ok_and(|b| !b.done) {
+        let _ = state.cursors.write().await.remove(cursor_id);
+    }
+    batch
+}
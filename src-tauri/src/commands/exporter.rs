@@ -0,0 +1,57 @@
+//! Metrics endpoint commands
+//!
+//! The endpoint's settings are persisted through `tauri-plugin-store`; it
+//! is restarted with them on launch if it was enabled.
+
+use serde_json::json;
+use std::sync::Arc;
+use tauri::State;
+
+use crate::exporter::{ExporterConfig, ExporterStatus};
+use crate::state::AppState;
+
+const STORE_FILE: &str = "exporter.json";
+
+/// Load the metrics endpoint settings from the store
+pub fn load_exporter_config(app: &tauri::AppHandle) -> Result<ExporterConfig, String> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(STORE_FILE)
+        .map_err(|e| format!("Failed to open store: {}", e))?;
+
+    match store.get("config") {
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|e| format!("Failed to parse metrics endpoint config: {}", e)),
+        None => Ok(ExporterConfig::default()),
+    }
+}
+
+/// Get the metrics endpoint settings and scrape URL
+#[tauri::command]
+pub async fn get_exporter_status(
+    state: State<'_, Arc<AppState>>,
+) -> Result<ExporterStatus, String> {
+    Ok(state.exporter.status())
+}
+
+/// Enable, disable or move the metrics endpoint
+///
+/// The endpoint only listens on 127.0.0.1. Settings are saved only once the
+/// endpoint has started (or stopped) successfully.
+#[tauri::command]
+pub async fn set_exporter_config(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    config: ExporterConfig,
+) -> Result<ExporterStatus, String> {
+    use tauri_plugin_store::StoreExt;
+
+    let status = state.configure_exporter(config).await?;
+
+    let store = app.store(STORE_FILE)
+        .map_err(|e| format!("Failed to open store: {}", e))?;
+    store.set("config", json!(config));
+    store.save().map_err(|e| format!("Failed to save: {}", e))?;
+
+    Ok(status)
+}
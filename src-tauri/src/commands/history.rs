@@ -0,0 +1,176 @@
+//! Query history commands
+//!
+//! The history is persisted through `tauri-plugin-store` so it survives
+//! restarts.
+
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+use tauri::State;
+
+use crate::commands::query::{self, QueryResult};
+use crate::history::{HistoryEntry, HistoryFilter, QueryHistory, QueryKind};
+use crate::script::ScriptResult;
+use crate::state::AppState;
+
+const STORE_FILE: &str = "query_history.json";
+
+/// Load the query history from the store
+pub fn load_query_history(app: &tauri::AppHandle) -> Result<QueryHistory, String> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(STORE_FILE)
+        .map_err(|e| format!("Failed to open store: {}", e))?;
+
+    let entries = match store.get("entries") {
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|e| format!("Failed to parse query history: {}", e))?,
+        None => Vec::new(),
+    };
+
+    Ok(QueryHistory::new(entries))
+}
+
+/// Revision of the last history written to the store
+static SAVED_REVISION: parking_lot::Mutex<u64> = parking_lot::const_mutex(0);
+
+/// Write the query history to the store.
+///
+/// Callers save a copy after releasing the history lock, so copies can
+/// arrive out of order; one older than the last write is skipped.
+pub fn persist(app: &tauri::AppHandle, history: &QueryHistory) -> Result<(), String> {
+    use tauri_plugin_store::StoreExt;
+
+    let mut saved = SAVED_REVISION.lock();
+    if history.revision() <= *saved {
+        return Ok(());
+    }
+
+    let store = app.store(STORE_FILE)
+        .map_err(|e| format!("Failed to open store: {}", e))?;
+
+    store.set("entries", json!(history.entries()));
+    store.save().map_err(|e| format!("Failed to save: {}", e))?;
+    *saved = history.revision();
+
+    Ok(())
+}
+
+/// Write a copy of the query history to the store, off the async runtime
+async fn save(app: &tauri::AppHandle, history: QueryHistory) -> Result<(), String> {
+    let app = app.clone();
+    tokio::task::spawn_blocking(move || persist(&app, &history))
+        .await
+        .map_err(|e| format!("Failed to save: {}", e))?
+}
+
+/// Search executed queries, newest first
+///
+/// `text` matches anywhere in the query, ignoring case. `connection` is a
+/// connection id or database path. `from` and `to` are Unix timestamps (ms).
+#[tauri::command]
+pub async fn search_query_history(
+    state: State<'_, Arc<AppState>>,
+    text: Option<String>,
+    connection: Option<String>,
+    from: Option<u64>,
+    to: Option<u64>,
+    pinned_only: Option<bool>,
+    limit: Option<usize>,
+) -> Result<Vec<HistoryEntry>, String> {
+    let filter = HistoryFilter {
+        text,
+        connection,
+        from,
+        to,
+        pinned_only: pinned_only.unwrap_or(false),
+        limit,
+    };
+    Ok(state.query_history.read().await.search(&filter))
+}
+
+/// Pin a query as a favourite, or unpin it
+#[tauri::command]
+pub async fn pin_query_history(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    entry_id: String,
+    pinned: bool,
+) -> Result<HistoryEntry, String> {
+    let (entry, snapshot) = {
+        let mut history = state.query_history.write().await;
+        let entry = history.set_pinned(&entry_id, pinned)?;
+        (entry, history.clone())
+    };
+    save(&app, snapshot).await?;
+    Ok(entry)
+}
+
+/// Delete a history entry
+#[tauri::command]
+pub async fn delete_query_history(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    entry_id: String,
+) -> Result<(), String> {
+    let snapshot = {
+        let mut history = state.query_history.write().await;
+        history.remove(&entry_id)?;
+        history.clone()
+    };
+    save(&app, snapshot).await
+}
+
+/// Delete unpinned history entries, optionally only one connection's
+#[tauri::command]
+pub async fn clear_query_history(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    connection: Option<String>,
+) -> Result<(), String> {
+    let snapshot = {
+        let mut history = state.query_history.write().await;
+        history.clear(connection.as_deref());
+        history.clone()
+    };
+    save(&app, snapshot).await
+}
+
+/// Result of rerunning a history entry, shaped like the command that first
+/// ran it
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum RerunResult {
+    Query(QueryResult),
+    Script(ScriptResult<QueryResult>),
+}
+
+/// Run a history entry's query again
+///
+/// Runs on `connection_id` if given, otherwise on the connection the query
+/// originally ran on. Scripts run again as a script, in their own
+/// transaction. The run is recorded as a new history entry.
+#[tauri::command]
+pub async fn rerun_query_history(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    entry_id: String,
+    connection_id: Option<String>,
+) -> Result<RerunResult, String> {
+    let entry = state
+        .query_history
+        .read()
+        .await
+        .get(&entry_id)
+        .cloned()
+        .ok_or_else(|| format!("Unknown history entry: {}", entry_id))?;
+
+    let connection_id = connection_id.unwrap_or(entry.connection_id);
+    let session = state.session(Some(&connection_id)).await?;
+    match entry.kind {
+        QueryKind::Query => query::run_query(&app, &state, &session, entry.query).await.map(RerunResult::Query),
+        QueryKind::Script => query::run_script(&app, &state, &session, entry.query, false)
+            .await
+            .map(RerunResult::Script),
+    }
+}
@@ -10,6 +10,7 @@ use serde_json::{json, Value};
 use std::sync::Arc;
 use tauri::State;
 
+use crate::connection::pool::Session;
 use crate::state::AppState;
 
 /// LLM Provider configuration
@@ -221,6 +222,7 @@ pub async fn chat_completion(
     app: tauri::AppHandle,
     state: State<'_, Arc<AppState>>,
     messages: Vec<ChatMessage>,
+    connection_id: Option<String>,
 ) -> Result<ChatResponse, String> {
     let config = get_llm_config(app.clone()).await?
         .ok_or("No LLM configuration found")?;
@@ -232,8 +234,9 @@ pub async fn chat_completion(
     // Get MCP tools (only for native OpenAI API, not custom endpoints)
     let is_native_openai = config.provider == "openai" && 
         config.endpoint.as_ref().map(|e| e.is_empty()).unwrap_or(true);
+    let session = state.session(connection_id.as_deref()).await.ok();
     let tools = if is_native_openai {
-        get_openai_tools(&state).await?
+        get_openai_tools(session.as_deref()).await?
     } else {
         vec![]  // Skip tools for custom endpoints - may not support function calling
     };
@@ -339,32 +342,10 @@ pub async fn chat_completion(
                 .unwrap_or(json!({}));
             
             // Call MCP tool
-            let mcp_lock = state.mcp_server.read().await;
-            if let Some(mcp) = mcp_lock.as_ref() {
-                let mcp_req = sochdb_mcp::RpcRequest {
-                    jsonrpc: "2.0".to_string(),
-                    id: Value::Number(1.into()),
-                    method: "tools/call".to_string(),
-                    params: json!({
-                        "name": call.function.name,
-                        "arguments": args,
-                    }),
-                };
-                
-                let mcp_resp = mcp.dispatch(&mcp_req);
-                let result_text = if let Some(result) = mcp_resp.result {
-                    if let Some(content) = result["content"].as_array() {
-                        content.get(0)
-                            .and_then(|c| c["text"].as_str())
-                            .map(|s| s.to_string())
-                            .unwrap_or_else(|| serde_json::to_string(&result).unwrap_or_default())
-                    } else {
-                        serde_json::to_string(&result).unwrap_or_default()
-                    }
-                } else if let Some(err) = mcp_resp.error {
-                    format!("Error: {}", err.message)
-                } else {
-                    "No result".to_string()
+            if let Some(session) = session.as_ref() {
+                let result_text = match session.call_tool(&call.function.name, args).await {
+                    Ok(result) => tool_result_text(&result),
+                    Err(e) => format!("Error: {}", e),
                 };
                 
                 results.push(ToolResult {
@@ -397,27 +378,27 @@ pub async fn chat_completion(
     })
 }
 
+/// Flatten an MCP tool result into the text handed back to the model
+fn tool_result_text(result: &Value) -> String {
+    result["content"]
+        .as_array()
+        .and_then(|content| content.first())
+        .and_then(|c| c["text"].as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| serde_json::to_string(result).unwrap_or_default())
+}
+
 /// Convert MCP tools to OpenAI function format
-async fn get_openai_tools(state: &State<'_, Arc<AppState>>) -> Result<Vec<Value>, String> {
-    let mcp_lock = state.mcp_server.read().await;
-    let mcp = match mcp_lock.as_ref() {
-        Some(m) => m,
+async fn get_openai_tools(session: Option<&Session>) -> Result<Vec<Value>, String> {
+    let session = match session {
+        Some(s) => s,
         None => return Ok(vec![]),
     };
     
     // Get tools list
-    let req = sochdb_mcp::RpcRequest {
-        jsonrpc: "2.0".to_string(),
-        id: Value::Number(1.into()),
-        method: "tools/list".to_string(),
-        params: Value::Null,
-    };
-    
-    let resp = mcp.dispatch(&req);
-    
-    let tools_list = match resp.result {
-        Some(result) => result["tools"].as_array().cloned().unwrap_or_default(),
-        None => return Ok(vec![]),
+    let tools_list = match session.list_tools().await {
+        Ok(result) => result["tools"].as_array().cloned().unwrap_or_default(),
+        Err(_) => return Ok(vec![]),
     };
     
     // Convert to OpenAI format
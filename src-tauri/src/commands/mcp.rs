@@ -5,30 +5,17 @@ use tauri::State;
 use std::sync::Arc;
 
 use crate::state::AppState;
-use sochdb_mcp::RpcRequest;
 
 /// List available MCP tools
 #[tauri::command]
 pub async fn mcp_list_tools(
     state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
 ) -> Result<Value, String> {
-    let mcp_lock = state.mcp_server.read().await;
-    let mcp = mcp_lock.as_ref().ok_or("MCP server not initialized. Connect to a database first.")?;
+    let session = state.session(connection_id.as_deref()).await
+        .map_err(|e| format!("{}. Connect to a database first.", e))?;
     
-    // Create a tools/list request
-    let req = RpcRequest {
-        jsonrpc: "2.0".to_string(),
-        id: Value::Number(1.into()),
-        method: "tools/list".to_string(),
-        params: Value::Null,
-    };
-    
-    let resp = mcp.dispatch(&req);
-    
-    match resp.result {
-        Some(result) => Ok(result),
-        None => Err(resp.error.map(|e| e.message).unwrap_or("Unknown error".to_string())),
-    }
+    session.list_tools().await
 }
 
 /// Call an MCP tool
@@ -37,30 +24,15 @@ pub async fn mcp_call_tool(
     state: State<'_, Arc<AppState>>,
     tool_name: String,
     arguments: Value,
+    connection_id: Option<String>,
 ) -> Result<Value, String> {
-    let mcp_lock = state.mcp_server.read().await;
-    let mcp = mcp_lock.as_ref().ok_or("MCP server not initialized. Connect to a database first.")?;
-    
-    // Create a tools/call request
-    let req = RpcRequest {
-        jsonrpc: "2.0".to_string(),
-        id: Value::Number(1.into()),
-        method: "tools/call".to_string(),
-        params: serde_json::json!({
-            "name": tool_name,
-            "arguments": arguments,
-        }),
-    };
-    
-    let resp = mcp.dispatch(&req);
+    let session = state.session(connection_id.as_deref()).await
+        .map_err(|e| format!("{}. Connect to a database first.", e))?;
     
-    match resp.result {
-        Some(result) => Ok(result),
-        None => Err(resp.error.map(|e| e.message).unwrap_or("Unknown error".to_string())),
-    }
+    session.call_tool(&tool_name, arguments).await
 }
 
-/// Get service status (DB connection + MCP)
+/// Get service status (active DB session + MCP)
 #[tauri::command]
 pub async fn get_service_status(
     state: State<'_, Arc<AppState>>,
@@ -1,8 +1,14 @@
 pub mod admin;
+pub mod connections;
 pub mod context;
+pub mod cursors;
+pub mod exporter;
+pub mod history;
 pub mod llm;
 pub mod mcp;
 pub mod query;
+pub mod saved_queries;
+pub mod schedules;
 pub mod schema;
 pub mod vector;
 
@@ -2,17 +2,35 @@
 
 use serde::{Deserialize, Serialize};
 use tauri::State;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+use crate::connection::pool::Session;
+use crate::analyze;
+use crate::history::{QueryKind, Run};
+use crate::parser::sql::{self, SqlStatement};
+use crate::planner::{self, Catalog, QueryPlan, Statement, TableStats};
+use crate::results::{self, ColumnDescriptor, ResultSet};
+use crate::running_queries::RunningQuery;
+use crate::script::{Script, ScriptResult};
 use crate::state::AppState;
+use crate::util::now_millis;
 
 /// Result of a query execution
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryResult {
+    /// Column names, in the order of `column_info`
     pub columns: Vec<String>,
+    /// Column names with their types, in `SELECT` list order
+    pub column_info: Vec<ColumnDescriptor>,
     pub rows: Vec<Vec<serde_json::Value>>,
     pub stats: QueryStats,
 }
 
+/// Longest a failed script waits for a stopped statement to end on the
+/// engine before giving up on rolling back
+const ABANDONED_STATEMENT_WAIT: Duration = Duration::from_secs(30);
+
 /// Query execution statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryStats {
@@ -22,108 +40,276 @@ pub struct QueryStats {
 }
 
 /// Execute a SQL/SochQL query
+///
+/// The query is recorded in the query history, whether it succeeds or not.
 #[tauri::command]
 pub async fn execute_query(
+    app: tauri::AppHandle,
     state: State<'_, Arc<AppState>>,
     query: String,
+    connection_id: Option<String>,
 ) -> Result<QueryResult, String> {
-    let status = state.get_status().await;
-    if !status.db_connected {
-        return Err("No active connection".to_string());
+    let session = state.session(connection_id.as_deref()).await?;
+    run_query(&app, &state, &session, query).await
+}
+
+/// Run a query on a session and record it in the query history
+pub async fn run_query(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    session: &Session,
+    query: String,
+) -> Result<QueryResult, String> {
+    let executed_at = now_millis();
+    let start = std::time::Instant::now();
+    let result = execute(state, session, &query).await;
+    let execution_time_ms = match &result {
+        Ok(result) => result.stats.execution_time_ms,
+        Err(_) => start.elapsed().as_secs_f64() * 1000.0,
+    };
+
+    let run = Run {
+        kind: QueryKind::Query,
+        query,
+        executed_at,
+        execution_time_ms,
+        result: result.as_ref().map(|r| r.stats.row_count).map_err(|e| e.clone()),
+    };
+    record_history(app, state, session, run).await;
+    result
+}
+
+/// Run a script of `;`-separated statements in order on one connection.
+///
+/// A script without a `BEGIN` runs in a single transaction. When a statement
+/// fails, the open transaction is rolled back and the rest of the script
+/// skipped, unless `continue_on_error` is set. The script is recorded in the
+/// query history as one entry.
+#[tauri::command]
+pub async fn execute_script(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    script: String,
+    continue_on_error: Option<bool>,
+    connection_id: Option<String>,
+) -> Result<ScriptResult<QueryResult>, String> {
+    let session = state.session(connection_id.as_deref()).await?;
+    run_script(&app, &state, &session, script, continue_on_error.unwrap_or(false)).await
+}
+
+/// Run a script on a session and record it in the query history.
+///
+/// The session is held for the whole script, so other queries, cursors and
+/// jobs on it are refused rather than landing in its transaction.
+pub async fn run_script(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    session: &Session,
+    script: String,
+    continue_on_error: bool,
+) -> Result<ScriptResult<QueryResult>, String> {
+    let parsed = Script::parse(&script)?;
+    if parsed.statements().any(|statement| !sql::parameter_names(statement).is_empty()) {
+        return Err("Bind the script's parameters before running it".to_string());
+    }
+    if !state.running_queries.settle(&session.id, Duration::ZERO).await {
+        return Err(format!(
+            "A cancelled statement is still running on connection '{}'; run the script once it stops",
+            session.id
+        ));
     }
+    let _script = session.script_guard()?;
+
+    let executed_at = now_millis();
+    // Set once a stopped statement outlives the wait: it may still change
+    // the transaction, so nothing more is sent, not even ROLLBACK
+    let unknown = AtomicBool::new(false);
+    let unknown = &unknown;
+    let result = parsed
+        .run(continue_on_error, |statement| async move {
+            if unknown.load(Ordering::Relaxed) {
+                return Err("Transaction state unknown: a cancelled statement is still running".to_string());
+            }
+            let result = run_statement(state, session, &statement).await;
+            if result.is_err() && !state.running_queries.settle(&session.id, ABANDONED_STATEMENT_WAIT).await {
+                unknown.store(true, Ordering::Relaxed);
+            }
+            result
+        })
+        .await;
+
+    let run = Run {
+        kind: QueryKind::Script,
+        query: script,
+        executed_at,
+        execution_time_ms: result.execution_time_ms,
+        result: match result.first_error().or(result.transaction_error.as_deref()) {
+            Some(error) => Err(error.to_string()),
+            None => Ok(result.statements.iter().filter_map(|s| s.result.as_ref()).map(|r| r.stats.row_count).sum()),
+        },
+    };
+    record_history(app, state, session, run).await;
+    Ok(result)
+}
+
+/// Add an entry to the query history and save it
+async fn record_history(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    session: &Session,
+    run: Run,
+) {
+    let snapshot = {
+        let mut history = state.query_history.write().await;
+        history.record(run, session.id.clone(), session.path.clone());
+        history.clone()
+    };
+
+    // Written in the background, so neither the query's caller nor other
+    // history users wait on the disk
+    let app = app.clone();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = crate::commands::history::persist(&app, &snapshot) {
+            eprintln!("Failed to save query history: {}", e);
+        }
+    });
+}
+
+/// Run a query on the engine, listed as running and subject to the
+/// session's statement timeout. Refused while a script holds the session.
+pub async fn execute(state: &AppState, session: &Session, query: &str) -> Result<QueryResult, String> {
+    let _script = session.statement_guard()?;
+    run_statement(state, session, query).await
+}
 
+/// [`execute`] for a caller that already holds the session's statement or
+/// script guard
+pub async fn run_statement(state: &AppState, session: &Session, query: &str) -> Result<QueryResult, String> {
     let start = std::time::Instant::now();
     
     // Use MCP tools/call for query execution
-    let mcp_lock = state.mcp_server.read().await;
-    let mcp = mcp_lock.as_ref().ok_or("MCP server not initialized")?;
+    let call = session.call_tool("sochdb_query", serde_json::json!({ "query": query, "format": "json" }));
+    let result = state
+        .running_queries
+        .run(&session.id, query, session.statement_timeout(), call)
+        .await;
+    let elapsed = start.elapsed();
+    session.telemetry.record_query(elapsed, result.is_ok());
+    let result = result?;
+    let execution_time = elapsed.as_secs_f64() * 1000.0;
     
-    // Create query request
-    let req = sochdb_mcp::RpcRequest {
-        jsonrpc: "2.0".to_string(),
-        id: serde_json::Value::Number(1.into()),
-        method: "tools/call".to_string(),
-        params: serde_json::json!({
-            "name": "sochdb_query",
-            "arguments": { "query": query, "format": "json" }
-        }),
+    let text = result
+        .get("content")
+        .and_then(|v| v.as_array())
+        .and_then(|arr| arr.first())
+        .and_then(|v| v.get("text"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+
+    // Columns follow the SELECT list. The table's declared schema types
+    // them first, then the returned values, then the table's statistics
+    // for columns no returned row had a value for.
+    let (selected, table) = match sql::parse_sql(query) {
+        Ok(SqlStatement::Select { table, columns, .. }) => (columns, Some(table)),
+        _ => (Vec::new(), None),
     };
-    
-    let resp = mcp.dispatch(&req);
-    let execution_time = start.elapsed().as_secs_f64() * 1000.0;
-    
-    match resp.result {
-        Some(result) => {
-            let text = result
-                .get("content")
-                .and_then(|v| v.as_array())
-                .and_then(|arr| arr.first())
-                .and_then(|v| v.get("text"))
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-
-            let (columns, parsed_rows): (Vec<String>, Vec<Vec<serde_json::Value>>) =
-                match serde_json::from_str::<serde_json::Value>(text) {
-                    Ok(serde_json::Value::Array(items)) => {
-                        if let Some(first_object) = items.first().and_then(|item| item.as_object()) {
-                            let mut columns: Vec<String> = first_object.keys().cloned().collect();
-                            columns.sort();
-                            let rows = items
-                                .into_iter()
-                                .map(|item| {
-                                    if let Some(obj) = item.as_object() {
-                                        columns
-                                            .iter()
-                                            .map(|col| obj.get(col).cloned().unwrap_or(serde_json::Value::Null))
-                                            .collect()
-                                    } else {
-                                        vec![item]
-                                    }
-                                })
-                                .collect();
-                            (columns, rows)
-                        } else {
-                            (
-                                vec!["result".to_string()],
-                                items.into_iter().map(|item| vec![item]).collect(),
-                            )
-                        }
-                    }
-                    Ok(value) => (vec!["result".to_string()], vec![vec![value]]),
-                    Err(_) => (
-                        vec!["result".to_string()],
-                        vec![vec![serde_json::Value::String(text.to_string())]],
-                    ),
-                };
-
-            Ok(QueryResult {
-                columns,
-                rows: parsed_rows.clone(),
-                stats: QueryStats {
-                    row_count: parsed_rows.len(),
-                    execution_time_ms: execution_time,
-                    scanned_rows: 0,
-                },
-            })
-        },
-        None => {
-            let err_msg = resp.error.map(|e| e.message).unwrap_or("Query failed".to_string());
-            Err(err_msg)
+    let mut result_set = ResultSet::parse(text, &selected);
+    if let Some(table) = &table {
+        // The rows are already in hand, so an unreadable schema or
+        // statistics only costs the column types
+        match results::load_schema(session, table).await {
+            Ok(Some(declared)) => result_set.apply_schema(&declared),
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to load the schema of {}: {}", table, e),
+        }
+        if result_set.has_untyped_columns() {
+            match analyze::load_analysis(session, table).await {
+                Ok(Some(analysis)) => result_set.apply_statistics(&analysis),
+                Ok(None) => {}
+                Err(e) => eprintln!("Failed to load statistics for {}: {}", table, e),
+            }
         }
     }
+
+    Ok(QueryResult {
+        columns: result_set.column_names(),
+        column_info: result_set.columns,
+        stats: QueryStats {
+            row_count: result_set.rows.len(),
+            execution_time_ms: execution_time,
+            scanned_rows: 0,
+        },
+        rows: result_set.rows,
+    })
 }
 
-/// Get query explanation/plan
+/// Get the query plan, with estimated rows and cost per node.
+///
+/// With `analyze` (or an `EXPLAIN ANALYZE` prefix) the query is also run and
+/// each node annotated with its actual rows and time. Only read statements
+/// can be analyzed.
 #[tauri::command]
 pub async fn explain_query(
     state: State<'_, Arc<AppState>>,
-    _query: String,
-) -> Result<String, String> {
-    let status = state.get_status().await;
-    if !status.db_connected {
-        return Err("No active connection".to_string());
+    query: String,
+    analyze: Option<bool>,
+    connection_id: Option<String>,
+) -> Result<QueryPlan, String> {
+    let session = state.session(connection_id.as_deref()).await?;
+    let (query, explain_analyze) = planner::strip_explain(&query);
+    let analyze = analyze.unwrap_or(false) || explain_analyze;
+
+    // Cached counts only; explaining never starts a full scan
+    let counts = state.collection_stats.read().await.get(&session.id);
+    let mut catalog = Catalog {
+        indexes: session.backend.indexes().await?,
+        ..Default::default()
+    };
+    for table in Statement::parse(query)?.tables() {
+        let row_count = counts
+            .snapshot
+            .as_ref()
+            .and_then(|s| s.collections.iter().find(|c| c.name == table))
+            .map(|c| c.key_count);
+        // Unreadable statistics fall back to default estimates
+        let analysis = analyze::load_analysis(&session, &table).await.unwrap_or_else(|e| {
+            eprintln!("Failed to load statistics for {}: {}", table, e);
+            None
+        });
+        catalog.tables.insert(table, TableStats { row_count, analysis });
     }
-    
-    // TODO: Implement explain via MCP
-    Ok("Query plan: Full scan (explain not yet implemented)".to_string())
+
+    let plan = planner::plan(query, &catalog)?;
+    if analyze {
+        let _script = session.statement_guard()?;
+        // Listed and timed out like any other query it reads for
+        state
+            .running_queries
+            .run(&session.id, query, session.statement_timeout(), plan.analyze(&session))
+            .await
+    } else {
+        Ok(plan.explain())
+    }
+}
+
+/// Queries currently running, oldest first
+#[tauri::command]
+pub async fn list_running_queries(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+) -> Result<Vec<RunningQuery>, String> {
+    let mut queries = state.running_queries.list();
+    if let Some(connection_id) = connection_id {
+        queries.retain(|q| q.connection_id == connection_id);
+    }
+    Ok(queries)
+}
+
+/// Cancel a running query; `execute_query` returns a "Query cancelled" error
+#[tauri::command]
+pub async fn cancel_query(
+    state: State<'_, Arc<AppState>>,
+    query_id: String,
+) -> Result<RunningQuery, String> {
+    state.running_queries.cancel(&query_id)
 }
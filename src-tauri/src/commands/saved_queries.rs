@@ -0,0 +1,163 @@
+//! Saved query library commands
+//!
+//! The library is persisted through `tauri-plugin-store`; export and
+//! import go through a JSON file chosen by the user.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::State;
+
+use crate::commands::query::{self, QueryResult};
+use crate::saved_queries::{ImportSummary, LibraryFile, QueryLibrary, SavedQuery, SavedQueryRequest};
+use crate::state::AppState;
+use crate::util::now_millis;
+
+const STORE_FILE: &str = "saved_queries.json";
+
+/// Load the saved query library from the store
+pub fn load_query_library(app: &tauri::AppHandle) -> Result<QueryLibrary, String> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(STORE_FILE)
+        .map_err(|e| format!("Failed to open store: {}", e))?;
+
+    let queries = match store.get("queries") {
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|e| format!("Failed to parse saved queries: {}", e))?,
+        None => Vec::new(),
+    };
+
+    Ok(QueryLibrary::new(queries))
+}
+
+/// Revision of the last library written to the store
+static SAVED_REVISION: parking_lot::Mutex<u64> = parking_lot::const_mutex(0);
+
+/// Write a copy of the library to the store, off the async runtime.
+///
+/// Callers save a copy after releasing the library lock, so copies can
+/// arrive out of order; one older than the last write is skipped.
+async fn persist(app: &tauri::AppHandle, library: QueryLibrary) -> Result<(), String> {
+    let app = app.clone();
+    tokio::task::spawn_blocking(move || {
+        use tauri_plugin_store::StoreExt;
+
+        let mut saved = SAVED_REVISION.lock();
+        if library.revision() <= *saved {
+            return Ok(());
+        }
+
+        let store = app.store(STORE_FILE)
+            .map_err(|e| format!("Failed to open store: {}", e))?;
+
+        store.set("queries", json!(library.list(None, None)));
+        store.save().map_err(|e| format!("Failed to save: {}", e))?;
+        *saved = library.revision();
+
+        Ok(())
+    })
+    .await
+    .map_err(|e| format!("Failed to save: {}", e))?
+}
+
+/// List saved queries by folder and name
+///
+/// `folder` also matches its subfolders; `tag` ignores case.
+#[tauri::command]
+pub async fn list_saved_queries(
+    state: State<'_, Arc<AppState>>,
+    folder: Option<String>,
+    tag: Option<String>,
+) -> Result<Vec<SavedQuery>, String> {
+    Ok(state.query_library.read().await.list(folder.as_deref(), tag.as_deref()))
+}
+
+/// Create or update a saved query
+#[tauri::command]
+pub async fn save_query(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    request: SavedQueryRequest,
+) -> Result<SavedQuery, String> {
+    let (saved, snapshot) = {
+        let mut library = state.query_library.write().await;
+        let saved = library.save(request, now_millis())?;
+        (saved, library.clone())
+    };
+    persist(&app, snapshot).await?;
+    Ok(saved)
+}
+
+/// Delete a saved query
+#[tauri::command]
+pub async fn delete_saved_query(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    query_id: String,
+) -> Result<(), String> {
+    let snapshot = {
+        let mut library = state.query_library.write().await;
+        library.remove(&query_id)?;
+        library.clone()
+    };
+    persist(&app, snapshot).await
+}
+
+/// Run a saved query with values bound to its placeholders
+///
+/// `params` maps each name in the query's `parameters` to a JSON value:
+/// `$name` by its name, `$1` and bare `?`s by their position.
+#[tauri::command]
+pub async fn run_saved_query(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    query_id: String,
+    params: Option<HashMap<String, Value>>,
+    connection_id: Option<String>,
+) -> Result<QueryResult, String> {
+    let bound = state
+        .query_library
+        .read()
+        .await
+        .bind(&query_id, &params.unwrap_or_default())?;
+    let session = state.session(connection_id.as_deref()).await?;
+    query::run_query(&app, &state, &session, bound).await
+}
+
+/// Write the whole library to a JSON file
+#[tauri::command]
+pub async fn export_saved_queries(
+    state: State<'_, Arc<AppState>>,
+    path: String,
+) -> Result<usize, String> {
+    let file = state.query_library.read().await.export();
+    let json = serde_json::to_string_pretty(&file)
+        .map_err(|e| format!("Failed to serialize saved queries: {}", e))?;
+    tokio::fs::write(&path, json)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(file.queries.len())
+}
+
+/// Merge a library exported by `export_saved_queries`
+#[tauri::command]
+pub async fn import_saved_queries(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    path: String,
+) -> Result<ImportSummary, String> {
+    let json = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let file: LibraryFile = serde_json::from_str(&json)
+        .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+
+    let (summary, snapshot) = {
+        let mut library = state.query_library.write().await;
+        let summary = library.import(file, now_millis())?;
+        (summary, library.clone())
+    };
+    persist(&app, snapshot).await?;
+    Ok(summary)
+}
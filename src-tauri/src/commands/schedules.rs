@@ -0,0 +1,111 @@
+//! Maintenance schedule commands
+//!
+//! Schedules and their run history are persisted through
+//! `tauri-plugin-store` so they survive restarts.
+
+use serde_json::json;
+use std::sync::Arc;
+use tauri::State;
+
+use crate::scheduler::{MaintenanceSchedule, ScheduleRequest, ScheduledRun, Scheduler};
+use crate::state::AppState;
+use crate::util::now_millis;
+
+const STORE_FILE: &str = "maintenance.json";
+
+/// Load schedules and run history from the store
+pub fn load_scheduler(app: &tauri::AppHandle) -> Result<Scheduler, String> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(STORE_FILE)
+        .map_err(|e| format!("Failed to open store: {}", e))?;
+
+    let schedules = match store.get("schedules") {
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|e| format!("Failed to parse maintenance schedules: {}", e))?,
+        None => Vec::new(),
+    };
+    let history = match store.get("history") {
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|e| format!("Failed to parse maintenance history: {}", e))?,
+        None => Vec::new(),
+    };
+
+    Ok(Scheduler::new(schedules, history))
+}
+
+/// Revision of the last scheduler written to the store
+static SAVED_REVISION: parking_lot::Mutex<u64> = parking_lot::const_mutex(0);
+
+/// Write schedules and run history to the store.
+///
+/// Callers save a copy after releasing the scheduler lock, so copies can
+/// arrive out of order; one older than the last write is skipped.
+pub fn persist(app: &tauri::AppHandle, scheduler: &Scheduler) -> Result<(), String> {
+    use tauri_plugin_store::StoreExt;
+
+    let mut saved = SAVED_REVISION.lock();
+    if scheduler.revision() <= *saved {
+        return Ok(());
+    }
+
+    let store = app.store(STORE_FILE)
+        .map_err(|e| format!("Failed to open store: {}", e))?;
+
+    store.set("schedules", json!(scheduler.schedules(None)));
+    store.set("history", json!(scheduler.history(None, None).into_iter().rev().collect::<Vec<_>>()));
+    store.save().map_err(|e| format!("Failed to save: {}", e))?;
+    *saved = scheduler.revision();
+
+    Ok(())
+}
+
+/// List maintenance schedules, optionally only those for one connection
+#[tauri::command]
+pub async fn list_schedules(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+) -> Result<Vec<MaintenanceSchedule>, String> {
+    Ok(state.scheduler.read().await.schedules(connection_id.as_deref()))
+}
+
+/// Create or update a maintenance schedule
+#[tauri::command]
+pub async fn save_schedule(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    request: ScheduleRequest,
+) -> Result<MaintenanceSchedule, String> {
+    let (schedule, snapshot) = {
+        let mut scheduler = state.scheduler.write().await;
+        let schedule = scheduler.save(request, now_millis())?;
+        (schedule, scheduler.clone())
+    };
+    persist(&app, &snapshot)?;
+    Ok(schedule)
+}
+
+/// Delete a maintenance schedule
+#[tauri::command]
+pub async fn delete_schedule(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    schedule_id: String,
+) -> Result<(), String> {
+    let snapshot = {
+        let mut scheduler = state.scheduler.write().await;
+        scheduler.remove(&schedule_id)?;
+        scheduler.clone()
+    };
+    persist(&app, &snapshot)
+}
+
+/// Get scheduled maintenance runs, newest first
+#[tauri::command]
+pub async fn get_maintenance_history(
+    state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<ScheduledRun>, String> {
+    Ok(state.scheduler.read().await.history(connection_id.as_deref(), limit))
+}
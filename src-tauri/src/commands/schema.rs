@@ -3,6 +3,7 @@
 use serde::{Deserialize, Serialize};
 use tauri::State;
 use std::sync::Arc;
+use crate::analyze::{self, ColumnStatistics};
 use crate::state::AppState;
 
 /// Column definition
@@ -12,6 +13,8 @@ pub struct ColumnInfo {
     pub data_type: String,
     pub nullable: bool,
     pub is_primary_key: bool,
+    /// Statistics from the last `analyze`, if any
+    pub statistics: Option<ColumnStatistics>,
 }
 
 /// Table definition
@@ -20,6 +23,8 @@ pub struct TableInfo {
     pub name: String,
     pub row_count: usize,
     pub columns: Vec<ColumnInfo>,
+    /// Unix timestamp (ms) of the last `analyze`
+    pub analyzed_at: Option<u64>,
 }
 
 /// Index definition
@@ -51,34 +56,34 @@ pub struct DatabaseSchema {
 #[tauri::command]
 pub async fn get_schema(
     state: State<'_, Arc<AppState>>,
+    connection_id: Option<String>,
 ) -> Result<DatabaseSchema, String> {
-    let status = state.get_status().await;
-    if !status.db_connected {
-        return Err("No active connection".to_string());
-    }
+    let session = state.session(connection_id.as_deref()).await?;
 
     // Use MCP to list tables via scan
-    let mcp_lock = state.mcp_server.read().await;
-    let mcp = mcp_lock.as_ref().ok_or("MCP server not initialized")?;
-    
     // List tables via MCP
-    let req = sochdb_mcp::RpcRequest {
-        jsonrpc: "2.0".to_string(),
-        id: serde_json::Value::Number(1.into()),
-        method: "tools/call".to_string(),
-        params: serde_json::json!({
-            "name": "sochdb_list_tables",
-            "arguments": {}
-        }),
-    };
-    
-    let _resp = mcp.dispatch(&req);
+    let _resp = session
+        .call_tool("sochdb_list_tables", serde_json::json!({}))
+        .await;
+
+    // Top-level paths from the cached collection counts, which leave out
+    // Studio's own keys
+    let counts = state.collection_stats(&session).await;
+    let paths = counts
+        .snapshot
+        .map(|s| {
+            s.collections
+                .into_iter()
+                .map(|c| PathInfo { path: c.name, count: c.key_count as usize })
+                .collect()
+        })
+        .unwrap_or_default();
     
     // Return basic schema - real implementation would parse MCP response
     Ok(DatabaseSchema {
         tables: vec![],
         indexes: vec![],
-        paths: vec![],
+        paths,
     })
 }
 
@@ -87,16 +92,34 @@ pub async fn get_schema(
 pub async fn get_table_info(
     state: State<'_, Arc<AppState>>,
     table_name: String,
+    connection_id: Option<String>,
 ) -> Result<TableInfo, String> {
-    let status = state.get_status().await;
-    if !status.db_connected {
-        return Err("No active connection".to_string());
-    }
+    let session = state.session(connection_id.as_deref()).await?;
+    
+    // Columns are only known once the collection has been analyzed
+    let Some(analysis) = analyze::load_analysis(&session, &table_name).await? else {
+        return Ok(TableInfo {
+            name: table_name,
+            row_count: 0,
+            columns: vec![],
+            analyzed_at: None,
+        });
+    };
     
-    // TODO: Query table schema via MCP
     Ok(TableInfo {
         name: table_name,
-        row_count: 0,
-        columns: vec![],
+        row_count: analysis.row_count as usize,
+        columns: analysis
+            .columns
+            .into_iter()
+            .map(|stats| ColumnInfo {
+                name: stats.name.clone(),
+                data_type: stats.data_type.clone(),
+                nullable: stats.null_fraction > 0.0,
+                is_primary_key: false,
+                statistics: Some(stats),
+            })
+            .collect(),
+        analyzed_at: Some(analysis.analyzed_at),
     })
 }
@@ -22,32 +22,22 @@ pub async fn vector_search(
     column: String,
     query_text: String,
     limit: usize,
+    connection_id: Option<String>,
 ) -> Result<Vec<VectorResult>, String> {
-    let status = state.get_status().await;
-    if !status.db_connected {
-        return Err("No active connection".to_string());
-    }
+    let session = state.session(connection_id.as_deref()).await?;
     
     // Use MCP for vector search
-    let mcp_lock = state.mcp_server.read().await;
-    let mcp = mcp_lock.as_ref().ok_or("MCP server not initialized")?;
-    
-    let req = sochdb_mcp::RpcRequest {
-        jsonrpc: "2.0".to_string(),
-        id: serde_json::Value::Number(1.into()),
-        method: "tools/call".to_string(),
-        params: serde_json::json!({
-            "name": "vector_search",
-            "arguments": {
+    let _resp = session
+        .call_tool(
+            "vector_search",
+            serde_json::json!({
                 "table": table,
                 "column": column,
                 "query": query_text,
                 "limit": limit
-            }
-        }),
-    };
-    
-    let _resp = mcp.dispatch(&req);
+            }),
+        )
+        .await;
     
     // Return empty for now - real implementation would parse MCP response
     Ok(vec![])
@@ -58,11 +48,9 @@ pub async fn vector_search(
 pub async fn get_vector_columns(
     state: State<'_, Arc<AppState>>,
     _table: String,
+    connection_id: Option<String>,
 ) -> Result<Vec<String>, String> {
-    let status = state.get_status().await;
-    if !status.db_connected {
-        return Err("No active connection".to_string());
-    }
+    state.session(connection_id.as_deref()).await?;
     
     // TODO: Query vector columns via MCP
     Ok(vec![])
@@ -0,0 +1,664 @@
+//! Database backends behind a session
+//!
+//! A session talks to its database through [`DatabaseBackend`], so query,
+//! schema, stats and vector commands work the same against an embedded
+//! database and a remote `sochdb-server`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sochdb::connection::EmbeddedConnection;
+use sochdb_mcp::{McpServer, RpcRequest};
+use sochdb_storage::sstable::SstReader;
+
+use crate::running_queries;
+use crate::stats::{CollectionCounter, CollectionSnapshot, SampleRng, STATS_SAMPLE_BYTES};
+
+/// Entries per batch when counting collections
+const COUNT_BATCH_SIZE: usize = 1000;
+
+/// Storage, transaction and MVCC statistics. `None` means the backend
+/// cannot report it.
+#[derive(Debug, Clone, Default)]
+pub struct BackendStats {
+    pub bytes_written: Option<u64>,
+    pub bytes_read: Option<u64>,
+    pub transactions_started: Option<u64>,
+    pub transactions_committed: Option<u64>,
+    pub transactions_aborted: Option<u64>,
+    /// Bytes held in the active and immutable memtables
+    pub memtable_size_bytes: Option<u64>,
+    /// Total size of the WAL segments on disk
+    pub wal_size_bytes: Option<u64>,
+    /// Open MVCC read snapshots
+    pub active_snapshots: Option<u64>,
+    /// Oldest timestamp still visible to an open snapshot
+    pub min_active_timestamp: Option<u64>,
+    /// Superseded versions waiting for GC
+    pub garbage_versions: Option<u64>,
+}
+
+impl BackendStats {
+    /// Transactions started but neither committed nor aborted
+    pub fn active_transactions(&self) -> Option<u64> {
+        let finished = self.transactions_committed? + self.transactions_aborted?;
+        Some(self.transactions_started?.saturating_sub(finished))
+    }
+}
+
+/// Most recent WAL checkpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointMark {
+    pub lsn: u64,
+    /// Unix timestamp (ms) the checkpoint completed
+    pub completed_at: u64,
+}
+
+/// Database metadata read when a session opens. `None` means the backend
+/// cannot report it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatabaseMetadata {
+    /// On-disk format version
+    pub format_version: Option<u32>,
+    /// Version of the storage engine serving the database
+    pub engine_version: Option<String>,
+    /// Unix timestamp (ms) the database was created
+    pub created_at: Option<u64>,
+    /// Total size of the data directory, including the WAL
+    pub data_size_bytes: Option<u64>,
+    pub wal_size_bytes: Option<u64>,
+    /// Whether opening the database replayed the WAL after an unclean shutdown
+    pub recovered_from_crash: Option<bool>,
+}
+
+/// Bytes on disk by storage component
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub total_bytes: u64,
+    pub wal_bytes: u64,
+    /// SST files by LSM level, lowest level first
+    pub sst_levels: Vec<SstLevelUsage>,
+    pub vector_index_bytes: u64,
+    /// Everything else: manifests, options, lock files
+    pub metadata_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SstLevelUsage {
+    pub level: u32,
+    pub file_count: usize,
+    pub bytes: u64,
+}
+
+impl DiskUsage {
+    /// Sort `files` (path and size) into storage components.
+    ///
+    /// `sst_levels` maps each live SST file to its level; SST files the
+    /// engine no longer tracks count as metadata.
+    pub fn classify(
+        files: &[(PathBuf, u64)],
+        wal_dir: &Path,
+        vector_dir: &Path,
+        sst_levels: &HashMap<PathBuf, u32>,
+    ) -> Self {
+        let mut usage = DiskUsage::default();
+        let mut levels: BTreeMap<u32, SstLevelUsage> = BTreeMap::new();
+        for (path, size) in files {
+            usage.total_bytes += size;
+            if path.starts_with(wal_dir) {
+                usage.wal_bytes += size;
+            } else if path.starts_with(vector_dir) {
+                usage.vector_index_bytes += size;
+            } else if let Some(&level) = sst_levels.get(path) {
+                let entry = levels.entry(level).or_insert(SstLevelUsage { level, file_count: 0, bytes: 0 });
+                entry.file_count += 1;
+                entry.bytes += size;
+            } else {
+                usage.metadata_bytes += size;
+            }
+        }
+        usage.sst_levels = levels.into_values().collect();
+        usage
+    }
+}
+
+/// A secondary index on a table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexDef {
+    pub name: String,
+    pub table: String,
+    /// Indexed columns, leading column first
+    pub columns: Vec<String>,
+}
+
+/// Batches of key/value pairs from [`DatabaseBackend::scan_stream`], in key order
+pub type ScanStream = tokio::sync::mpsc::Receiver<Result<Vec<(Vec<u8>, Vec<u8>)>, String>>;
+
+/// Operations every connection type supports
+#[async_trait]
+pub trait DatabaseBackend: Send + Sync {
+    /// List MCP tools, returning a `tools/list` result
+    async fn list_tools(&self) -> Result<Value, String>;
+
+    /// Call an MCP tool, returning a `tools/call` result
+    async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String>;
+
+    /// Scan all key/value pairs under `prefix` (empty scans the whole database)
+    async fn scan(&self, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, String>;
+
+    /// The value stored under exactly `key`.
+    ///
+    /// The default scans `key` as a prefix, since a key is also a prefix of
+    /// its longer siblings.
+    async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        Ok(self
+            .scan(key)
+            .await?
+            .into_iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value))
+    }
+
+    /// Scan `prefix` in batches of up to `batch_size` pairs.
+    ///
+    /// The default scans everything up front; backends that can read
+    /// incrementally only fetch the next batch once the previous one is taken.
+    async fn scan_stream(&self, prefix: &[u8], batch_size: usize) -> Result<ScanStream, String> {
+        let entries = self.scan(prefix).await?;
+        let batches: Vec<Vec<(Vec<u8>, Vec<u8>)>> = entries.chunks(batch_size.max(1)).map(<[_]>::to_vec).collect();
+        let (tx, rx) = tokio::sync::mpsc::channel(batches.len().max(1));
+        for batch in batches {
+            // The receiver is still in hand, so the buffer cannot be full or closed
+            let _ = tx.try_send(Ok(batch));
+        }
+        Ok(rx)
+    }
+
+    /// Per-collection key counts and sizes.
+    ///
+    /// The default streams every key and keeps only the running counts.
+    async fn collection_counts(&self) -> Result<CollectionSnapshot, String> {
+        let start = Instant::now();
+        // A true root scan. A normal "/" prefix scan is rejected by the
+        // storage layer's minimum-prefix safety check.
+        let mut stream = self.scan_stream(b"", COUNT_BATCH_SIZE).await?;
+        let mut counter = CollectionCounter::new();
+        while let Some(batch) = stream.recv().await {
+            for (key, value) in batch? {
+                counter.add(&key, (key.len() + value.len()) as u64);
+            }
+        }
+        Ok(counter.exact(start.elapsed()))
+    }
+
+    /// Storage, transaction and MVCC statistics
+    async fn stats(&self) -> Result<BackendStats, String>;
+
+    /// The most recent WAL checkpoint, if the database has one
+    async fn last_checkpoint(&self) -> Result<Option<CheckpointMark>, String>;
+
+    /// Format, version and size information about the database
+    async fn metadata(&self) -> Result<DatabaseMetadata, String>;
+
+    /// Cheap liveness probe; an error means the database is unreachable
+    async fn health_check(&self) -> Result<(), String>;
+
+    /// Limit the time the server spends on each tool call, for backends that
+    /// can stop a call server-side
+    fn set_statement_timeout(&self, _timeout: Option<Duration>) {}
+
+    /// Bytes on disk by storage component, if the backend can see its files
+    async fn disk_usage(&self) -> Result<Option<DiskUsage>, String> {
+        Ok(None)
+    }
+
+    /// Secondary indexes, if the backend can list them
+    async fn indexes(&self) -> Result<Vec<IndexDef>, String> {
+        Ok(Vec::new())
+    }
+
+    /// The embedded MCP server, for operations that need direct storage access
+    fn embedded(&self) -> Option<&McpServer> {
+        None
+    }
+}
+
+/// In-process database opened from a local path
+pub struct EmbeddedBackend {
+    /// Shared with blocking tool calls
+    mcp: Arc<McpServer>,
+    path: PathBuf,
+}
+
+impl EmbeddedBackend {
+    /// Open the database at `path`.
+    ///
+    /// A `read_only` open does not take the writer lock, so it can inspect a
+    /// database another process is writing.
+    pub fn open(path: &Path, read_only: bool) -> Result<Self, String> {
+        let conn = if read_only {
+            EmbeddedConnection::open_read_only(path)
+        } else {
+            EmbeddedConnection::open(path)
+        }
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+
+        // Create MCP server with shared connection
+        Ok(Self {
+            mcp: Arc::new(McpServer::new(Arc::new(conn))),
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn request(mcp: &McpServer, method: &str, params: Value) -> Result<Value, String> {
+        let req = RpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Value::Number(1.into()),
+            method: method.to_string(),
+            params,
+        };
+
+        let resp = mcp.dispatch(&req);
+
+        match resp.result {
+            Some(result) => Ok(result),
+            None => Err(resp.error.map(|e| e.message).unwrap_or("Unknown error".to_string())),
+        }
+    }
+
+    /// Count collections, reading at most [`STATS_SAMPLE_BYTES`] of stored data.
+    ///
+    /// A database that fits in the budget is counted key by key. A larger
+    /// one is estimated from randomly chosen SST files, scaled up by their
+    /// share of the stored bytes. Those files still hold overwritten and
+    /// deleted versions until compaction, so estimates run high on
+    /// databases with a lot of churn.
+    fn count_collections(mcp: &McpServer) -> Result<CollectionSnapshot, String> {
+        let start = Instant::now();
+        let kernel = mcp.connection().kernel();
+        let mut files: Vec<(PathBuf, u64)> = kernel
+            .sst_files()
+            .into_iter()
+            .filter_map(|file| std::fs::metadata(&file.path).ok().map(|meta| (file.path, meta.len())))
+            .collect();
+        let total_bytes = kernel.memtable_size_bytes() + files.iter().map(|(_, len)| len).sum::<u64>();
+
+        let mut counter = CollectionCounter::new();
+        if total_bytes <= STATS_SAMPLE_BYTES {
+            let txn = kernel.begin_read_only_fast();
+            let result = (|| -> Result<(), String> {
+                for entry in kernel.scan_iter_unchecked(txn, b"").map_err(|e| e.to_string())? {
+                    let (key, value) = entry.map_err(|e| e.to_string())?;
+                    counter.add(&key, (key.len() + value.len()) as u64);
+                }
+                Ok(())
+            })();
+            kernel.abort_read_only_fast(txn);
+            result.map_err(|e| format!("Scan failed: {}", e))?;
+            return Ok(counter.exact(start.elapsed()));
+        }
+
+        SampleRng::from_time().shuffle(&mut files);
+        let mut sampled_bytes = 0;
+        for (path, len) in files {
+            if sampled_bytes >= STATS_SAMPLE_BYTES {
+                break;
+            }
+            let reader = SstReader::open(&path)
+                .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+            // An entry's stored size runs to the next entry's offset
+            let mut previous: Option<(Vec<u8>, u64)> = None;
+            for entry in reader.entries() {
+                let entry = entry.map_err(|e| format!("Failed to read {}: {}", path.display(), e.message))?;
+                if let Some((key, offset)) = previous.replace((entry.key, entry.offset)) {
+                    counter.add(&key, entry.offset.saturating_sub(offset));
+                }
+            }
+            if let Some((key, offset)) = previous {
+                counter.add(&key, len.saturating_sub(offset));
+            }
+            sampled_bytes += len;
+        }
+        Ok(counter.estimate(sampled_bytes, total_bytes, start.elapsed()))
+    }
+}
+
+#[async_trait]
+impl DatabaseBackend for EmbeddedBackend {
+    async fn list_tools(&self) -> Result<Value, String> {
+        Self::request(&self.mcp, "tools/list", Value::Null)
+    }
+
+    async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+        // Off the async runtime, so a slow statement can be timed out or
+        // cancelled while it runs. The engine cannot be interrupted, so the
+        // query stays listed until the statement actually returns.
+        let mcp = self.mcp.clone();
+        let params = serde_json::json!({
+            "name": name,
+            "arguments": arguments,
+        });
+        let query = running_queries::current();
+        tokio::task::spawn_blocking(move || {
+            let _query = query;
+            Self::request(&mcp, "tools/call", params)
+        })
+            .await
+            .map_err(|e| format!("Tool call failed: {}", e))?
+    }
+
+    async fn scan(&self, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, String> {
+        let kernel = self.mcp.connection().kernel();
+        let txn = kernel.begin_read_only_fast();
+        let result = kernel.scan_unchecked(txn, prefix);
+        kernel.abort_read_only_fast(txn);
+
+        let entries = result.map_err(|e| format!("Scan failed: {}", e))?;
+        Ok(entries
+            .into_iter()
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect())
+    }
+
+    async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        let kernel = self.mcp.connection().kernel();
+        let txn = kernel.begin_read_only_fast();
+        let result = kernel.get_unchecked(txn, key);
+        kernel.abort_read_only_fast(txn);
+
+        let value = result.map_err(|e| format!("Get failed: {}", e))?;
+        Ok(value.map(|value| value.to_vec()))
+    }
+
+    async fn scan_stream(&self, prefix: &[u8], batch_size: usize) -> Result<ScanStream, String> {
+        // One batch in flight: the blocking reader waits until the previous
+        // batch is taken, and stops once the receiver is dropped
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let mcp = self.mcp.clone();
+        let prefix = prefix.to_vec();
+        let batch_size = batch_size.max(1);
+        tokio::task::spawn_blocking(move || {
+            let kernel = mcp.connection().kernel();
+            let txn = kernel.begin_read_only_fast();
+            let result = (|| -> Result<(), String> {
+                let mut batch = Vec::with_capacity(batch_size);
+                for entry in kernel.scan_iter_unchecked(txn, &prefix).map_err(|e| e.to_string())? {
+                    batch.push(entry.map_err(|e| e.to_string())?);
+                    if batch.len() == batch_size
+                        && tx.blocking_send(Ok(std::mem::replace(&mut batch, Vec::with_capacity(batch_size)))).is_err()
+                    {
+                        return Ok(());
+                    }
+                }
+                if !batch.is_empty() {
+                    let _ = tx.blocking_send(Ok(batch));
+                }
+                Ok(())
+            })();
+            kernel.abort_read_only_fast(txn);
+            if let Err(e) = result {
+                let _ = tx.blocking_send(Err(format!("Scan failed: {}", e)));
+            }
+        });
+        Ok(rx)
+    }
+
+    async fn collection_counts(&self) -> Result<CollectionSnapshot, String> {
+        let mcp = self.mcp.clone();
+        tokio::task::spawn_blocking(move || Self::count_collections(&mcp))
+            .await
+            .map_err(|e| format!("Counting collections failed: {}", e))?
+    }
+
+    async fn stats(&self) -> Result<BackendStats, String> {
+        let db_stats = self.mcp.db_stats();
+        let kernel = self.mcp.connection().kernel();
+        let mvcc = kernel.mvcc_stats();
+        let wal_dir = kernel.database_info().wal_dir;
+        let wal_size = tokio::task::spawn_blocking(move || dir_size(&wal_dir).ok())
+            .await
+            .map_err(|e| format!("Measuring the WAL failed: {}", e))?;
+        Ok(BackendStats {
+            bytes_written: Some(db_stats.bytes_written),
+            bytes_read: Some(db_stats.bytes_read),
+            transactions_started: Some(db_stats.transactions_started),
+            transactions_committed: Some(db_stats.transactions_committed),
+            transactions_aborted: Some(db_stats.transactions_aborted),
+            memtable_size_bytes: Some(kernel.memtable_size_bytes()),
+            wal_size_bytes: wal_size,
+            active_snapshots: Some(mvcc.active_snapshots as u64),
+            // No open snapshots means nothing pins an old timestamp
+            min_active_timestamp: mvcc.min_active_timestamp,
+            garbage_versions: Some(mvcc.garbage_versions as u64),
+        })
+    }
+
+    async fn last_checkpoint(&self) -> Result<Option<CheckpointMark>, String> {
+        Ok(self
+            .mcp
+            .connection()
+            .kernel()
+            .last_checkpoint()
+            .map(|checkpoint| CheckpointMark {
+                lsn: checkpoint.lsn,
+                completed_at: checkpoint.completed_at_us / 1000,
+            }))
+    }
+
+    async fn metadata(&self) -> Result<DatabaseMetadata, String> {
+        let info = self.mcp.connection().kernel().database_info();
+        let (path, wal_dir) = (self.path.clone(), info.wal_dir);
+        let (data_size, wal_size) =
+            tokio::task::spawn_blocking(move || (dir_size(&path).ok(), dir_size(&wal_dir).ok()))
+                .await
+                .map_err(|e| format!("Measuring the database failed: {}", e))?;
+        Ok(DatabaseMetadata {
+            format_version: Some(info.format_version),
+            engine_version: Some(info.engine_version),
+            created_at: Some(info.created_at_us / 1000),
+            data_size_bytes: data_size,
+            wal_size_bytes: wal_size,
+            recovered_from_crash: Some(info.recovered_from_crash),
+        })
+    }
+
+    async fn health_check(&self) -> Result<(), String> {
+        // Open file handles outlive a deleted directory, so check the path too
+        if !self.path.exists() {
+            return Err(format!("Database {} no longer exists", self.path.display()));
+        }
+
+        // Runs every few seconds, so stick to a read-only begin/abort
+        // rather than anything that touches the disk
+        let kernel = self.mcp.connection().kernel();
+        let txn = kernel.begin_read_only_fast();
+        kernel.abort_read_only_fast(txn);
+        Ok(())
+    }
+
+    async fn disk_usage(&self) -> Result<Option<DiskUsage>, String> {
+        let kernel = self.mcp.connection().kernel();
+        let info = kernel.database_info();
+        let sst_levels: HashMap<PathBuf, u32> = kernel
+            .sst_files()
+            .into_iter()
+            .map(|file| (file.path, file.level))
+            .collect();
+
+        let (path, wal_dir) = (self.path.clone(), info.wal_dir.clone());
+        let files = tokio::task::spawn_blocking(move || {
+            let mut files = Vec::new();
+            list_files(&path, &mut files)
+                .map_err(|e| format!("Failed to list {}: {}", path.display(), e))?;
+            // The WAL may be configured to live outside the data directory
+            if !wal_dir.starts_with(&path) {
+                list_files(&wal_dir, &mut files)
+                    .map_err(|e| format!("Failed to list {}: {}", wal_dir.display(), e))?;
+            }
+            Ok::<_, String>(files)
+        })
+        .await
+        .map_err(|e| format!("Listing database files failed: {}", e))??;
+
+        Ok(Some(DiskUsage::classify(&files, &info.wal_dir, &info.vector_index_dir, &sst_levels)))
+    }
+
+    async fn indexes(&self) -> Result<Vec<IndexDef>, String> {
+        Ok(self
+            .mcp
+            .connection()
+            .kernel()
+            .indexes()
+            .into_iter()
+            .map(|index| IndexDef {
+                name: index.name,
+                table: index.table,
+                columns: index.columns,
+            })
+            .collect())
+    }
+
+    fn embedded(&self) -> Option<&McpServer> {
+        Some(self.mcp.as_ref())
+    }
+}
+
+/// Stands in for a session's backend while it is reopened, so the old
+/// handle is released first. Every operation fails.
+pub struct ClosedBackend;
+
+impl ClosedBackend {
+    fn error<T>() -> Result<T, String> {
+        Err("Connection is closed while it reconnects".to_string())
+    }
+}
+
+#[async_trait]
+impl DatabaseBackend for ClosedBackend {
+    async fn list_tools(&self) -> Result<Value, String> {
+        Self::error()
+    }
+
+    async fn call_tool(&self, _name: &str, _arguments: Value) -> Result<Value, String> {
+        Self::error()
+    }
+
+    async fn scan(&self, _prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, String> {
+        Self::error()
+    }
+
+    async fn stats(&self) -> Result<BackendStats, String> {
+        Self::error()
+    }
+
+    async fn last_checkpoint(&self) -> Result<Option<CheckpointMark>, String> {
+        Self::error()
+    }
+
+    async fn metadata(&self) -> Result<DatabaseMetadata, String> {
+        Self::error()
+    }
+
+    async fn health_check(&self) -> Result<(), String> {
+        Self::error()
+    }
+}
+
+/// Total size of the files under `path` (or of `path` itself if it is a file)
+fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let meta = std::fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        return Ok(meta.len());
+    }
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        total += dir_size(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+/// Every file under `path`, with its size
+fn list_files(path: &Path, out: &mut Vec<(PathBuf, u64)>) -> std::io::Result<()> {
+    let meta = std::fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        out.push((path.to_path_buf(), meta.len()));
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(path)? {
+        list_files(&entry?.path(), out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_active_transactions_needs_all_counters() {
+        let mut stats = BackendStats {
+            transactions_started: Some(10),
+            transactions_committed: Some(6),
+            transactions_aborted: Some(1),
+            ..BackendStats::default()
+        };
+        assert_eq!(stats.active_transactions(), Some(3));
+
+        stats.transactions_aborted = None;
+        assert_eq!(stats.active_transactions(), None);
+    }
+
+    #[test]
+    fn test_dir_size() {
+        let dir = std::env::temp_dir().join(format!("sochdb-studio-size-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("wal")).unwrap();
+        std::fs::write(dir.join("data.sst"), [0u8; 100]).unwrap();
+        std::fs::write(dir.join("wal").join("000001.log"), [0u8; 28]).unwrap();
+
+        assert_eq!(dir_size(&dir).unwrap(), 128);
+        assert_eq!(dir_size(&dir.join("wal")).unwrap(), 28);
+        assert!(dir_size(&dir.join("missing")).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_disk_usage_classification() {
+        let files: Vec<(PathBuf, u64)> = [
+            ("/db/wal/000001.log", 100),
+            ("/db/sst/000010.sst", 40),
+            ("/db/sst/000011.sst", 60),
+            ("/db/sst/000020.sst", 500),
+            ("/db/sst/000099.sst", 7),
+            ("/db/vectors/embeddings.hnsw", 300),
+            ("/db/MANIFEST", 3),
+        ]
+        .into_iter()
+        .map(|(path, size)| (PathBuf::from(path), size))
+        .collect();
+        let levels = HashMap::from([
+            (PathBuf::from("/db/sst/000020.sst"), 1),
+            (PathBuf::from("/db/sst/000010.sst"), 0),
+            (PathBuf::from("/db/sst/000011.sst"), 0),
+        ]);
+
+        let usage = DiskUsage::classify(&files, Path::new("/db/wal"), Path::new("/db/vectors"), &levels);
+        assert_eq!(usage.total_bytes, 1010);
+        assert_eq!(usage.wal_bytes, 100);
+        assert_eq!(usage.vector_index_bytes, 300);
+        // An SST file the engine no longer tracks is leftover metadata
+        assert_eq!(usage.metadata_bytes, 10);
+        assert_eq!(
+            usage.sst_levels,
+            vec![
+                SstLevelUsage { level: 0, file_count: 2, bytes: 100 },
+                SstLevelUsage { level: 1, file_count: 1, bytes: 500 },
+            ]
+        );
+    }
+}
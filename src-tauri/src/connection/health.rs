@@ -0,0 +1,185 @@
+//! Connection health monitoring
+//!
+//! A background task probes the active session every [`PROBE_INTERVAL`].
+//! Repeated probe failures mark the connection lost, after which it is
+//! reopened with exponential backoff. Every state change is reported as a
+//! [`HealthEvent`] on the [`HEALTH_EVENT`] channel.
+
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// Tauri event carrying [`HealthEvent`]s
+pub const HEALTH_EVENT: &str = "connection-health";
+
+/// Time between probes of a healthy or degraded connection
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Consecutive failed probes before a connection is considered lost
+pub const FAILURES_BEFORE_LOST: u32 = 3;
+
+/// First reconnect delay; doubled after every failed attempt
+const RECONNECT_BACKOFF_BASE: Duration = Duration::from_secs(1);
+
+/// Upper bound on the reconnect delay
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Health of a session's connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionHealth {
+    /// The last probe succeeded
+    Healthy,
+    /// Recent probes failed, but not enough to give up on the connection
+    Degraded,
+    /// Probes keep failing; the connection will be reopened
+    Lost,
+    /// Reopening the connection
+    Reconnecting,
+}
+
+/// Payload of a [`HEALTH_EVENT`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthEvent {
+    pub connection_id: String,
+    pub health: ConnectionHealth,
+    /// Error from the failed probe or reconnect attempt
+    pub error: Option<String>,
+    /// Reconnect attempt number, while reconnecting
+    pub attempt: Option<u32>,
+}
+
+/// Health state machine for the monitored connection
+#[derive(Debug, Clone)]
+pub struct HealthTracker {
+    health: ConnectionHealth,
+    consecutive_failures: u32,
+    reconnect_attempts: u32,
+}
+
+impl HealthTracker {
+    pub fn new() -> Self {
+        Self {
+            health: ConnectionHealth::Healthy,
+            consecutive_failures: 0,
+            reconnect_attempts: 0,
+        }
+    }
+
+    pub fn health(&self) -> ConnectionHealth {
+        self.health
+    }
+
+    /// Whether the next step is a reconnect attempt rather than a probe
+    pub fn needs_reconnect(&self) -> bool {
+        matches!(self.health, ConnectionHealth::Lost | ConnectionHealth::Reconnecting)
+    }
+
+    /// Delay before the next probe or reconnect attempt
+    pub fn next_delay(&self) -> Duration {
+        if self.needs_reconnect() {
+            backoff(self.reconnect_attempts)
+        } else {
+            PROBE_INTERVAL
+        }
+    }
+
+    /// Record a successful probe, returning the new health if it changed
+    pub fn probe_succeeded(&mut self) -> Option<ConnectionHealth> {
+        self.consecutive_failures = 0;
+        self.transition(ConnectionHealth::Healthy)
+    }
+
+    /// Record a failed probe, returning the new health if it changed
+    pub fn probe_failed(&mut self) -> Option<ConnectionHealth> {
+        self.consecutive_failures += 1;
+        if self.consecutive_failures >= FAILURES_BEFORE_LOST {
+            self.transition(ConnectionHealth::Lost)
+        } else {
+            self.transition(ConnectionHealth::Degraded)
+        }
+    }
+
+    /// Record the start of a reconnect attempt, returning its number
+    pub fn reconnect_started(&mut self) -> u32 {
+        self.reconnect_attempts += 1;
+        self.health = ConnectionHealth::Reconnecting;
+        self.reconnect_attempts
+    }
+
+    /// Record a successful reconnect
+    pub fn reconnected(&mut self) {
+        *self = Self::new();
+    }
+
+    fn transition(&mut self, health: ConnectionHealth) -> Option<ConnectionHealth> {
+        if self.health == health {
+            return None;
+        }
+        self.health = health;
+        Some(health)
+    }
+}
+
+impl Default for HealthTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reconnect delay after `failed_attempts` failed attempts
+fn backoff(failed_attempts: u32) -> Duration {
+    RECONNECT_BACKOFF_BASE
+        .saturating_mul(2u32.saturating_pow(failed_attempts))
+        .min(RECONNECT_BACKOFF_MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failures_degrade_then_lose_connection() {
+        let mut tracker = HealthTracker::new();
+        assert_eq!(tracker.probe_failed(), Some(ConnectionHealth::Degraded));
+        for _ in 2..FAILURES_BEFORE_LOST {
+            assert_eq!(tracker.probe_failed(), None);
+        }
+        assert_eq!(tracker.probe_failed(), Some(ConnectionHealth::Lost));
+        assert!(tracker.needs_reconnect());
+    }
+
+    #[test]
+    fn test_probe_success_recovers_degraded_connection() {
+        let mut tracker = HealthTracker::new();
+        assert_eq!(tracker.probe_succeeded(), None);
+        tracker.probe_failed();
+        assert_eq!(tracker.probe_succeeded(), Some(ConnectionHealth::Healthy));
+
+        // The failure count starts over after a success
+        tracker.probe_failed();
+        assert_eq!(tracker.health(), ConnectionHealth::Degraded);
+    }
+
+    #[test]
+    fn test_reconnect_backoff() {
+        let mut tracker = HealthTracker::new();
+        for _ in 0..FAILURES_BEFORE_LOST {
+            tracker.probe_failed();
+        }
+        assert_eq!(tracker.next_delay(), Duration::from_secs(1));
+
+        assert_eq!(tracker.reconnect_started(), 1);
+        assert_eq!(tracker.health(), ConnectionHealth::Reconnecting);
+        assert_eq!(tracker.next_delay(), Duration::from_secs(2));
+        tracker.reconnect_started();
+        assert_eq!(tracker.next_delay(), Duration::from_secs(4));
+        for _ in 0..10 {
+            tracker.reconnect_started();
+        }
+        assert_eq!(tracker.next_delay(), RECONNECT_BACKOFF_MAX);
+
+        tracker.reconnected();
+        assert_eq!(tracker.health(), ConnectionHealth::Healthy);
+        assert_eq!(tracker.next_delay(), PROBE_INTERVAL);
+    }
+}
@@ -5,18 +5,30 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 /// Saved connection configuration
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SavedConnection {
     pub id: String,
     pub name: String,
     pub path: String,
     pub connection_type: ConnectionType,
+    /// Sidebar group the connection is listed under
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Colour label, e.g. "#e11d48"
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Unix timestamp (ms) the connection was last opened
+    #[serde(default)]
+    pub last_opened_at: Option<u64>,
+    /// Queries running longer than this many ms are cancelled
+    #[serde(default)]
+    pub statement_timeout_ms: Option<u64>,
 }
 
 /// Type of connection
-#[allow(dead_code)]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ConnectionType {
     LocalFile,
@@ -26,39 +38,22 @@ pub enum ConnectionType {
 }
 
 /// Connection manager state
-#[allow(dead_code)]
 pub struct ConnectionManager {
     pool: Arc<ConnectionPool>,
     saved_connections: Vec<SavedConnection>,
+    /// Bumped on every change to the saved connections, so a copy saved
+    /// after the lock is released can be told apart from a newer one
+    saved_revision: u64,
     active_connection_id: Option<String>,
 }
 
-#[allow(dead_code)]
 impl ConnectionManager {
     pub fn new() -> Self {
         Self {
             pool: Arc::new(ConnectionPool::new()),
-            saved_connections: vec![
-                SavedConnection {
-                    id: "local-dev".to_string(),
-                    name: "Local Development".to_string(),
-                    path: "./dev.sochdb".to_string(),
-                    connection_type: ConnectionType::LocalFile,
-                },
-                SavedConnection {
-                    id: "production".to_string(),
-                    name: "Production".to_string(),
-                    path: "/var/sochdb/prod".to_string(),
-                    connection_type: ConnectionType::LocalFile,
-                },
-                SavedConnection {
-                    id: "remote".to_string(),
-                    name: "Remote Server".to_string(),
-                    path: "192.168.1.10:7654".to_string(),
-                    connection_type: ConnectionType::TcpIp,
-                },
-            ],
-            active_connection_id: Some("local-dev".to_string()),
+            saved_connections: Vec::new(),
+            saved_revision: 0,
+            active_connection_id: None,
         }
     }
 
@@ -72,14 +67,129 @@ impl ConnectionManager {
         &self.saved_connections
     }
 
+    /// Number of changes to the saved connections since they were loaded
+    pub fn saved_revision(&self) -> u64 {
+        self.saved_revision
+    }
+
+    /// Replace the saved connections (e.g. after loading them from disk)
+    pub fn set_saved_connections(&mut self, connections: Vec<SavedConnection>) {
+        self.saved_connections = connections;
+    }
+
+    /// Get a saved connection by id
+    pub fn saved_connection(&self, id: &str) -> Option<&SavedConnection> {
+        self.saved_connections.iter().find(|c| c.id == id)
+    }
+
     /// Add a new saved connection
-    pub fn add_saved_connection(&mut self, conn: SavedConnection) {
+    pub fn add_saved_connection(&mut self, conn: SavedConnection) -> Result<(), String> {
+        if self.saved_connection(&conn.id).is_some() {
+            return Err(format!("Saved connection '{}' already exists", conn.id));
+        }
         self.saved_connections.push(conn);
+        self.saved_revision += 1;
+        Ok(())
+    }
+
+    /// Replace an existing saved connection, keeping its position
+    pub fn update_saved_connection(&mut self, conn: SavedConnection) -> Result<(), String> {
+        let existing = self
+            .saved_connections
+            .iter_mut()
+            .find(|c| c.id == conn.id)
+            .ok_or_else(|| format!("Unknown saved connection: {}", conn.id))?;
+        *existing = conn;
+        self.saved_revision += 1;
+        Ok(())
     }
 
     /// Remove a saved connection
     pub fn remove_saved_connection(&mut self, id: &str) {
         self.saved_connections.retain(|c| c.id != id);
+        self.saved_revision += 1;
+    }
+
+    /// Copy a saved connection under `new_id`, inserting it right after the original
+    pub fn duplicate_saved_connection(&mut self, id: &str, new_id: String) -> Result<SavedConnection, String> {
+        let index = self
+            .saved_connections
+            .iter()
+            .position(|c| c.id == id)
+            .ok_or_else(|| format!("Unknown saved connection: {}", id))?;
+
+        let mut copy = self.saved_connections[index].clone();
+        copy.id = new_id;
+        copy.name = format!("{} (copy)", copy.name);
+        copy.last_opened_at = None;
+
+        self.saved_connections.insert(index + 1, copy.clone());
+        self.saved_revision += 1;
+        Ok(copy)
+    }
+
+    /// Reorder saved connections to match `ids`.
+    ///
+    /// Connections missing from `ids` keep their relative order and are
+    /// appended at the end.
+    pub fn reorder_saved_connections(&mut self, ids: &[String]) -> Result<(), String> {
+        if let Some(unknown) = ids.iter().find(|id| self.saved_connection(id).is_none()) {
+            return Err(format!("Unknown saved connection: {}", unknown));
+        }
+
+        let mut remaining = std::mem::take(&mut self.saved_connections);
+        let mut ordered = Vec::with_capacity(remaining.len());
+        for id in ids {
+            if let Some(pos) = remaining.iter().position(|c| &c.id == id) {
+                ordered.push(remaining.remove(pos));
+            }
+        }
+        ordered.extend(remaining);
+        self.saved_connections = ordered;
+        self.saved_revision += 1;
+        Ok(())
+    }
+
+    /// Move saved connections into a group (`None` ungroups them)
+    pub fn set_group(&mut self, ids: &[String], group: Option<String>) -> Result<(), String> {
+        if let Some(unknown) = ids.iter().find(|id| self.saved_connection(id).is_none()) {
+            return Err(format!("Unknown saved connection: {}", unknown));
+        }
+        for conn in self.saved_connections.iter_mut() {
+            if ids.contains(&conn.id) {
+                conn.group = group.clone();
+            }
+        }
+        self.saved_revision += 1;
+        Ok(())
+    }
+
+    /// Record that a saved connection was just opened.
+    ///
+    /// Returns `false` if `id` is not a saved connection.
+    pub fn touch_saved_connection(&mut self, id: &str, opened_at: u64) -> bool {
+        match self.saved_connections.iter_mut().find(|c| c.id == id) {
+            Some(conn) => {
+                conn.last_opened_at = Some(opened_at);
+                self.saved_revision += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set a saved connection's statement timeout.
+    ///
+    /// Returns `false` if `id` is not a saved connection.
+    pub fn set_statement_timeout(&mut self, id: &str, timeout_ms: Option<u64>) -> bool {
+        match self.saved_connections.iter_mut().find(|c| c.id == id) {
+            Some(conn) => {
+                conn.statement_timeout_ms = timeout_ms;
+                self.saved_revision += 1;
+                true
+            }
+            None => false,
+        }
     }
 
     /// Get the active connection ID
@@ -91,6 +201,19 @@ impl ConnectionManager {
     pub fn set_active_connection(&mut self, id: String) {
         self.active_connection_id = Some(id);
     }
+
+    /// Clear the active connection
+    pub fn clear_active_connection(&mut self) {
+        self.active_connection_id = None;
+    }
+
+    /// Resolve an optional connection id to a concrete one, falling back
+    /// to the active connection
+    pub fn resolve(&self, connection_id: Option<&str>) -> Option<String> {
+        connection_id
+            .or(self.active_connection_id.as_deref())
+            .map(|id| id.to_string())
+    }
 }
 
 impl Default for ConnectionManager {
@@ -98,3 +221,63 @@ impl Default for ConnectionManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved(id: &str) -> SavedConnection {
+        SavedConnection {
+            id: id.to_string(),
+            name: id.to_uppercase(),
+            path: format!("./{}.sochdb", id),
+            connection_type: ConnectionType::LocalFile,
+            group: None,
+            tags: vec![],
+            color: None,
+            last_opened_at: None,
+            statement_timeout_ms: None,
+        }
+    }
+
+    fn ids(manager: &ConnectionManager) -> Vec<&str> {
+        manager.saved_connections().iter().map(|c| c.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_reorder_keeps_unlisted_connections() {
+        let mut manager = ConnectionManager::new();
+        for id in ["a", "b", "c"] {
+            manager.add_saved_connection(saved(id)).unwrap();
+        }
+
+        manager.reorder_saved_connections(&["c".to_string(), "a".to_string()]).unwrap();
+        assert_eq!(ids(&manager), vec!["c", "a", "b"]);
+
+        let revision = manager.saved_revision();
+        assert!(manager.reorder_saved_connections(&["missing".to_string()]).is_err());
+        assert_eq!(ids(&manager), vec!["c", "a", "b"]);
+        assert_eq!(manager.saved_revision(), revision);
+    }
+
+    #[test]
+    fn test_duplicate_inserts_after_original() {
+        let mut manager = ConnectionManager::new();
+        manager.add_saved_connection(saved("a")).unwrap();
+        manager.add_saved_connection(saved("b")).unwrap();
+        manager.touch_saved_connection("a", 42);
+
+        let copy = manager.duplicate_saved_connection("a", "a2".to_string()).unwrap();
+        assert_eq!(copy.name, "A (copy)");
+        assert_eq!(copy.last_opened_at, None);
+        assert_eq!(ids(&manager), vec!["a", "a2", "b"]);
+        assert_eq!(manager.saved_revision(), 4);
+    }
+
+    #[test]
+    fn test_add_rejects_duplicate_id() {
+        let mut manager = ConnectionManager::new();
+        manager.add_saved_connection(saved("a")).unwrap();
+        assert!(manager.add_saved_connection(saved("a")).is_err());
+    }
+}
@@ -1,3 +1,6 @@
+pub mod backend;
+pub mod health;
 pub mod manager;
 pub mod pool;
+pub mod remote;
 
@@ -2,67 +2,193 @@
 
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
 
-use sochdb::DurableSochClient;
+use sochdb_mcp::McpServer;
 
-/// Connection pool entry
-#[allow(dead_code)]
-#[derive(Clone)]
-pub struct PoolEntry {
+use super::backend::DatabaseBackend;
+use super::health::ConnectionHealth;
+use super::manager::ConnectionType;
+use crate::exporter::SessionTelemetry;
+use crate::policy;
+
+/// An open database session
+pub struct Session {
+    /// Connection id the session is registered under
+    pub id: String,
+    /// Database path, or server address for remote connections
+    pub path: String,
+    pub connection_type: ConnectionType,
+    /// Backend the session's commands run against
+    pub backend: Box<dyn DatabaseBackend>,
+    /// Time the session was established
+    pub connected_at: Instant,
+    /// Opened without the writer lock; all writes are refused
+    pub read_only: bool,
+    /// Last known health, updated by the health monitor
+    health: parking_lot::RwLock<ConnectionHealth>,
+    /// Query latency and tool call counts for the metrics endpoint
+    pub telemetry: SessionTelemetry,
+    /// Queries running longer than this are cancelled
+    statement_timeout: parking_lot::RwLock<Option<Duration>>,
+    /// Held exclusively by a running script, shared by statements and jobs
+    script_lock: Arc<RwLock<()>>,
+}
+
+impl Session {
+    pub fn new(
+        id: String,
+        path: String,
+        connection_type: ConnectionType,
+        backend: Box<dyn DatabaseBackend>,
+        read_only: bool,
+    ) -> Self {
+        Self {
+            id,
+            path,
+            connection_type,
+            backend,
+            connected_at: Instant::now(),
+            read_only,
+            health: parking_lot::RwLock::new(ConnectionHealth::Healthy),
+            telemetry: SessionTelemetry::default(),
+            statement_timeout: parking_lot::RwLock::new(None),
+            script_lock: Arc::new(RwLock::new(())),
+        }
+    }
+
+    /// Get seconds since the session connected
+    pub fn uptime_seconds(&self) -> u64 {
+        self.connected_at.elapsed().as_secs()
+    }
+
+    /// Last known health of the connection
+    pub fn health(&self) -> ConnectionHealth {
+        *self.health.read()
+    }
+
+    pub fn set_health(&self, health: ConnectionHealth) {
+        *self.health.write() = health;
+    }
+
+    pub fn statement_timeout(&self) -> Option<Duration> {
+        *self.statement_timeout.read()
+    }
+
+    pub fn set_statement_timeout(&self, timeout: Option<Duration>) {
+        *self.statement_timeout.write() = timeout;
+        self.backend.set_statement_timeout(timeout);
+    }
+
+    /// Permission to run a statement or job, refused while a script has the
+    /// session: its transaction is open on the engine, and anything else
+    /// sent meanwhile would land inside it
+    pub fn statement_guard(&self) -> Result<OwnedRwLockReadGuard<()>, String> {
+        self.script_lock.clone().try_read_owned().map_err(|_| {
+            format!("A script is running on connection '{}'; try again once it finishes", self.id)
+        })
+    }
+
+    /// Hold the session for a script until the guard is dropped, refused
+    /// while statements or jobs are running on it
+    pub fn script_guard(&self) -> Result<OwnedRwLockWriteGuard<()>, String> {
+        self.script_lock.clone().try_write_owned().map_err(|_| {
+            format!("Connection '{}' is busy with other statements or jobs; run the script once they finish", self.id)
+        })
+    }
+
+    /// Refuse `operation` if this session is read-only
+    pub fn ensure_writable(&self, operation: &str) -> Result<(), String> {
+        if self.read_only {
+            return Err(format!(
+                "Connection '{}' is read-only: {} is not allowed",
+                self.id, operation
+            ));
+        }
+        Ok(())
+    }
+
+    /// The embedded MCP server, for `operation`s that need direct storage access
+    pub fn embedded(&self, operation: &str) -> Result<&McpServer, String> {
+        self.backend.embedded().ok_or_else(|| {
+            format!("{} is only available for embedded connections", operation)
+        })
+    }
+
+    /// List MCP tools
+    pub async fn list_tools(&self) -> Result<Value, String> {
+        self.backend.list_tools().await
+    }
+
+    /// Call an MCP tool, refusing mutating tools on read-only sessions
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+        if self.read_only {
+            policy::check_read_only_tool(name, &arguments)
+                .map_err(|reason| format!("Connection '{}' is read-only: {}", self.id, reason))?;
+        }
+        let result = self.backend.call_tool(name, arguments).await;
+        self.telemetry.record_tool_call(name, result.is_ok());
+        result
+    }
+}
+
+/// Session summary for UI display
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub connection_id: String,
     pub path: String,
-    pub connected: bool,
-    pub client: Option<Arc<DurableSochClient>>,
+    pub connection_type: ConnectionType,
+    pub active: bool,
+    pub read_only: bool,
+    pub health: ConnectionHealth,
+    pub uptime_seconds: u64,
 }
 
-/// Connection pool for managing multiple database connections
-#[allow(dead_code)]
+/// Connection pool for managing multiple open database sessions
 pub struct ConnectionPool {
-    connections: Arc<RwLock<HashMap<String, PoolEntry>>>,
+    sessions: Arc<RwLock<HashMap<String, Arc<Session>>>>,
 }
 
-#[allow(dead_code)]
 impl ConnectionPool {
     pub fn new() -> Self {
         Self {
-            connections: Arc::new(RwLock::new(HashMap::new())),
+            sessions: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
-    /// Add a connection to the pool
-    pub async fn add(&self, id: String, path: String, client: Arc<DurableSochClient>) -> Result<(), String> {
-        let mut conns = self.connections.write().await;
-        conns.insert(
-            id,
-            PoolEntry {
-                path,
-                connected: true,
-                client: Some(client),
-            },
-        );
-        Ok(())
+    /// Add a session to the pool, returning any session it replaced
+    pub async fn add(&self, session: Session) -> Option<Arc<Session>> {
+        let mut sessions = self.sessions.write().await;
+        sessions.insert(session.id.clone(), Arc::new(session))
     }
 
-    /// Remove a connection from the pool
-    pub async fn remove(&self, id: &str) -> Result<(), String> {
-        let mut conns = self.connections.write().await;
-        conns.remove(id);
-        Ok(())
+    /// Remove a session from the pool
+    pub async fn remove(&self, id: &str) -> Option<Arc<Session>> {
+        let mut sessions = self.sessions.write().await;
+        sessions.remove(id)
+    }
+
+    /// Get a session from the pool
+    pub async fn get(&self, id: &str) -> Option<Arc<Session>> {
+        let sessions = self.sessions.read().await;
+        sessions.get(id).cloned()
     }
 
-    /// Get a connection from the pool
-    pub async fn get(&self, id: &str) -> Option<PoolEntry> {
-        let conns = self.connections.read().await;
-        conns.get(id).cloned()
+    /// Find the session that has the given database path or address open
+    pub async fn find_by_path(&self, path: &str) -> Option<Arc<Session>> {
+        let sessions = self.sessions.read().await;
+        sessions.values().find(|s| s.path == path).cloned()
     }
 
-    /// List all connections
-    pub async fn list(&self) -> Vec<(String, PoolEntry)> {
-        let conns = self.connections.read().await;
-        conns
-            .iter()
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect()
+    /// List all sessions, oldest first
+    pub async fn list(&self) -> Vec<Arc<Session>> {
+        let sessions = self.sessions.read().await;
+        let mut list: Vec<Arc<Session>> = sessions.values().cloned().collect();
+        list.sort_by_key(|s| s.connected_at);
+        list
     }
 }
 
@@ -0,0 +1,531 @@
+//! Remote connections to `sochdb-server` over gRPC
+//!
+//! Uses the services defined in `server/proto/sochdb.proto`: `McpService`
+//! for tool calls and `KvService` for scans. A server is reached over TCP
+//! (`host:port`) or a Unix domain socket path.
+
+use std::path::Path;
+use std::time::Duration;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tonic::transport::{Channel, Endpoint};
+
+use super::backend::{BackendStats, CheckpointMark, DatabaseBackend, DatabaseMetadata, ScanStream};
+
+pub mod proto {
+    tonic::include_proto!("sochdb.v1");
+}
+
+use proto::kv_service_client::KvServiceClient;
+use proto::mcp_service_client::McpServiceClient;
+use proto::vector_index_service_client::VectorIndexServiceClient;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Database served by a `sochdb-server` process
+pub struct RemoteBackend {
+    channel: Channel,
+    /// Sent with each tool call so the server stops it too
+    statement_timeout: parking_lot::RwLock<Option<Duration>>,
+}
+
+impl RemoteBackend {
+    fn new(channel: Channel) -> Self {
+        Self {
+            channel,
+            statement_timeout: parking_lot::RwLock::new(None),
+        }
+    }
+
+    /// Connect over TCP to `host:port` (or a full `http://` URI)
+    pub async fn connect_tcp(address: &str) -> Result<Self, String> {
+        let uri = if address.contains("://") {
+            address.to_string()
+        } else {
+            format!("http://{}", address)
+        };
+
+        let channel = Endpoint::from_shared(uri)
+            .map_err(|e| format!("Invalid server address '{}': {}", address, e))?
+            .connect_timeout(CONNECT_TIMEOUT)
+            .connect()
+            .await
+            .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+
+        Ok(Self::new(channel))
+    }
+
+    /// Connect over a Unix domain socket
+    #[cfg(unix)]
+    pub async fn connect_unix(path: &Path) -> Result<Self, String> {
+        use hyper_util::rt::TokioIo;
+        use tokio::net::UnixStream;
+        use tonic::transport::Uri;
+        use tower::service_fn;
+
+        let socket = path.to_path_buf();
+        // The URI is only a placeholder; every connection dials the socket
+        let channel = Endpoint::from_static("http://[::]:50051")
+            .connect_timeout(CONNECT_TIMEOUT)
+            .connect_with_connector(service_fn(move |_: Uri| {
+                let socket = socket.clone();
+                async move { Ok::<_, std::io::Error>(TokioIo::new(UnixStream::connect(socket).await?)) }
+            }))
+            .await
+            .map_err(|e| format!("Failed to connect to {}: {}", path.display(), e))?;
+
+        Ok(Self::new(channel))
+    }
+
+    /// Connect over a Unix domain socket
+    #[cfg(not(unix))]
+    pub async fn connect_unix(_path: &Path) -> Result<Self, String> {
+        Err("Unix socket connections are not supported on this platform".to_string())
+    }
+
+    async fn server_health(&self) -> Result<proto::HealthCheckResponse, String> {
+        VectorIndexServiceClient::new(self.channel.clone())
+            .health_check(proto::HealthCheckRequest { index_name: String::new() })
+            .await
+            .map(|resp| resp.into_inner())
+            .map_err(|e| format!("HealthCheck failed: {}", e.message()))
+    }
+}
+
+#[async_trait]
+impl DatabaseBackend for RemoteBackend {
+    async fn list_tools(&self) -> Result<Value, String> {
+        let resp = McpServiceClient::new(self.channel.clone())
+            .list_tools(proto::ListToolsRequest { tags: vec![] })
+            .await
+            .map_err(|e| format!("ListTools failed: {}", e.message()))?
+            .into_inner();
+
+        // Reshape into the MCP `tools/list` result the embedded server returns
+        let tools: Vec<Value> = resp
+            .tools
+            .into_iter()
+            .map(|tool| {
+                let input_schema = serde_json::from_str::<Value>(&tool.input_schema)
+                    .unwrap_or_else(|_| json!({"type": "object", "properties": {}}));
+                json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "inputSchema": input_schema,
+                })
+            })
+            .collect();
+
+        Ok(json!({ "tools": tools }))
+    }
+
+    async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, String> {
+        // 0 leaves the call unbounded
+        let timeout_ms = self
+            .statement_timeout
+            .read()
+            .map_or(0, |t| u32::try_from(t.as_millis()).unwrap_or(u32::MAX));
+        let resp = McpServiceClient::new(self.channel.clone())
+            .execute_tool(proto::ExecuteToolRequest {
+                tool_name: name.to_string(),
+                input: arguments.to_string(),
+                context: String::new(),
+                timeout_ms,
+            })
+            .await
+            .map_err(|e| format!("ExecuteTool failed: {}", e.message()))?
+            .into_inner();
+
+        if !resp.success {
+            return Err(if resp.error.is_empty() {
+                format!("Tool '{}' failed", name)
+            } else {
+                resp.error
+            });
+        }
+
+        // Reshape into the MCP `tools/call` result the embedded server returns
+        Ok(json!({
+            "content": [{ "type": "text", "text": resp.output }]
+        }))
+    }
+
+    async fn scan(&self, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, String> {
+        let mut stream = KvServiceClient::new(self.channel.clone())
+            .scan(proto::KvScanRequest {
+                namespace: String::new(),
+                prefix: prefix.to_vec(),
+                limit: 0,
+            })
+            .await
+            .map_err(|e| format!("Scan failed: {}", e.message()))?
+            .into_inner();
+
+        let mut entries = Vec::new();
+        while let Some(entry) = stream
+            .message()
+            .await
+            .map_err(|e| format!("Scan failed: {}", e.message()))?
+        {
+            entries.push((entry.key, entry.value));
+        }
+        Ok(entries)
+    }
+
+    async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        let resp = KvServiceClient::new(self.channel.clone())
+            .get(proto::KvGetRequest {
+                namespace: String::new(),
+                key: key.to_vec(),
+            })
+            .await
+            .map_err(|e| format!("Get failed: {}", e.message()))?
+            .into_inner();
+
+        if !resp.error.is_empty() {
+            return Err(format!("Get failed: {}", resp.error));
+        }
+        Ok(resp.found.then_some(resp.value))
+    }
+
+    async fn scan_stream(&self, prefix: &[u8], batch_size: usize) -> Result<ScanStream, String> {
+        let mut stream = KvServiceClient::new(self.channel.clone())
+            .scan(proto::KvScanRequest {
+                namespace: String::new(),
+                prefix: prefix.to_vec(),
+                limit: 0,
+            })
+            .await
+            .map_err(|e| format!("Scan failed: {}", e.message()))?
+            .into_inner();
+
+        // One batch in flight: the server stream is read as batches are taken
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let batch_size = batch_size.max(1);
+        tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(batch_size);
+            loop {
+                match stream.message().await {
+                    Ok(Some(entry)) => {
+                        batch.push((entry.key, entry.value));
+                        if batch.len() == batch_size
+                            && tx.send(Ok(std::mem::replace(&mut batch, Vec::with_capacity(batch_size)))).await.is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        let _ = tx.send(Err(format!("Scan failed: {}", e.message()))).await;
+                        return;
+                    }
+                }
+            }
+            if !batch.is_empty() {
+                let _ = tx.send(Ok(batch)).await;
+            }
+        });
+        Ok(rx)
+    }
+
+    async fn stats(&self) -> Result<BackendStats, String> {
+        // sochdb-server does not expose storage or MVCC statistics over gRPC
+        Ok(BackendStats::default())
+    }
+
+    async fn last_checkpoint(&self) -> Result<Option<CheckpointMark>, String> {
+        // WAL checkpoints are internal to sochdb-server
+        Ok(None)
+    }
+
+    async fn metadata(&self) -> Result<DatabaseMetadata, String> {
+        // Only the server version is exposed over gRPC
+        let resp = self.server_health().await?;
+        Ok(DatabaseMetadata {
+            engine_version: Some(resp.version).filter(|v| !v.is_empty()),
+            ..DatabaseMetadata::default()
+        })
+    }
+
+    async fn health_check(&self) -> Result<(), String> {
+        let resp = self.server_health().await?;
+        match resp.status() {
+            proto::health_check_response::Status::Serving => Ok(()),
+            status => Err(format!("Server is not serving ({})", status.as_str_name())),
+        }
+    }
+
+    fn set_statement_timeout(&self, timeout: Option<Duration>) {
+        *self.statement_timeout.write() = timeout;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::pin::Pin;
+    use proto::kv_service_server::{KvService, KvServiceServer};
+    use proto::mcp_service_server::{McpService, McpServiceServer};
+    use proto::vector_index_service_server::{VectorIndexService, VectorIndexServiceServer};
+    use tokio_stream::Stream;
+    use tonic::transport::Server;
+    use tonic::{Request, Response, Status};
+
+    /// In-process stand-in for `sochdb-server`
+    struct StandIn;
+
+    const ENTRIES: &[(&str, &str)] = &[
+        ("users/1", "alice"),
+        ("users/2", "bob"),
+        ("orders/1", "book"),
+    ];
+
+    #[tonic::async_trait]
+    impl McpService for StandIn {
+        async fn register_tool(
+            &self,
+            _request: Request<proto::RegisterToolRequest>,
+        ) -> Result<Response<proto::RegisterToolResponse>, Status> {
+            Err(Status::unimplemented("register_tool"))
+        }
+
+        async fn execute_tool(
+            &self,
+            request: Request<proto::ExecuteToolRequest>,
+        ) -> Result<Response<proto::ExecuteToolResponse>, Status> {
+            let req = request.into_inner();
+            let resp = if req.tool_name == "echo" {
+                proto::ExecuteToolResponse {
+                    success: true,
+                    output: req.input,
+                    ..Default::default()
+                }
+            } else {
+                proto::ExecuteToolResponse {
+                    success: false,
+                    error: format!("unknown tool {}", req.tool_name),
+                    ..Default::default()
+                }
+            };
+            Ok(Response::new(resp))
+        }
+
+        async fn list_tools(
+            &self,
+            _request: Request<proto::ListToolsRequest>,
+        ) -> Result<Response<proto::ListToolsResponse>, Status> {
+            Ok(Response::new(proto::ListToolsResponse {
+                tools: vec![proto::McpTool {
+                    name: "echo".to_string(),
+                    description: "Echo the input".to_string(),
+                    input_schema: r#"{"type":"object"}"#.to_string(),
+                    ..Default::default()
+                }],
+            }))
+        }
+
+        async fn unregister_tool(
+            &self,
+            _request: Request<proto::UnregisterToolRequest>,
+        ) -> Result<Response<proto::UnregisterToolResponse>, Status> {
+            Err(Status::unimplemented("unregister_tool"))
+        }
+
+        async fn get_tool_schema(
+            &self,
+            _request: Request<proto::GetToolSchemaRequest>,
+        ) -> Result<Response<proto::GetToolSchemaResponse>, Status> {
+            Err(Status::unimplemented("get_tool_schema"))
+        }
+    }
+
+    #[tonic::async_trait]
+    impl KvService for StandIn {
+        type ScanStream = Pin<Box<dyn Stream<Item = Result<proto::KvScanResponse, Status>> + Send>>;
+
+        async fn get(
+            &self,
+            _request: Request<proto::KvGetRequest>,
+        ) -> Result<Response<proto::KvGetResponse>, Status> {
+            Err(Status::unimplemented("get"))
+        }
+
+        async fn put(
+            &self,
+            _request: Request<proto::KvPutRequest>,
+        ) -> Result<Response<proto::KvPutResponse>, Status> {
+            Err(Status::unimplemented("put"))
+        }
+
+        async fn delete(
+            &self,
+            _request: Request<proto::KvDeleteRequest>,
+        ) -> Result<Response<proto::KvDeleteResponse>, Status> {
+            Err(Status::unimplemented("delete"))
+        }
+
+        async fn scan(
+            &self,
+            request: Request<proto::KvScanRequest>,
+        ) -> Result<Response<Self::ScanStream>, Status> {
+            let prefix = request.into_inner().prefix;
+            let matches: Vec<proto::KvScanResponse> = ENTRIES
+                .iter()
+                .filter(|(key, _)| key.as_bytes().starts_with(&prefix))
+                .map(|(key, value)| proto::KvScanResponse {
+                    key: key.as_bytes().to_vec(),
+                    value: value.as_bytes().to_vec(),
+                })
+                .collect();
+            Ok(Response::new(Box::pin(tokio_stream::iter(matches.into_iter().map(Ok)))))
+        }
+
+        async fn batch_get(
+            &self,
+            _request: Request<proto::KvBatchGetRequest>,
+        ) -> Result<Response<proto::KvBatchGetResponse>, Status> {
+            Err(Status::unimplemented("batch_get"))
+        }
+
+        async fn batch_put(
+            &self,
+            _request: Request<proto::KvBatchPutRequest>,
+        ) -> Result<Response<proto::KvBatchPutResponse>, Status> {
+            Err(Status::unimplemented("batch_put"))
+        }
+    }
+
+    #[tonic::async_trait]
+    impl VectorIndexService for StandIn {
+        async fn create_index(
+            &self,
+            _request: Request<proto::CreateIndexRequest>,
+        ) -> Result<Response<proto::CreateIndexResponse>, Status> {
+            Err(Status::unimplemented("create_index"))
+        }
+
+        async fn drop_index(
+            &self,
+            _request: Request<proto::DropIndexRequest>,
+        ) -> Result<Response<proto::DropIndexResponse>, Status> {
+            Err(Status::unimplemented("drop_index"))
+        }
+
+        async fn insert_batch(
+            &self,
+            _request: Request<proto::InsertBatchRequest>,
+        ) -> Result<Response<proto::InsertBatchResponse>, Status> {
+            Err(Status::unimplemented("insert_batch"))
+        }
+
+        async fn insert_stream(
+            &self,
+            _request: Request<tonic::Streaming<proto::InsertStreamRequest>>,
+        ) -> Result<Response<proto::InsertStreamResponse>, Status> {
+            Err(Status::unimplemented("insert_stream"))
+        }
+
+        async fn search(
+            &self,
+            _request: Request<proto::SearchRequest>,
+        ) -> Result<Response<proto::SearchResponse>, Status> {
+            Err(Status::unimplemented("search"))
+        }
+
+        async fn search_batch(
+            &self,
+            _request: Request<proto::SearchBatchRequest>,
+        ) -> Result<Response<proto::SearchBatchResponse>, Status> {
+            Err(Status::unimplemented("search_batch"))
+        }
+
+        async fn get_stats(
+            &self,
+            _request: Request<proto::GetStatsRequest>,
+        ) -> Result<Response<proto::GetStatsResponse>, Status> {
+            Err(Status::unimplemented("get_stats"))
+        }
+
+        async fn health_check(
+            &self,
+            _request: Request<proto::HealthCheckRequest>,
+        ) -> Result<Response<proto::HealthCheckResponse>, Status> {
+            Ok(Response::new(proto::HealthCheckResponse {
+                status: proto::health_check_response::Status::Serving as i32,
+                version: "test".to_string(),
+                indexes: vec![],
+            }))
+        }
+    }
+
+    async fn exercise(backend: &RemoteBackend) {
+        let tools = backend.list_tools().await.unwrap();
+        assert_eq!(tools["tools"][0]["name"], "echo");
+        assert_eq!(tools["tools"][0]["inputSchema"]["type"], "object");
+
+        let result = backend.call_tool("echo", json!({"path": "users/1"})).await.unwrap();
+        assert_eq!(result["content"][0]["text"], r#"{"path":"users/1"}"#);
+        assert!(backend.call_tool("missing", json!({})).await.is_err());
+
+        let users = backend.scan(b"users/").await.unwrap();
+        assert_eq!(users.len(), 2);
+        assert_eq!(backend.scan(b"").await.unwrap().len(), ENTRIES.len());
+
+        let mut batches = backend.scan_stream(b"", 2).await.unwrap();
+        let mut streamed = Vec::new();
+        while let Some(batch) = batches.recv().await {
+            let batch = batch.unwrap();
+            assert!(batch.len() <= 2);
+            streamed.extend(batch);
+        }
+        assert_eq!(streamed, backend.scan(b"").await.unwrap());
+
+        assert!(backend.stats().await.unwrap().memtable_size_bytes.is_none());
+        assert!(backend.last_checkpoint().await.unwrap().is_none());
+        backend.health_check().await.unwrap();
+
+        let metadata = backend.metadata().await.unwrap();
+        assert_eq!(metadata.engine_version.as_deref(), Some("test"));
+        assert!(metadata.format_version.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_backend() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(
+            Server::builder()
+                .add_service(McpServiceServer::new(StandIn))
+                .add_service(KvServiceServer::new(StandIn))
+                .add_service(VectorIndexServiceServer::new(StandIn))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+
+        let backend = RemoteBackend::connect_tcp(&address).await.unwrap();
+        exercise(&backend).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_backend() {
+        let socket = std::env::temp_dir().join(format!("sochdb-studio-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = tokio::net::UnixListener::bind(&socket).unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(McpServiceServer::new(StandIn))
+                .add_service(KvServiceServer::new(StandIn))
+                .add_service(VectorIndexServiceServer::new(StandIn))
+                .serve_with_incoming(tokio_stream::wrappers::UnixListenerStream::new(listener)),
+        );
+
+        let backend = RemoteBackend::connect_unix(&socket).await.unwrap();
+        exercise(&backend).await;
+        let _ = std::fs::remove_file(&socket);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_server() {
+        assert!(RemoteBackend::connect_tcp("127.0.0.1:1").await.is_err());
+    }
+}
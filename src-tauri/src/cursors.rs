@@ -0,0 +1,493 @@
+//! Cursors over large query results
+//!
+//! A cursor hands a result out in batches. Reads that only filter and
+//! limit one access path are streamed off backend key scans, so the result
+//! is never held whole; anything else (sorts, vector searches) runs on the
+//! engine and is paged out of its one response, so it must be bounded to at
+//! most [`MAX_ENGINE_ROWS`] rows. Cursors left idle for
+//! [`CURSOR_TTL`] are dropped, as are those of closed sessions.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::connection::backend::ScanStream;
+use crate::connection::pool::Session;
+use crate::planner::ScanSpec;
+use crate::results;
+
+/// Rows per batch when the caller does not ask for a size
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// Largest batch a single fetch returns
+pub const MAX_BATCH_SIZE: usize = 10_000;
+
+/// Idle time after which a cursor is dropped
+pub const CURSOR_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Time between sweeps for expired cursors
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Open cursors allowed at once, across all sessions
+pub const MAX_OPEN_CURSORS: usize = 64;
+
+/// Rows a cursor holds from one engine response
+pub const MAX_ENGINE_ROWS: usize = 100_000;
+
+/// Key/value pairs read from the backend per scan batch
+const SCAN_BATCH_SIZE: usize = 1000;
+
+/// An open cursor, as returned by `open_cursor`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CursorInfo {
+    pub id: String,
+    pub connection_id: String,
+    pub query: String,
+    /// Columns known so far; batches may add more (see
+    /// [`CursorBatch::columns_added`])
+    pub columns: Vec<String>,
+    /// Rows are streamed off key scans rather than paged from one response
+    pub streamed: bool,
+}
+
+/// One batch of rows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CursorBatch {
+    pub cursor_id: String,
+    pub rows: Vec<Vec<Value>>,
+    /// Columns first seen in this batch, appended after the earlier ones.
+    /// Its rows have a value for every column; earlier rows lack these.
+    pub columns_added: Vec<String>,
+    /// Rows handed out so far, including this batch
+    pub fetched: u64,
+    /// No rows are left; the cursor is closed
+    pub done: bool,
+}
+
+/// Where a cursor's rows come from
+enum Source {
+    Scan(Box<ScanSource>),
+    /// Rows of one engine response, already split into columns
+    Rows(std::vec::IntoIter<Vec<Value>>),
+}
+
+struct ScanSource {
+    session: Arc<Session>,
+    spec: ScanSpec,
+    /// Prefixes not scanned yet
+    prefixes: VecDeque<String>,
+    /// The prefix being scanned and its batches
+    current: Option<(String, ScanStream)>,
+    batch: std::vec::IntoIter<(Vec<u8>, Vec<u8>)>,
+    skipped: usize,
+    returned: usize,
+}
+
+impl ScanSource {
+    /// The next row passing the filter, after the offset and within the limit
+    async fn next_row(&mut self) -> Result<Option<Value>, String> {
+        loop {
+            if self.spec.limit.is_some_and(|limit| self.returned >= limit) {
+                return Ok(None);
+            }
+            if let Some((key, value)) = self.batch.next() {
+                let prefix = &self.current.as_ref().expect("batches come from the current scan").0;
+                let Some(row) = self.spec.row(prefix, &key, &value) else {
+                    continue;
+                };
+                if self.skipped < self.spec.offset {
+                    self.skipped += 1;
+                    continue;
+                }
+                self.returned += 1;
+                return Ok(Some(row));
+            }
+            if let Some((_, stream)) = &mut self.current {
+                if let Some(batch) = stream.recv().await {
+                    self.batch = batch?.into_iter();
+                    continue;
+                }
+            }
+            let Some(prefix) = self.prefixes.pop_front() else {
+                return Ok(None);
+            };
+            let stream = self.session.backend.scan_stream(prefix.as_bytes(), SCAN_BATCH_SIZE).await?;
+            self.current = Some((prefix, stream));
+        }
+    }
+}
+
+/// A result being read batch by batch
+pub struct Cursor {
+    info: CursorInfo,
+    source: Source,
+    /// Rows read ahead to work out the columns
+    buffered: VecDeque<Value>,
+    /// Selected `*`, so later rows' keys become new columns
+    expand: bool,
+    fetched: u64,
+    last_used: Instant,
+}
+
+impl Cursor {
+    /// Stream the rows of `spec` off the session's key scans.
+    ///
+    /// A `*` select reads the first row to name its columns; keys later
+    /// rows bring are added as they appear.
+    pub async fn scan(id: String, session: Arc<Session>, query: String, spec: ScanSpec) -> Result<Self, String> {
+        let expand = spec.columns.is_empty() || spec.columns.iter().any(|c| c == "*");
+        let selected = spec.columns.clone();
+        let mut source = ScanSource {
+            session: session.clone(),
+            prefixes: spec.prefixes.iter().cloned().collect(),
+            spec,
+            current: None,
+            batch: Vec::new().into_iter(),
+            skipped: 0,
+            returned: 0,
+        };
+
+        let mut buffered = VecDeque::new();
+        let columns = if expand {
+            let first = source.next_row().await?;
+            let columns = results::column_names(first.as_slice(), &selected);
+            buffered.extend(first);
+            columns
+        } else {
+            selected
+        };
+
+        Ok(Self {
+            info: CursorInfo {
+                id,
+                connection_id: session.id.clone(),
+                query,
+                columns,
+                streamed: true,
+            },
+            source: Source::Scan(Box::new(source)),
+            buffered,
+            expand,
+            fetched: 0,
+            last_used: Instant::now(),
+        })
+    }
+
+    /// Page out rows the engine already returned
+    pub fn rows(id: String, connection_id: String, query: String, columns: Vec<String>, rows: Vec<Vec<Value>>) -> Self {
+        Self {
+            info: CursorInfo {
+                id,
+                connection_id,
+                query,
+                columns,
+                streamed: false,
+            },
+            source: Source::Rows(rows.into_iter()),
+            buffered: VecDeque::new(),
+            expand: false,
+            fetched: 0,
+            last_used: Instant::now(),
+        }
+    }
+
+    pub fn info(&self) -> &CursorInfo {
+        &self.info
+    }
+
+    /// The next `n` rows (at most [`MAX_BATCH_SIZE`])
+    pub async fn fetch(&mut self, n: usize) -> Result<CursorBatch, String> {
+        self.last_used = Instant::now();
+        let n = n.clamp(1, MAX_BATCH_SIZE);
+        let mut rows = Vec::with_capacity(n.min(DEFAULT_BATCH_SIZE));
+        let mut columns_added = Vec::new();
+        let mut done = false;
+        while rows.len() < n {
+            let row = match &mut self.source {
+                Source::Rows(source) => source.next(),
+                Source::Scan(source) => {
+                    let value = match self.buffered.pop_front() {
+                        Some(value) => Some(value),
+                        None => source.next_row().await?,
+                    };
+                    value.map(|value| split(&mut self.info.columns, self.expand, value, &mut columns_added))
+                }
+            };
+            match row {
+                Some(row) => rows.push(row),
+                None => {
+                    done = true;
+                    break;
+                }
+            }
+        }
+        self.fetched += rows.len() as u64;
+        Ok(CursorBatch {
+            cursor_id: self.info.id.clone(),
+            rows,
+            columns_added,
+            fetched: self.fetched,
+            done,
+        })
+    }
+}
+
+/// A row's values in column order, as `ResultSet::parse` splits them.
+///
+/// Columns the row brings that are not in `columns` yet are appended to it
+/// and to `added`: its keys if `expand`, and [`results::RESULT_COLUMN`],
+/// the only column a row that is not an object fills.
+fn split(columns: &mut Vec<String>, expand: bool, row: Value, added: &mut Vec<String>) -> Vec<Value> {
+    let mut add = |name: &str| {
+        if !columns.iter().any(|c| c == name) {
+            columns.push(name.to_string());
+            added.push(name.to_string());
+        }
+    };
+    match row {
+        Value::Object(mut object) => {
+            if expand {
+                object.keys().for_each(|key| add(key));
+            }
+            columns
+                .iter()
+                .map(|column| object.remove(column).unwrap_or(Value::Null))
+                .collect()
+        }
+        row => {
+            add(results::RESULT_COLUMN);
+            columns
+                .iter()
+                .map(|column| if column == results::RESULT_COLUMN { row.clone() } else { Value::Null })
+                .collect()
+        }
+    }
+}
+
+/// Open cursors by id
+#[derive(Default)]
+pub struct CursorRegistry {
+    cursors: HashMap<String, Arc<Mutex<Cursor>>>,
+    next_id: AtomicU64,
+}
+
+impl CursorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a cursor under its id
+    pub fn insert(&mut self, cursor: Cursor) -> Result<(), String> {
+        if self.cursors.len() >= MAX_OPEN_CURSORS {
+            return Err(format!("Too many open cursors (limit {}); close some first", MAX_OPEN_CURSORS));
+        }
+        self.cursors.insert(cursor.info.id.clone(), Arc::new(Mutex::new(cursor)));
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Result<Arc<Mutex<Cursor>>, String> {
+        self.cursors
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("Unknown or expired cursor: {}", id))
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<(), String> {
+        self.cursors
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| format!("Unknown or expired cursor: {}", id))
+    }
+
+    /// Drop cursors idle past [`CURSOR_TTL`] at `now` or whose session is
+    /// not in `open`. Returns how many were dropped.
+    pub fn expire(&mut self, now: Instant, open: &[&str]) -> usize {
+        let before = self.cursors.len();
+        self.cursors.retain(|_, cursor| match cursor.try_lock() {
+            Ok(cursor) => {
+                open.contains(&cursor.info.connection_id.as_str())
+                    && now.saturating_duration_since(cursor.last_used) < CURSOR_TTL
+            }
+            // Locked means a fetch is running right now
+            Err(_) => true,
+        });
+        before - self.cursors.len()
+    }
+
+    /// A fresh cursor id; never handed out twice, even to concurrent opens
+    pub fn next_id(&self) -> String {
+        format!("cursor-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    use crate::connection::backend::{BackendStats, CheckpointMark, DatabaseBackend, DatabaseMetadata};
+    use crate::connection::manager::ConnectionType;
+    use crate::planner::{self, Catalog};
+
+    /// In-memory rows `users/0` .. `users/n`
+    struct Rows(Vec<(Vec<u8>, Vec<u8>)>);
+
+    #[async_trait]
+    impl DatabaseBackend for Rows {
+        async fn list_tools(&self) -> Result<Value, String> {
+            Ok(json!({ "tools": [] }))
+        }
+
+        async fn call_tool(&self, name: &str, _arguments: Value) -> Result<Value, String> {
+            Err(format!("No tool {}", name))
+        }
+
+        async fn scan(&self, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, String> {
+            Ok(self.0.iter().filter(|(k, _)| k.starts_with(prefix)).cloned().collect())
+        }
+
+        async fn stats(&self) -> Result<BackendStats, String> {
+            Ok(BackendStats::default())
+        }
+
+        async fn last_checkpoint(&self) -> Result<Option<CheckpointMark>, String> {
+            Ok(None)
+        }
+
+        async fn metadata(&self) -> Result<DatabaseMetadata, String> {
+            Ok(DatabaseMetadata::default())
+        }
+
+        async fn health_check(&self) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn session(rows: u64) -> Arc<Session> {
+        let mut entries: Vec<(Vec<u8>, Vec<u8>)> = (0..rows)
+            .map(|i| {
+                let value = json!({ "name": format!("user {}", i), "age": i % 50 });
+                (format!("users/{:05}", i).into_bytes(), value.to_string().into_bytes())
+            })
+            .collect();
+        entries.push((b"orders/1".to_vec(), b"not json".to_vec()));
+        session_with(entries)
+    }
+
+    fn session_with(entries: Vec<(Vec<u8>, Vec<u8>)>) -> Arc<Session> {
+        Arc::new(Session::new("main".into(), "/data/main".into(), ConnectionType::Embedded, Box::new(Rows(entries)), false))
+    }
+
+    async fn open(query: &str, rows: u64) -> Cursor {
+        open_on(session(rows), query).await
+    }
+
+    async fn open_on(session: Arc<Session>, query: &str) -> Cursor {
+        let spec = planner::plan(query, &Catalog::default()).unwrap().scan_spec().unwrap();
+        Cursor::scan("cursor-1".into(), session, query.into(), spec).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_streamed_batches() {
+        let mut cursor = open("SELECT * FROM users WHERE age < 10", 2500).await;
+        assert!(cursor.info().streamed);
+        assert_eq!(cursor.info().columns, vec!["age", "name"]);
+
+        let mut total = 0;
+        loop {
+            let batch = cursor.fetch(120).await.unwrap();
+            assert!(batch.rows.len() <= 120);
+            assert!(batch.rows.iter().all(|row| row[0].as_u64().unwrap() < 10));
+            total += batch.rows.len();
+            assert_eq!(batch.fetched, total as u64);
+            if batch.done {
+                break;
+            }
+        }
+        assert_eq!(total, 500);
+
+        let mut limited = open("SELECT name FROM users LIMIT 3 OFFSET 1000", 2500).await;
+        assert_eq!(limited.info().columns, vec!["name"]);
+        let batch = limited.fetch(10).await.unwrap();
+        assert!(batch.done);
+        assert_eq!(batch.rows, vec![vec![json!("user 1000")], vec![json!("user 1001")], vec![json!("user 1002")]]);
+    }
+
+    #[tokio::test]
+    async fn test_key_lookups_and_raw_rows() {
+        let mut lookup = open("SELECT * FROM users WHERE id IN ('00007', '00042', '99999')", 100).await;
+        let batch = lookup.fetch(DEFAULT_BATCH_SIZE).await.unwrap();
+        assert_eq!(batch.rows, vec![vec![json!(7), json!("user 7")], vec![json!(42), json!("user 42")]]);
+
+        let mut raw = open("SCAN 'orders/'", 0).await;
+        assert_eq!(raw.info().columns, vec!["result"]);
+        assert_eq!(raw.fetch(5).await.unwrap().rows, vec![vec![json!("not json")]]);
+
+        let mut empty = open("SELECT * FROM users", 0).await;
+        assert!(empty.fetch(5).await.unwrap().done);
+
+        assert!(planner::plan("SELECT * FROM users ORDER BY name", &Catalog::default()).unwrap().scan_spec().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_columns_added_and_non_object_rows() {
+        let entries = vec![
+            (b"events/1".to_vec(), json!({ "kind": "login" }).to_string().into_bytes()),
+            (b"events/2".to_vec(), json!({ "kind": "logout", "reason": "idle" }).to_string().into_bytes()),
+            (b"events/3".to_vec(), b"not json".to_vec()),
+        ];
+
+        // Keys first seen in a later row are reported with its batch
+        let mut all = open_on(session_with(entries.clone()), "SELECT * FROM events").await;
+        assert_eq!(all.info().columns, vec!["kind"]);
+        let batch = all.fetch(1).await.unwrap();
+        assert_eq!((batch.rows, batch.columns_added), (vec![vec![json!("login")]], vec![]));
+        let batch = all.fetch(1).await.unwrap();
+        assert_eq!(batch.rows, vec![vec![json!("logout"), json!("idle")]]);
+        assert_eq!(batch.columns_added, vec!["reason"]);
+        let batch = all.fetch(5).await.unwrap();
+        assert_eq!(batch.rows, vec![vec![json!(null), json!(null), json!("not json")]]);
+        assert_eq!(batch.columns_added, vec!["result"]);
+        assert_eq!(all.info().columns, vec!["kind", "reason", "result"]);
+
+        // Explicit columns stay put, but a row that is not an object still
+        // fills the result column rather than the first selected one
+        let mut kinds = open_on(session_with(entries), "SELECT kind FROM events").await;
+        let batch = kinds.fetch(5).await.unwrap();
+        assert_eq!(batch.columns_added, vec!["result"]);
+        assert_eq!(
+            batch.rows,
+            vec![vec![json!("login")], vec![json!("logout")], vec![json!(null), json!("not json")]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_registry_expiry() {
+        let rows = vec![vec![json!(1)], vec![json!(2)], vec![json!(3)]];
+        let mut cursor = Cursor::rows("cursor-1".into(), "main".into(), "SELECT 1".into(), vec!["result".into()], rows);
+        let batch = cursor.fetch(2).await.unwrap();
+        assert_eq!((batch.rows.len(), batch.done), (2, false));
+        assert!(cursor.fetch(2).await.unwrap().done);
+
+        let mut registry = CursorRegistry::new();
+        registry.insert(cursor).unwrap();
+        assert_ne!(registry.next_id(), registry.next_id());
+        registry.insert(Cursor::rows("cursor-2".into(), "replica".into(), "SELECT 1".into(), vec![], vec![])).unwrap();
+
+        // Closing the replica session drops its cursor
+        assert_eq!(registry.expire(Instant::now(), &["main"]), 1);
+        assert!(registry.get("cursor-2").is_err());
+
+        let held = registry.get("cursor-1").unwrap();
+        let fetching = held.lock().await;
+        assert_eq!(registry.expire(Instant::now() + CURSOR_TTL, &["main"]), 0);
+        drop(fetching);
+        assert_eq!(registry.expire(Instant::now() + CURSOR_TTL, &["main"]), 1);
+        assert!(registry.remove("cursor-1").is_err());
+    }
+}
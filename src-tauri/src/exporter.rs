@@ -0,0 +1,487 @@
+//! Prometheus / OpenMetrics exporter
+//!
+//! An optional HTTP endpoint, off by default and bound to 127.0.0.1, that
+//! serves `/metrics` in OpenMetrics text format: the `get_stats` fields and
+//! transaction counters of every open session, plus the `execute_query`
+//! latency and MCP tool calls Studio itself observed.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use crate::connection::backend::{BackendStats, CheckpointMark};
+use crate::state::AppState;
+
+/// Port used until the user picks another
+pub const DEFAULT_PORT: u16 = 9464;
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Upper bounds (seconds) of the query latency histogram buckets
+pub const LATENCY_BUCKETS: [f64; 12] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Exporter settings, persisted across restarts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExporterConfig {
+    pub enabled: bool,
+    /// Port to listen on; the endpoint only ever binds 127.0.0.1
+    pub port: u16,
+}
+
+impl Default for ExporterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_PORT,
+        }
+    }
+}
+
+/// Exporter settings and where it is listening
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExporterStatus {
+    #[serde(flatten)]
+    pub config: ExporterConfig,
+    /// Scrape URL while the endpoint is running
+    pub url: Option<String>,
+}
+
+/// Cumulative latency histogram
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    /// Observations at or below each of [`LATENCY_BUCKETS`]
+    pub buckets: [u64; LATENCY_BUCKETS.len()],
+    pub count: u64,
+    /// Sum of all observations in seconds
+    pub sum: f64,
+}
+
+impl Histogram {
+    pub fn observe(&mut self, seconds: f64) {
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&mut self.buckets) {
+            if seconds <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+/// Query and tool call counts Studio observed on one session
+#[derive(Debug, Default)]
+pub struct SessionTelemetry {
+    inner: parking_lot::Mutex<TelemetrySnapshot>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TelemetrySnapshot {
+    /// `execute_query` latency, failed queries included
+    pub query_latency: Histogram,
+    pub query_errors: u64,
+    /// Succeeded and failed calls per MCP tool
+    pub tool_calls: BTreeMap<String, (u64, u64)>,
+}
+
+impl SessionTelemetry {
+    pub fn record_query(&self, elapsed: Duration, ok: bool) {
+        let mut inner = self.inner.lock();
+        inner.query_latency.observe(elapsed.as_secs_f64());
+        if !ok {
+            inner.query_errors += 1;
+        }
+    }
+
+    pub fn record_tool_call(&self, tool: &str, ok: bool) {
+        let mut inner = self.inner.lock();
+        let (succeeded, failed) = inner.tool_calls.entry(tool.to_string()).or_default();
+        if ok {
+            *succeeded += 1;
+        } else {
+            *failed += 1;
+        }
+    }
+
+    pub fn snapshot(&self) -> TelemetrySnapshot {
+        self.inner.lock().clone()
+    }
+}
+
+/// Everything exported for one open session
+#[derive(Debug, Clone, Default)]
+pub struct SessionMetrics {
+    pub connection_id: String,
+    pub uptime_seconds: u64,
+    /// `None` if the backend could not be reached
+    pub stats: Option<BackendStats>,
+    pub last_checkpoint: Option<CheckpointMark>,
+    /// Cached collection and key counts, once a scan has finished
+    pub collections: Option<usize>,
+    pub keys: Option<u64>,
+    /// Unix timestamp (ms) the cached counts were computed
+    pub counts_computed_at: Option<u64>,
+    pub counts_refreshing: bool,
+    pub telemetry: TelemetrySnapshot,
+}
+
+type Field = fn(&SessionMetrics) -> Option<f64>;
+
+/// Per-session gauges: name, help, value
+const GAUGES: &[(&str, &str, Field)] = &[
+    ("sochdb_memtable_size_bytes", "Bytes held in the active and immutable memtables",
+        |s| s.stats.as_ref()?.memtable_size_bytes.map(|v| v as f64)),
+    ("sochdb_wal_size_bytes", "Total size of the WAL segments on disk",
+        |s| s.stats.as_ref()?.wal_size_bytes.map(|v| v as f64)),
+    ("sochdb_collections", "Collections counted by the last background scan",
+        |s| s.collections.map(|v| v as f64)),
+    ("sochdb_keys", "Keys counted by the last background scan",
+        |s| s.keys.map(|v| v as f64)),
+    ("sochdb_counts_computed_timestamp_seconds", "Time the last background scan finished",
+        |s| s.counts_computed_at.map(|v| v as f64 / 1000.0)),
+    ("sochdb_counts_refreshing", "Whether a background scan is running",
+        |s| Some(if s.counts_refreshing { 1.0 } else { 0.0 })),
+    ("sochdb_active_transactions", "Transactions started but neither committed nor aborted",
+        |s| s.stats.as_ref()?.active_transactions().map(|v| v as f64)),
+    ("sochdb_last_checkpoint_lsn", "LSN of the most recent WAL checkpoint",
+        |s| s.last_checkpoint.map(|c| c.lsn as f64)),
+    ("sochdb_last_checkpoint_timestamp_seconds", "Time the most recent WAL checkpoint completed",
+        |s| s.last_checkpoint.map(|c| c.completed_at as f64 / 1000.0)),
+    ("sochdb_uptime_seconds", "Seconds since Studio opened the session",
+        |s| Some(s.uptime_seconds as f64)),
+    ("sochdb_active_snapshots", "Open MVCC read snapshots",
+        |s| s.stats.as_ref()?.active_snapshots.map(|v| v as f64)),
+    ("sochdb_min_active_timestamp", "Oldest timestamp still visible to an open snapshot",
+        |s| s.stats.as_ref()?.min_active_timestamp.map(|v| v as f64)),
+    ("sochdb_garbage_versions", "Superseded versions waiting for GC",
+        |s| s.stats.as_ref()?.garbage_versions.map(|v| v as f64)),
+];
+
+/// Per-session counters from the backend: name (without `_total`), help, value
+const COUNTERS: &[(&str, &str, Field)] = &[
+    ("sochdb_bytes_written", "Bytes written by the storage engine",
+        |s| s.stats.as_ref()?.bytes_written.map(|v| v as f64)),
+    ("sochdb_bytes_read", "Bytes read by the storage engine",
+        |s| s.stats.as_ref()?.bytes_read.map(|v| v as f64)),
+    ("sochdb_transactions_started", "Transactions started",
+        |s| s.stats.as_ref()?.transactions_started.map(|v| v as f64)),
+    ("sochdb_transactions_committed", "Transactions committed",
+        |s| s.stats.as_ref()?.transactions_committed.map(|v| v as f64)),
+    ("sochdb_transactions_aborted", "Transactions aborted",
+        |s| s.stats.as_ref()?.transactions_aborted.map(|v| v as f64)),
+    ("sochdb_studio_query_errors", "Queries run from Studio that failed",
+        |s| Some(s.telemetry.query_errors as f64)),
+];
+
+/// Render sessions in OpenMetrics text format.
+///
+/// Values a backend cannot report are left out rather than exported as zero.
+pub fn render(sessions: &[SessionMetrics]) -> String {
+    let mut out = String::new();
+
+    family(&mut out, "sochdb_studio", "info", "SochDB Studio build");
+    line(&mut out, "sochdb_studio_info", &[("version", env!("CARGO_PKG_VERSION"))], 1.0);
+
+    family(&mut out, "sochdb_up", "gauge", "Whether the session's backend answered the scrape");
+    for session in sessions {
+        let up = if session.stats.is_some() { 1.0 } else { 0.0 };
+        line(&mut out, "sochdb_up", &[("connection", &session.connection_id)], up);
+    }
+
+    for (name, help, field) in GAUGES {
+        family(&mut out, name, "gauge", help);
+        for session in sessions {
+            if let Some(value) = field(session) {
+                line(&mut out, name, &[("connection", &session.connection_id)], value);
+            }
+        }
+    }
+
+    for (name, help, field) in COUNTERS {
+        family(&mut out, name, "counter", help);
+        let sample = format!("{}_total", name);
+        for session in sessions {
+            if let Some(value) = field(session) {
+                line(&mut out, &sample, &[("connection", &session.connection_id)], value);
+            }
+        }
+    }
+
+    let name = "sochdb_studio_query_duration_seconds";
+    family(&mut out, name, "histogram", "Latency of queries run from Studio");
+    for session in sessions {
+        let connection = session.connection_id.as_str();
+        let histogram = &session.telemetry.query_latency;
+        let bucket = format!("{}_bucket", name);
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+            line(&mut out, &bucket, &[("connection", connection), ("le", &format!("{:?}", bound))], count as f64);
+        }
+        line(&mut out, &bucket, &[("connection", connection), ("le", "+Inf")], histogram.count as f64);
+        line(&mut out, &format!("{}_count", name), &[("connection", connection)], histogram.count as f64);
+        line(&mut out, &format!("{}_sum", name), &[("connection", connection)], histogram.sum);
+    }
+
+    let name = "sochdb_studio_mcp_tool_calls";
+    family(&mut out, name, "counter", "MCP tool calls made by Studio");
+    for session in sessions {
+        for (tool, (succeeded, failed)) in &session.telemetry.tool_calls {
+            for (outcome, count) in [("ok", succeeded), ("error", failed)] {
+                let labels = [("connection", session.connection_id.as_str()), ("tool", tool), ("outcome", outcome)];
+                line(&mut out, "sochdb_studio_mcp_tool_calls_total", &labels, *count as f64);
+            }
+        }
+    }
+
+    out.push_str("# EOF\n");
+    out
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+}
+
+fn line(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Snapshot every open session for a scrape
+pub async fn gather(state: &AppState) -> Vec<SessionMetrics> {
+    let sessions = state.connections.read().await.pool().list().await;
+    let mut metrics = Vec::with_capacity(sessions.len());
+    for session in sessions {
+        // Serve cached counts; a scrape never starts a full scan
+        let counts = state.collection_stats.read().await.get(&session.id);
+        let snapshot = counts.snapshot.as_ref();
+        metrics.push(SessionMetrics {
+            connection_id: session.id.clone(),
+            uptime_seconds: session.uptime_seconds(),
+            stats: session.backend.stats().await.ok(),
+            last_checkpoint: session.backend.last_checkpoint().await.ok().flatten(),
+            collections: snapshot.map(|s| s.collections.len()),
+            keys: snapshot.map(|s| s.total_keys),
+            counts_computed_at: snapshot.map(|s| s.computed_at),
+            counts_refreshing: counts.refreshing,
+            telemetry: session.telemetry.snapshot(),
+        });
+    }
+    metrics
+}
+
+/// The HTTP endpoint and its settings
+#[derive(Debug, Default)]
+pub struct Exporter {
+    config: parking_lot::Mutex<ExporterConfig>,
+    running: parking_lot::Mutex<Option<(SocketAddr, JoinHandle<()>)>>,
+}
+
+impl Exporter {
+    /// Apply `config`: restart the endpoint on its port if enabled, stop it
+    /// otherwise. On a bind error the endpoint stays stopped.
+    pub async fn apply(&self, state: Arc<AppState>, config: ExporterConfig) -> Result<(), String> {
+        self.stop().await;
+        *self.config.lock() = ExporterConfig { enabled: false, ..config };
+        if !config.enabled {
+            return Ok(());
+        }
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, config.port))
+            .await
+            .map_err(|e| format!("Failed to listen on 127.0.0.1:{}: {}", config.port, e))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to read metrics endpoint address: {}", e))?;
+        let task = tokio::spawn(serve(listener, state));
+
+        *self.running.lock() = Some((addr, task));
+        *self.config.lock() = config;
+        Ok(())
+    }
+
+    /// Stop the endpoint, waiting until its port is released
+    pub async fn stop(&self) {
+        let running = self.running.lock().take();
+        if let Some((_, task)) = running {
+            task.abort();
+            let _ = task.await;
+        }
+    }
+
+    pub fn status(&self) -> ExporterStatus {
+        ExporterStatus {
+            config: *self.config.lock(),
+            url: self.running.lock().as_ref().map(|(addr, _)| format!("http://{}/metrics", addr)),
+        }
+    }
+}
+
+async fn serve(listener: TcpListener, state: Arc<AppState>) {
+    loop {
+        let mut stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                eprintln!("Metrics endpoint failed to accept a connection: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let state = state.clone();
+        tokio::spawn(async move {
+            let scrape = async { render(&gather(&state).await) };
+            // A client that stalls past the timeout is just dropped
+            if let Ok(Err(e)) = tokio::time::timeout(REQUEST_TIMEOUT, respond(&mut stream, scrape)).await {
+                eprintln!("Metrics endpoint request failed: {}", e);
+            }
+        });
+    }
+}
+
+/// Answer one HTTP request: `GET /metrics` gets the rendered scrape
+async fn respond<S, F>(stream: &mut S, scrape: F) -> std::io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: Future<Output = String>,
+{
+    let mut request = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_BYTES {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&chunk[..n]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.lines().next().unwrap_or("").split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("").split('?').next().unwrap_or("");
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", CONTENT_TYPE, scrape.await),
+        (_, "/metrics") => ("405 Method Not Allowed", "text/plain", "Method not allowed\n".to_string()),
+        _ => ("404 Not Found", "text/plain", "Not found; metrics are served at /metrics\n".to_string()),
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_and_telemetry() {
+        let telemetry = SessionTelemetry::default();
+        telemetry.record_query(Duration::from_millis(3), true);
+        telemetry.record_query(Duration::from_millis(300), false);
+        telemetry.record_query(Duration::from_secs(30), true);
+        telemetry.record_tool_call("sochdb_query", true);
+        telemetry.record_tool_call("sochdb_query", false);
+        telemetry.record_tool_call("sochdb_get", true);
+
+        let snapshot = telemetry.snapshot();
+        let histogram = &snapshot.query_latency;
+        assert_eq!(histogram.count, 3);
+        assert_eq!(histogram.buckets[0], 0);
+        assert_eq!(histogram.buckets[1], 1);
+        assert_eq!(histogram.buckets[7], 2);
+        assert_eq!(histogram.buckets[LATENCY_BUCKETS.len() - 1], 2);
+        assert!((histogram.sum - 30.303).abs() < 1e-9);
+        assert_eq!(snapshot.query_errors, 1);
+        assert_eq!(snapshot.tool_calls["sochdb_query"], (1, 1));
+        assert_eq!(snapshot.tool_calls["sochdb_get"], (1, 0));
+    }
+
+    #[test]
+    fn test_render() {
+        let telemetry = SessionTelemetry::default();
+        telemetry.record_query(Duration::from_millis(20), true);
+        telemetry.record_tool_call("sochdb_query", true);
+        let sessions = [
+            SessionMetrics {
+                connection_id: "db \"main\"".to_string(),
+                stats: Some(BackendStats {
+                    transactions_started: Some(12),
+                    transactions_committed: Some(9),
+                    transactions_aborted: Some(1),
+                    wal_size_bytes: Some(4096),
+                    ..Default::default()
+                }),
+                last_checkpoint: Some(CheckpointMark { lsn: 42, completed_at: 1_500 }),
+                telemetry: telemetry.snapshot(),
+                ..Default::default()
+            },
+            SessionMetrics {
+                connection_id: "remote".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let text = render(&sessions);
+        assert!(text.ends_with("# EOF\n"));
+        assert!(text.contains("# TYPE sochdb_transactions_started counter\n"));
+        assert!(text.contains("sochdb_transactions_started_total{connection=\"db \\\"main\\\"\"} 12\n"));
+        assert!(text.contains("sochdb_active_transactions{connection=\"db \\\"main\\\"\"} 2\n"));
+        assert!(text.contains("sochdb_last_checkpoint_timestamp_seconds{connection=\"db \\\"main\\\"\"} 1.5\n"));
+        assert!(text.contains("sochdb_up{connection=\"remote\"} 0\n"));
+        assert!(text.contains(
+            "sochdb_studio_query_duration_seconds_bucket{connection=\"db \\\"main\\\"\",le=\"0.025\"} 1\n"
+        ));
+        assert!(text.contains("sochdb_studio_query_duration_seconds_bucket{connection=\"remote\",le=\"+Inf\"} 0\n"));
+        assert!(text.contains(
+            "sochdb_studio_mcp_tool_calls_total{connection=\"db \\\"main\\\"\",tool=\"sochdb_query\",outcome=\"ok\"} 1\n"
+        ));
+        // Unreported values are left out, not exported as zero
+        assert!(!text.contains("sochdb_wal_size_bytes{connection=\"remote\"}"));
+        assert!(!text.contains("sochdb_bytes_written_total"));
+    }
+
+    #[tokio::test]
+    async fn test_respond() {
+        async fn request(raw: &str) -> String {
+            let (mut client, mut server) = tokio::io::duplex(64 * 1024);
+            client.write_all(raw.as_bytes()).await.unwrap();
+            respond(&mut server, async { "# EOF\n".to_string() }).await.unwrap();
+            let mut response = String::new();
+            client.read_to_string(&mut response).await.unwrap();
+            response
+        }
+
+        let ok = request("GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+        assert!(ok.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(ok.contains(&format!("Content-Type: {}\r\n", CONTENT_TYPE)));
+        assert!(ok.ends_with("\r\n\r\n# EOF\n"));
+
+        assert!(request("GET / HTTP/1.1\r\n\r\n").await.starts_with("HTTP/1.1 404"));
+        assert!(request("POST /metrics HTTP/1.1\r\n\r\n").await.starts_with("HTTP/1.1 405"));
+    }
+}
@@ -0,0 +1,293 @@
+//! Query history
+//!
+//! Every query run through `execute_query`, and every script run through
+//! `execute_script`, is recorded with its connection, duration, row count
+//! or error. Unpinned entries are dropped oldest first
+//! beyond [`MAX_ENTRIES`]; pinned favourites are kept until unpinned.
+
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+
+/// Unpinned entries kept
+pub const MAX_ENTRIES: usize = 1000;
+
+/// What a history entry ran
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryKind {
+    #[default]
+    Query,
+    /// A `;`-separated script, rerun as a whole
+    Script,
+}
+
+/// A finished query or script, as handed to [`QueryHistory::record`]
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub kind: QueryKind,
+    pub query: String,
+    /// Unix timestamp (ms) the run started
+    pub executed_at: u64,
+    pub execution_time_ms: f64,
+    /// Rows returned, or the error
+    pub result: Result<usize, String>,
+}
+
+/// One executed query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: String,
+    #[serde(default)]
+    pub kind: QueryKind,
+    pub query: String,
+    /// Session the query ran on
+    pub connection_id: String,
+    /// Database path, or server address for remote connections
+    pub connection_path: String,
+    /// Unix timestamp (ms) the query started
+    pub executed_at: u64,
+    pub execution_time_ms: f64,
+    /// Rows returned (`null` if the query failed)
+    pub row_count: Option<usize>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+/// Criteria for [`QueryHistory::search`]; unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryFilter {
+    /// Case-insensitive substring of the query text
+    pub text: Option<String>,
+    /// Connection id or path
+    pub connection: Option<String>,
+    /// Unix timestamps (ms), inclusive
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    #[serde(default)]
+    pub pinned_only: bool,
+    pub limit: Option<usize>,
+}
+
+impl HistoryFilter {
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        let text = self.text.as_ref().map(|t| t.to_lowercase());
+        text.is_none_or(|t| entry.query.to_lowercase().contains(&t))
+            && self
+                .connection
+                .as_ref()
+                .is_none_or(|c| &entry.connection_id == c || &entry.connection_path == c)
+            && self.from.is_none_or(|from| entry.executed_at >= from)
+            && self.to.is_none_or(|to| entry.executed_at <= to)
+            && (!self.pinned_only || entry.pinned)
+    }
+}
+
+/// Recorded queries, oldest first
+#[derive(Debug, Default, Clone)]
+pub struct QueryHistory {
+    entries: VecDeque<HistoryEntry>,
+    /// Bumped on every change, so a copy saved after the lock is released
+    /// can be told apart from a newer one
+    revision: u64,
+}
+
+impl QueryHistory {
+    pub fn new(entries: Vec<HistoryEntry>) -> Self {
+        let mut history = Self {
+            entries: entries.into(),
+            revision: 0,
+        };
+        history.prune();
+        history
+    }
+
+    /// Number of changes since the history was loaded
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// All entries, oldest first
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    /// Record a finished run on a connection
+    pub fn record(&mut self, run: Run, connection_id: String, connection_path: String) -> HistoryEntry {
+        let Run { kind, query, executed_at, execution_time_ms, result } = run;
+        let (row_count, error) = match result {
+            Ok(rows) => (Some(rows), None),
+            Err(e) => (None, Some(e)),
+        };
+        let entry = HistoryEntry {
+            id: self.next_id(executed_at),
+            kind,
+            query,
+            connection_id,
+            connection_path,
+            executed_at,
+            execution_time_ms,
+            row_count,
+            error,
+            pinned: false,
+        };
+        self.entries.push_back(entry.clone());
+        self.prune();
+        self.revision += 1;
+        entry
+    }
+
+    pub fn get(&self, id: &str) -> Option<&HistoryEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    /// Matching entries, newest first
+    pub fn search(&self, filter: &HistoryFilter) -> Vec<HistoryEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|e| filter.matches(e))
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
+    /// Pin or unpin an entry
+    pub fn set_pinned(&mut self, id: &str, pinned: bool) -> Result<HistoryEntry, String> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or_else(|| format!("Unknown history entry: {}", id))?;
+        entry.pinned = pinned;
+        let entry = entry.clone();
+        self.prune();
+        self.revision += 1;
+        Ok(entry)
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<(), String> {
+        let before = self.entries.len();
+        self.entries.retain(|e| e.id != id);
+        if self.entries.len() == before {
+            return Err(format!("Unknown history entry: {}", id));
+        }
+        self.revision += 1;
+        Ok(())
+    }
+
+    /// Drop unpinned entries, optionally only those of one connection
+    pub fn clear(&mut self, connection: Option<&str>) {
+        self.entries.retain(|e| {
+            e.pinned || connection.is_some_and(|c| e.connection_id != c && e.connection_path != c)
+        });
+        self.revision += 1;
+    }
+
+    fn next_id(&self, now: u64) -> String {
+        let base = format!("query-{}", now);
+        let mut id = base.clone();
+        let mut n = 1;
+        while self.entries.iter().any(|e| e.id == id) {
+            id = format!("{}-{}", base, n);
+            n += 1;
+        }
+        id
+    }
+
+    fn prune(&mut self) {
+        let mut excess = self.entries.iter().filter(|e| !e.pinned).count().saturating_sub(MAX_ENTRIES);
+        self.entries.retain(|e| {
+            if excess > 0 && !e.pinned {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(query: &str, executed_at: u64, execution_time_ms: f64, result: Result<usize, String>) -> Run {
+        Run { kind: QueryKind::Query, query: query.to_string(), executed_at, execution_time_ms, result }
+    }
+
+    fn history() -> QueryHistory {
+        let mut history = QueryHistory::default();
+        history.record(run("SELECT * FROM users", 1_000, 2.5, Ok(3)), "main".into(), "/data/main".into());
+        history.record(run("SELECT * FROM orders", 2_000, 1.0, Ok(0)), "main".into(), "/data/main".into());
+        history.record(
+            run("select * from Users where id = 1", 3_000, 0.5, Err("Connection lost".into())),
+            "replica".into(),
+            "db.internal:7070".into(),
+        );
+        history
+    }
+
+    #[test]
+    fn test_search() {
+        let history = history();
+
+        let all = history.search(&HistoryFilter::default());
+        assert_eq!(all.iter().map(|e| e.executed_at).collect::<Vec<_>>(), vec![3_000, 2_000, 1_000]);
+        assert_eq!(all[0].error.as_deref(), Some("Connection lost"));
+        assert_eq!(all[0].row_count, None);
+
+        let users = HistoryFilter { text: Some("USERS".into()), ..Default::default() };
+        assert_eq!(history.search(&users).len(), 2);
+
+        let by_path = HistoryFilter { connection: Some("/data/main".into()), ..Default::default() };
+        assert_eq!(history.search(&by_path).len(), 2);
+
+        let range = HistoryFilter { from: Some(1_500), to: Some(2_500), ..Default::default() };
+        assert_eq!(history.search(&range)[0].query, "SELECT * FROM orders");
+
+        let limited = HistoryFilter { limit: Some(1), ..Default::default() };
+        assert_eq!(history.search(&limited)[0].executed_at, 3_000);
+
+        // Entries saved before scripts were recorded are queries
+        let mut saved = serde_json::to_value(&all[0]).unwrap();
+        saved.as_object_mut().unwrap().remove("kind");
+        assert_eq!(serde_json::from_value::<HistoryEntry>(saved).unwrap().kind, QueryKind::Query);
+    }
+
+    #[test]
+    fn test_pin_remove_and_clear() {
+        let mut history = history();
+        let id = history.search(&HistoryFilter::default())[2].id.clone();
+
+        assert!(history.set_pinned(&id, true).unwrap().pinned);
+        let pinned = HistoryFilter { pinned_only: true, ..Default::default() };
+        assert_eq!(history.search(&pinned).len(), 1);
+        assert!(history.set_pinned("query-0", true).is_err());
+
+        let revision = history.revision();
+        history.clear(Some("replica"));
+        assert_eq!(history.entries().len(), 2);
+        assert!(history.revision() > revision);
+        history.clear(None);
+        assert_eq!(history.entries().len(), 1);
+        assert_eq!(history.get(&id).unwrap().query, "SELECT * FROM users");
+
+        history.remove(&id).unwrap();
+        assert!(history.remove(&id).is_err());
+    }
+
+    #[test]
+    fn test_prune_keeps_pinned() {
+        let mut history = QueryHistory::default();
+        let first = history.record(run("SELECT 1", 0, 0.1, Ok(1)), "main".into(), "/data/main".into());
+        history.set_pinned(&first.id, true).unwrap();
+        for i in 0..MAX_ENTRIES as u64 + 5 {
+            history.record(run(&format!("SELECT {}", i), i + 1, 0.1, Ok(1)), "main".into(), "/data/main".into());
+        }
+
+        let entries = history.entries();
+        assert_eq!(entries.len(), MAX_ENTRIES + 1);
+        assert_eq!(entries[0].id, first.id);
+        assert_eq!(entries[1].executed_at, 6);
+    }
+}
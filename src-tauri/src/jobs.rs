@@ -0,0 +1,387 @@
+//! Background jobs for long-running admin operations
+//!
+//! Maintenance, backup, restore and verification run as jobs so the command
+//! that starts them returns immediately. Each job has an id, reports progress
+//! as [`JOB_EVENT`] events, can be cancelled, and stays in the history after
+//! it finishes.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::util::now_millis;
+
+/// Tauri event carrying a [`Job`] whenever it changes
+pub const JOB_EVENT: &str = "job-updated";
+
+/// Finished jobs kept in the history
+const MAX_FINISHED_JOBS: usize = 100;
+
+/// Admin operation a job runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    Checkpoint,
+    Gc,
+    Compact,
+    Analyze,
+    Backup,
+    Restore,
+    Verify,
+}
+
+impl JobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::Checkpoint => "checkpoint",
+            JobKind::Gc => "gc",
+            JobKind::Compact => "compact",
+            JobKind::Analyze => "analyze",
+            JobKind::Backup => "backup",
+            JobKind::Restore => "restore",
+            JobKind::Verify => "verify",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, JobStatus::Running)
+    }
+}
+
+/// A submitted admin operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub kind: JobKind,
+    pub connection_id: String,
+    pub status: JobStatus,
+    /// Fraction complete (0.0 to 1.0), if the operation reports it
+    pub progress: Option<f64>,
+    /// Latest progress message
+    pub message: Option<String>,
+    /// Set once cancellation has been requested
+    pub cancel_requested: bool,
+    /// Operation-specific result, once succeeded
+    pub result: Option<Value>,
+    pub error: Option<String>,
+    /// Unix timestamps (ms)
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+}
+
+/// Called with a job whenever it changes
+pub type JobListener = Arc<dyn Fn(&Job) + Send + Sync>;
+
+struct JobEntry {
+    job: Job,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Running jobs and job history
+pub struct JobManager {
+    jobs: Mutex<Vec<JobEntry>>,
+    next_id: AtomicU64,
+    listener: RwLock<Option<JobListener>>,
+}
+
+/// Handle a running job uses to report progress and check for cancellation
+#[derive(Clone)]
+pub struct JobContext {
+    id: String,
+    cancelled: Arc<AtomicBool>,
+    manager: Arc<JobManager>,
+}
+
+impl JobContext {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Whether the job has been asked to stop
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Report progress (`fraction` from 0.0 to 1.0)
+    pub fn progress(&self, fraction: Option<f64>, message: impl Into<String>) {
+        let message = message.into();
+        self.manager.update(&self.id, |job| {
+            job.progress = fraction.map(|f| f.clamp(0.0, 1.0));
+            job.message = Some(message);
+        });
+    }
+}
+
+impl JobManager {
+    pub fn new() -> Self {
+        Self {
+            jobs: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+            listener: RwLock::new(None),
+        }
+    }
+
+    /// Set the callback notified of every job change
+    pub fn set_listener(&self, listener: JobListener) {
+        *self.listener.write() = Some(listener);
+    }
+
+    /// Start `work` as a background job.
+    ///
+    /// Only one job of each kind runs per connection at a time. Blocking
+    /// storage calls inside `work` belong in `spawn_blocking`.
+    pub fn spawn<F, Fut>(
+        self: &Arc<Self>,
+        kind: JobKind,
+        connection_id: &str,
+        work: F,
+    ) -> Result<Job, String>
+    where
+        F: FnOnce(JobContext) -> Fut,
+        Fut: Future<Output = Result<Value, String>> + Send + 'static,
+    {
+        let ctx = self.start(kind, connection_id)?;
+        let job = self.get(&ctx.id).ok_or("Job disappeared while starting")?;
+
+        let manager = self.clone();
+        let id = ctx.id.clone();
+        let future = work(ctx);
+        tokio::spawn(async move {
+            let result = future.await;
+            manager.finish(&id, result);
+        });
+        Ok(job)
+    }
+
+    /// Get a job by id
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.jobs.lock().iter().find(|e| e.job.id == id).map(|e| e.job.clone())
+    }
+
+    /// List jobs, newest first, optionally only those for one connection
+    pub fn list(&self, connection_id: Option<&str>) -> Vec<Job> {
+        self.jobs
+            .lock()
+            .iter()
+            .rev()
+            .filter(|e| connection_id.is_none_or(|id| e.job.connection_id == id))
+            .map(|e| e.job.clone())
+            .collect()
+    }
+
+    /// Ask a running job to stop.
+    ///
+    /// Cancellation is cooperative: the job stops at its next checkpoint, or
+    /// runs to completion if the operation cannot be interrupted.
+    pub fn cancel(&self, id: &str) -> Result<Job, String> {
+        let job = {
+            let mut jobs = self.jobs.lock();
+            let entry = jobs
+                .iter_mut()
+                .find(|e| e.job.id == id)
+                .ok_or_else(|| format!("Unknown job: {}", id))?;
+            if entry.job.status.is_finished() {
+                return Err(format!("Job {} has already finished", id));
+            }
+            entry.cancelled.store(true, Ordering::Relaxed);
+            entry.job.cancel_requested = true;
+            entry.job.clone()
+        };
+        self.notify(&job);
+        Ok(job)
+    }
+
+    fn start(self: &Arc<Self>, kind: JobKind, connection_id: &str) -> Result<JobContext, String> {
+        let (job, cancelled) = {
+            let mut jobs = self.jobs.lock();
+            if let Some(running) = jobs.iter().find(|e| {
+                e.job.kind == kind && e.job.connection_id == connection_id && !e.job.status.is_finished()
+            }) {
+                return Err(format!(
+                    "A {} job ({}) is already running on '{}'",
+                    kind.as_str(), running.job.id, connection_id
+                ));
+            }
+
+            let job = Job {
+                id: format!("job-{}", self.next_id.fetch_add(1, Ordering::Relaxed)),
+                kind,
+                connection_id: connection_id.to_string(),
+                status: JobStatus::Running,
+                progress: None,
+                message: None,
+                cancel_requested: false,
+                result: None,
+                error: None,
+                started_at: now_millis(),
+                finished_at: None,
+            };
+            let cancelled = Arc::new(AtomicBool::new(false));
+            jobs.push(JobEntry { job: job.clone(), cancelled: cancelled.clone() });
+            (job, cancelled)
+        };
+        self.notify(&job);
+
+        Ok(JobContext {
+            id: job.id,
+            cancelled,
+            manager: self.clone(),
+        })
+    }
+
+    fn finish(&self, id: &str, result: Result<Value, String>) {
+        self.update(id, |job| {
+            job.finished_at = Some(now_millis());
+            match result {
+                Ok(value) => {
+                    job.status = JobStatus::Succeeded;
+                    job.progress = Some(1.0);
+                    job.result = Some(value);
+                }
+                Err(e) => {
+                    job.status = if job.cancel_requested { JobStatus::Cancelled } else { JobStatus::Failed };
+                    job.error = Some(e);
+                }
+            }
+        });
+        self.prune();
+    }
+
+    fn update(&self, id: &str, change: impl FnOnce(&mut Job)) {
+        let job = {
+            let mut jobs = self.jobs.lock();
+            let Some(entry) = jobs.iter_mut().find(|e| e.job.id == id) else {
+                return;
+            };
+            change(&mut entry.job);
+            entry.job.clone()
+        };
+        self.notify(&job);
+    }
+
+    /// Drop the oldest finished jobs beyond [`MAX_FINISHED_JOBS`]
+    fn prune(&self) {
+        let mut jobs = self.jobs.lock();
+        let finished = jobs.iter().filter(|e| e.job.status.is_finished()).count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+        jobs.retain(|e| {
+            if excess > 0 && e.job.status.is_finished() {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+    }
+
+    fn notify(&self, job: &Job) {
+        let listener = self.listener.read().clone();
+        if let Some(listener) = listener {
+            listener(job);
+        }
+    }
+}
+
+impl Default for JobManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_job_lifecycle() {
+        let manager = Arc::new(JobManager::new());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        manager.set_listener(Arc::new(move |job: &Job| seen.lock().push(job.status)));
+
+        let ctx = manager.start(JobKind::Compact, "db").unwrap();
+        assert!(manager.start(JobKind::Compact, "db").is_err());
+        assert!(manager.start(JobKind::Compact, "other").is_ok());
+        assert!(manager.start(JobKind::Gc, "db").is_ok());
+
+        ctx.progress(Some(0.5), "halfway");
+        assert_eq!(manager.get(ctx.id()).unwrap().progress, Some(0.5));
+
+        manager.finish(ctx.id(), Ok(json!({"files": 3})));
+        let job = manager.get(ctx.id()).unwrap();
+        assert_eq!(job.status, JobStatus::Succeeded);
+        assert_eq!(job.result, Some(json!({"files": 3})));
+        assert!(manager.cancel(ctx.id()).is_err());
+
+        // A finished job no longer blocks a new one of the same kind
+        assert!(manager.start(JobKind::Compact, "db").is_ok());
+        assert_eq!(manager.list(Some("db")).len(), 3);
+        assert_eq!(
+            events.lock()[..3],
+            [JobStatus::Running, JobStatus::Running, JobStatus::Running]
+        );
+    }
+
+    #[test]
+    fn test_cancel_marks_job_cancelled() {
+        let manager = Arc::new(JobManager::new());
+        let ctx = manager.start(JobKind::Analyze, "db").unwrap();
+
+        let job = manager.cancel(ctx.id()).unwrap();
+        assert!(job.cancel_requested);
+        assert!(ctx.is_cancelled());
+
+        manager.finish(ctx.id(), Err("cancelled".to_string()));
+        assert_eq!(manager.get(ctx.id()).unwrap().status, JobStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let manager = Arc::new(JobManager::new());
+        let running = manager.start(JobKind::Compact, "db").unwrap();
+        for _ in 0..MAX_FINISHED_JOBS + 5 {
+            let ctx = manager.start(JobKind::Gc, "db").unwrap();
+            manager.finish(ctx.id(), Ok(Value::Null));
+        }
+
+        let jobs = manager.list(None);
+        assert_eq!(jobs.len(), MAX_FINISHED_JOBS + 1);
+        assert!(jobs.iter().any(|j| j.id == running.id()));
+    }
+
+    #[tokio::test]
+    async fn test_spawn_runs_in_background() {
+        let manager = Arc::new(JobManager::new());
+        let job = manager
+            .spawn(JobKind::Checkpoint, "db", |ctx| async move {
+                ctx.progress(None, "flushing");
+                Ok(json!({"lsn": 42}))
+            })
+            .unwrap();
+        assert_eq!(job.status, JobStatus::Running);
+
+        for _ in 0..100 {
+            if manager.get(&job.id).unwrap().status.is_finished() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        let job = manager.get(&job.id).unwrap();
+        assert_eq!(job.status, JobStatus::Succeeded);
+        assert_eq!(job.result, Some(json!({"lsn": 42})));
+    }
+}
@@ -3,13 +3,31 @@
 //! A Tauri 2.0 desktop application for managing SochDB databases.
 //! Similar to DBeaver, TablePlus, MongoDB Compass - but for SochDB.
 
+mod analyze;
+mod backup;
 mod commands;
 mod connection;
+mod cursors;
+mod exporter;
+mod history;
+mod jobs;
+mod maintenance;
+mod metrics;
 mod parser;
+mod planner;
 pub mod policy;
+mod results;
+mod running_queries;
+mod saved_queries;
+mod scheduler;
+mod script;
 mod state;
+mod stats;
+mod util;
+mod verify;
 
 use std::sync::Arc;
+use tauri::{Emitter, Manager};
 
 pub use policy::{CommandCategory, CommandPolicy, ConnectionMode, PolicyCheck, UserRole};
 pub use state::AppState;
@@ -24,14 +42,113 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_store::Builder::default().build())
         .manage(app_state)
+        .setup(|app| {
+            // Restore saved connections for the sidebar. A store that cannot
+            // be read must not keep Studio from starting.
+            let saved = commands::connections::load_saved_connections(app.handle()).unwrap_or_else(|e| {
+                eprintln!("Failed to load saved connections: {}", e);
+                Vec::new()
+            });
+            let state = app.state::<Arc<AppState>>();
+            state.connections.blocking_write().set_saved_connections(saved);
+            *state.scheduler.blocking_write() = commands::schedules::load_scheduler(app.handle()).unwrap_or_else(|e| {
+                eprintln!("Failed to load maintenance schedules: {}", e);
+                scheduler::Scheduler::default()
+            });
+            *state.query_history.blocking_write() = commands::history::load_query_history(app.handle()).unwrap_or_else(|e| {
+                eprintln!("Failed to load query history: {}", e);
+                history::QueryHistory::default()
+            });
+            *state.query_library.blocking_write() = commands::saved_queries::load_query_library(app.handle()).unwrap_or_else(|e| {
+                eprintln!("Failed to load saved queries: {}", e);
+                saved_queries::QueryLibrary::default()
+            });
+
+            // Probe the active connection and report health changes to the UI
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(state.inner().clone().run_health_monitor(move |event| {
+                if let Err(e) = handle.emit(connection::health::HEALTH_EVENT, event) {
+                    eprintln!("Failed to emit connection health event: {}", e);
+                }
+            }));
+
+            // Sample every open session for the metrics charts
+            tauri::async_runtime::spawn(state.inner().clone().run_metrics_collector());
+
+            // Expire idle result cursors
+            tauri::async_runtime::spawn(state.inner().clone().run_cursor_reaper());
+
+            // Bring the Prometheus endpoint back up if it was left enabled.
+            // Unreadable settings leave it off rather than stop Studio.
+            let config = commands::exporter::load_exporter_config(app.handle()).unwrap_or_else(|e| {
+                eprintln!("Failed to load metrics endpoint settings: {}", e);
+                exporter::ExporterConfig::default()
+            });
+            if config.enabled {
+                let state = state.inner().clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = state.configure_exporter(config).await {
+                        eprintln!("Failed to start metrics endpoint: {}", e);
+                    }
+                });
+            }
+
+            // Forward job progress and completion to the UI
+            let handle = app.handle().clone();
+            state.jobs.set_listener(Arc::new(move |job| {
+                if let Err(e) = handle.emit(jobs::JOB_EVENT, job) {
+                    eprintln!("Failed to emit job event: {}", e);
+                }
+            }));
+
+            // Run maintenance schedules while Studio is open
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(state.inner().clone().run_scheduler(move |scheduler| {
+                if let Err(e) = commands::schedules::persist(&handle, scheduler) {
+                    eprintln!("Failed to save maintenance history: {}", e);
+                }
+            }));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             // Database commands
             commands::admin::connect,
+            commands::admin::connect_read_only,
             commands::admin::disconnect,
+            commands::admin::list_sessions,
+            commands::admin::switch_session,
+            commands::admin::close_session,
+            // Saved connection commands
+            commands::connections::list_saved_connections,
+            commands::connections::add_saved_connection,
+            commands::connections::update_saved_connection,
+            commands::connections::delete_saved_connection,
+            commands::connections::duplicate_saved_connection,
+            commands::connections::reorder_saved_connections,
+            commands::connections::group_saved_connections,
+            commands::connections::set_statement_timeout,
             commands::admin::get_stats,
+            commands::admin::get_collection_stats,
+            commands::admin::get_storage_breakdown,
+            commands::admin::get_metrics_history,
             commands::admin::checkpoint,
             commands::admin::gc,
             commands::admin::compact,
+            commands::admin::analyze,
+            commands::admin::backup_database,
+            commands::admin::restore_database,
+            commands::admin::verify_database,
+            commands::admin::list_jobs,
+            commands::admin::get_job,
+            commands::admin::cancel_job,
+            // Maintenance schedule commands
+            commands::schedules::list_schedules,
+            commands::schedules::save_schedule,
+            commands::schedules::delete_schedule,
+            commands::schedules::get_maintenance_history,
+            // Metrics endpoint commands
+            commands::exporter::get_exporter_status,
+            commands::exporter::set_exporter_config,
             // Policy commands
             commands::admin::get_policy,
             commands::admin::set_policy,
@@ -39,7 +156,28 @@ pub fn run() {
             commands::admin::get_allowed_commands,
             // Query commands
             commands::query::execute_query,
+            commands::query::execute_script,
             commands::query::explain_query,
+            commands::query::list_running_queries,
+            commands::query::cancel_query,
+            // Result cursor commands
+            commands::cursors::open_cursor,
+            commands::cursors::fetch_cursor,
+            commands::cursors::stream_cursor,
+            commands::cursors::close_cursor,
+            // Query history commands
+            commands::history::search_query_history,
+            commands::history::pin_query_history,
+            commands::history::delete_query_history,
+            commands::history::clear_query_history,
+            commands::history::rerun_query_history,
+            // Saved query commands
+            commands::saved_queries::list_saved_queries,
+            commands::saved_queries::save_query,
+            commands::saved_queries::delete_saved_query,
+            commands::saved_queries::run_saved_query,
+            commands::saved_queries::export_saved_queries,
+            commands::saved_queries::import_saved_queries,
             // Schema commands
             commands::schema::get_schema,
             commands::schema::get_table_info,
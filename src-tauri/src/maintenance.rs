@@ -0,0 +1,253 @@
+//! Maintenance operations: checkpoint, GC, compaction and analysis
+//!
+//! Each operation is submitted as a background job (see [`crate::jobs`]);
+//! the job's result is the operation's outcome serialized as JSON.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sochdb_storage::compaction::CompactionOptions;
+
+use crate::analyze::{self, CollectionAnalysis, Sampler};
+use crate::connection::pool::Session;
+use crate::jobs::{Job, JobContext, JobKind, JobManager};
+use crate::stats::collection_name;
+
+/// Keys read per batch while sampling collections
+const SCAN_BATCH_SIZE: usize = 1000;
+
+/// A maintenance operation and its parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum MaintenanceTask {
+    Checkpoint,
+    Gc,
+    /// Compact the whole database, or only the keys in
+    /// `[start_key, end_key)` and/or the SST files on `level`
+    Compact {
+        #[serde(default)]
+        start_key: Option<String>,
+        #[serde(default)]
+        end_key: Option<String>,
+        #[serde(default)]
+        level: Option<u32>,
+    },
+    /// Analyze every collection, or only `collections`
+    Analyze {
+        #[serde(default)]
+        collections: Option<Vec<String>>,
+        #[serde(default)]
+        sample_size: Option<usize>,
+    },
+}
+
+impl MaintenanceTask {
+    pub fn kind(&self) -> JobKind {
+        match self {
+            MaintenanceTask::Checkpoint => JobKind::Checkpoint,
+            MaintenanceTask::Gc => JobKind::Gc,
+            MaintenanceTask::Compact { .. } => JobKind::Compact,
+            MaintenanceTask::Analyze { .. } => JobKind::Analyze,
+        }
+    }
+
+    /// Whether the task needs direct access to an embedded database
+    fn needs_embedded(&self) -> bool {
+        !matches!(self, MaintenanceTask::Analyze { .. })
+    }
+}
+
+/// Outcome of a WAL checkpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointResult {
+    /// LSN up to which the WAL is now covered by flushed data
+    pub lsn: u64,
+    pub wal_bytes_reclaimed: u64,
+    pub duration_ms: f64,
+}
+
+/// Outcome of garbage collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GcResult {
+    pub versions_reclaimed: usize,
+    pub duration_ms: f64,
+}
+
+/// Outcome of a manual compaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactionResult {
+    pub sst_files_before: usize,
+    pub sst_files_after: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    /// Bytes written to the new SST files
+    pub bytes_rewritten: u64,
+    /// Disk space freed (`bytes_before - bytes_after`)
+    pub space_reclaimed_bytes: u64,
+    pub duration_ms: f64,
+}
+
+/// Submit `task` against `session` as a background job
+pub fn submit(jobs: &Arc<JobManager>, session: Arc<Session>, task: MaintenanceTask) -> Result<Job, String> {
+    let kind = task.kind();
+    session.ensure_writable(kind.as_str())?;
+    if task.needs_embedded() {
+        session.embedded(kind.as_str())?;
+    }
+
+    let script = session.statement_guard()?;
+    let connection_id = session.id.clone();
+    jobs.spawn(kind, &connection_id, move |ctx| async move {
+        let _script = script;
+        run(session, task, ctx).await
+    })
+}
+
+async fn run(session: Arc<Session>, task: MaintenanceTask, ctx: JobContext) -> Result<Value, String> {
+    match task {
+        MaintenanceTask::Analyze { collections, sample_size } => {
+            let results = analyze(&session, collections, sample_size, &ctx).await?;
+            to_value(results)
+        }
+        // Storage-level operations block; keep them off the async workers
+        task => tokio::task::spawn_blocking(move || match task {
+            MaintenanceTask::Checkpoint => to_value(checkpoint(&session)?),
+            MaintenanceTask::Gc => to_value(gc(&session)?),
+            MaintenanceTask::Compact { start_key, end_key, level } => {
+                let options = CompactionOptions {
+                    start_key: start_key.map(String::into_bytes),
+                    end_key: end_key.map(String::into_bytes),
+                    level,
+                };
+                to_value(compact(&session, &options, &ctx)?)
+            }
+            MaintenanceTask::Analyze { .. } => unreachable!("analyze runs on the async runtime"),
+        })
+        .await
+        .map_err(|e| format!("Maintenance task failed: {}", e))?,
+    }
+}
+
+/// Flush the memtable and truncate the WAL segments it makes redundant
+fn checkpoint(session: &Session) -> Result<CheckpointResult, String> {
+    let kernel = session.embedded("checkpoint")?.connection().kernel();
+
+    let start = Instant::now();
+    let stats = kernel.checkpoint()
+        .map_err(|e| format!("Checkpoint failed: {}", e))?;
+
+    Ok(CheckpointResult {
+        lsn: stats.lsn,
+        wal_bytes_reclaimed: stats.wal_bytes_reclaimed,
+        duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+    })
+}
+
+/// Reclaim versions no snapshot can see any more
+fn gc(session: &Session) -> Result<GcResult, String> {
+    let start = Instant::now();
+    let versions_reclaimed = session.embedded("gc")?.connection().gc();
+    Ok(GcResult {
+        versions_reclaimed,
+        duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+    })
+}
+
+/// Rewrite SST files, dropping deleted and superseded versions
+fn compact(session: &Session, options: &CompactionOptions, ctx: &JobContext) -> Result<CompactionResult, String> {
+    let kernel = session.embedded("compact")?.connection().kernel();
+    let before = kernel.sst_summary();
+    let start = Instant::now();
+
+    // Returning false from the callback stops after the current file
+    let stats = kernel
+        .compact(options, |progress| {
+            ctx.progress(
+                Some(progress.files_compacted as f64 / progress.files_total.max(1) as f64),
+                format!(
+                    "Compacted {} of {} SST files ({} bytes rewritten)",
+                    progress.files_compacted, progress.files_total, progress.bytes_rewritten
+                ),
+            );
+            !ctx.is_cancelled()
+        })
+        .map_err(|e| format!("Compaction failed: {}", e))?;
+
+    if ctx.is_cancelled() {
+        return Err("Compaction cancelled".to_string());
+    }
+
+    let after = kernel.sst_summary();
+    Ok(CompactionResult {
+        sst_files_before: before.file_count,
+        sst_files_after: after.file_count,
+        bytes_before: before.total_bytes,
+        bytes_after: after.total_bytes,
+        bytes_rewritten: stats.bytes_rewritten,
+        space_reclaimed_bytes: before.total_bytes.saturating_sub(after.total_bytes),
+        duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+    })
+}
+
+/// Sample collections and store their statistics under the stats prefix
+async fn analyze(
+    session: &Session,
+    collections: Option<Vec<String>>,
+    sample_size: Option<usize>,
+    ctx: &JobContext,
+) -> Result<Vec<CollectionAnalysis>, String> {
+    ctx.progress(None, "Scanning database");
+    let sample_size = sample_size.unwrap_or(analyze::DEFAULT_SAMPLE_SIZE);
+
+    // Sample a root scan by collection as it streams in; Studio's own keys
+    // belong to none, and unrequested collections are skipped
+    let mut stream = session.backend.scan_stream(b"", SCAN_BATCH_SIZE).await?;
+    let mut by_collection: BTreeMap<String, Sampler> = BTreeMap::new();
+    let mut scanned = 0u64;
+    while let Some(batch) = stream.recv().await {
+        if ctx.is_cancelled() {
+            return Err(format!("Analyze cancelled after scanning {} keys", scanned));
+        }
+        let batch = batch?;
+        scanned += batch.len() as u64;
+        for (key, value) in batch {
+            let Some(name) = collection_name(&key) else { continue };
+            if collections.as_ref().is_some_and(|requested| !requested.contains(&name)) {
+                continue;
+            }
+            by_collection
+                .entry(name)
+                .or_insert_with(|| Sampler::new(sample_size))
+                .add((key, value));
+        }
+        ctx.progress(None, format!("Scanned {} keys", scanned));
+    }
+
+    if let Some(requested) = &collections {
+        if let Some(missing) = requested.iter().find(|c| !by_collection.contains_key(*c)) {
+            return Err(format!("Unknown collection: {}", missing));
+        }
+    }
+
+    let total = by_collection.len();
+    let mut results = Vec::with_capacity(total);
+    for (done, (name, sampler)) in by_collection.into_iter().enumerate() {
+        if ctx.is_cancelled() {
+            return Err(format!("Analyze cancelled after {} of {} collections", done, total));
+        }
+        ctx.progress(Some(done as f64 / total as f64), format!("Analyzing {}", name));
+
+        let analysis = sampler.analyze(&name);
+        analyze::store_analysis(session, &analysis).await
+            .map_err(|e| format!("Failed to store statistics for {}: {}", name, e))?;
+        results.push(analysis);
+    }
+
+    Ok(results)
+}
+
+fn to_value<T: Serialize>(result: T) -> Result<Value, String> {
+    serde_json::to_value(result).map_err(|e| e.to_string())
+}
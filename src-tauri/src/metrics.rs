@@ -0,0 +1,235 @@
+//! Metrics history for the stats dashboard
+//!
+//! A background collector samples every open session's counters each
+//! [`SAMPLE_INTERVAL`] into a per-connection ring buffer holding
+//! [`RETENTION`] worth of samples. Charts ask for a time range at some
+//! resolution and get one point per bucket, with counters turned into rates.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::connection::backend::BackendStats;
+use crate::util::now_millis;
+
+/// Default chart range
+pub const DEFAULT_RANGE: Duration = Duration::from_secs(15 * 60);
+
+/// Time between samples
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How far back samples are kept
+pub const RETENTION: Duration = Duration::from_secs(6 * 60 * 60);
+
+const MAX_SAMPLES: usize = (RETENTION.as_secs() / SAMPLE_INTERVAL.as_secs()) as usize;
+
+/// Raw counters and gauges at one point in time
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetricsSample {
+    /// Unix timestamp (ms)
+    pub timestamp: u64,
+    pub bytes_written: Option<u64>,
+    pub bytes_read: Option<u64>,
+    pub transactions_started: Option<u64>,
+    pub transactions_committed: Option<u64>,
+    pub transactions_aborted: Option<u64>,
+    pub active_transactions: Option<u64>,
+    pub memtable_size_bytes: Option<u64>,
+    pub wal_size_bytes: Option<u64>,
+}
+
+impl MetricsSample {
+    /// Sample `stats`, stamped with the current time
+    pub fn now(stats: &BackendStats) -> Self {
+        Self {
+            timestamp: now_millis(),
+            bytes_written: stats.bytes_written,
+            bytes_read: stats.bytes_read,
+            transactions_started: stats.transactions_started,
+            transactions_committed: stats.transactions_committed,
+            transactions_aborted: stats.transactions_aborted,
+            active_transactions: stats.active_transactions(),
+            memtable_size_bytes: stats.memtable_size_bytes,
+            wal_size_bytes: stats.wal_size_bytes,
+        }
+    }
+}
+
+/// One chart point: rates over a bucket and gauges at its end.
+///
+/// Rates are `null` when the backend does not report the counter, or when
+/// the counters restarted (e.g. after a reconnect) within the bucket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricsPoint {
+    /// Unix timestamp (ms) of the bucket's last sample
+    pub timestamp: u64,
+    pub bytes_written_per_sec: Option<f64>,
+    pub bytes_read_per_sec: Option<f64>,
+    pub transactions_started_per_sec: Option<f64>,
+    /// Transactions committed or aborted per second
+    pub transactions_per_sec: Option<f64>,
+    pub commits_per_sec: Option<f64>,
+    pub aborts_per_sec: Option<f64>,
+    /// Fraction of the bucket's finished transactions that aborted
+    pub abort_ratio: Option<f64>,
+    pub active_transactions: Option<u64>,
+    pub memtable_size_bytes: Option<u64>,
+    pub wal_size_bytes: Option<u64>,
+}
+
+impl MetricsPoint {
+    /// Rates between `from` and `to`, gauges from `to`
+    fn between(from: &MetricsSample, to: &MetricsSample) -> Self {
+        let seconds = to.timestamp.saturating_sub(from.timestamp) as f64 / 1000.0;
+        let delta = |counter: fn(&MetricsSample) -> Option<u64>| -> Option<u64> {
+            counter(to)?.checked_sub(counter(from)?)
+        };
+        let rate = |delta: Option<u64>| delta.filter(|_| seconds > 0.0).map(|d| d as f64 / seconds);
+
+        let commits = delta(|s| s.transactions_committed);
+        let aborts = delta(|s| s.transactions_aborted);
+        let finished = commits.zip(aborts).map(|(c, a)| c + a);
+
+        Self {
+            timestamp: to.timestamp,
+            bytes_written_per_sec: rate(delta(|s| s.bytes_written)),
+            bytes_read_per_sec: rate(delta(|s| s.bytes_read)),
+            transactions_started_per_sec: rate(delta(|s| s.transactions_started)),
+            transactions_per_sec: rate(finished),
+            commits_per_sec: rate(commits),
+            aborts_per_sec: rate(aborts),
+            abort_ratio: aborts
+                .zip(finished)
+                .filter(|_| seconds > 0.0)
+                .map(|(a, f)| if f == 0 { 0.0 } else { a as f64 / f as f64 }),
+            active_transactions: to.active_transactions,
+            memtable_size_bytes: to.memtable_size_bytes,
+            wal_size_bytes: to.wal_size_bytes,
+        }
+    }
+}
+
+/// Recent samples for every open connection
+#[derive(Debug, Default)]
+pub struct MetricsHistory {
+    samples: HashMap<String, VecDeque<MetricsSample>>,
+}
+
+impl MetricsHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a sample, dropping the oldest once [`RETENTION`] is full
+    pub fn record(&mut self, connection_id: &str, sample: MetricsSample) {
+        let samples = self.samples.entry(connection_id.to_string()).or_default();
+        samples.push_back(sample);
+        while samples.len() > MAX_SAMPLES {
+            samples.pop_front();
+        }
+    }
+
+    /// Drop the history of connections that are no longer open
+    pub fn retain(&mut self, open: &[&str]) {
+        self.samples.retain(|id, _| open.contains(&id.as_str()));
+    }
+
+    /// Chart points for the `range` before `now`, one per `resolution`.
+    ///
+    /// Each point's rates run from the previous point's last sample to its
+    /// own, so consecutive points cover the range without gaps.
+    pub fn points(&self, connection_id: &str, now: u64, range: Duration, resolution: Duration) -> Vec<MetricsPoint> {
+        let Some(samples) = self.samples.get(connection_id) else {
+            return Vec::new();
+        };
+        let resolution = resolution.max(SAMPLE_INTERVAL).as_millis() as u64;
+        let since = now.saturating_sub(range.as_millis() as u64);
+
+        // The last sample before the range is the baseline for the first rate
+        let mut previous = samples.iter().rev().find(|s| s.timestamp < since);
+        let mut points = Vec::new();
+        let mut in_range = samples.iter().filter(|s| s.timestamp >= since && s.timestamp <= now).peekable();
+        while let Some(sample) = in_range.next() {
+            let bucket = sample.timestamp / resolution;
+            let mut last = sample;
+            while let Some(next) = in_range.next_if(|s| s.timestamp / resolution == bucket) {
+                last = next;
+            }
+            points.push(MetricsPoint::between(previous.unwrap_or(sample), last));
+            previous = Some(last);
+        }
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(seconds: u64, written: u64, committed: u64, aborted: u64) -> MetricsSample {
+        MetricsSample {
+            timestamp: seconds * 1000,
+            bytes_written: Some(written),
+            bytes_read: None,
+            transactions_started: Some(committed + aborted),
+            transactions_committed: Some(committed),
+            transactions_aborted: Some(aborted),
+            active_transactions: Some(0),
+            memtable_size_bytes: Some(seconds),
+            wal_size_bytes: None,
+        }
+    }
+
+    #[test]
+    fn test_rates() {
+        let mut history = MetricsHistory::new();
+        history.record("db", sample(0, 0, 0, 0));
+        history.record("db", sample(5, 500, 8, 2));
+        history.record("db", sample(10, 1500, 18, 2));
+
+        let points = history.points("db", 10_000, Duration::from_secs(60), SAMPLE_INTERVAL);
+        assert_eq!(points.len(), 3);
+        // The first sample has nothing before it
+        assert_eq!(points[0].bytes_written_per_sec, None);
+        assert_eq!(points[1].bytes_written_per_sec, Some(100.0));
+        assert_eq!(points[1].transactions_per_sec, Some(2.0));
+        assert_eq!(points[1].abort_ratio, Some(0.2));
+        assert_eq!(points[2].abort_ratio, Some(0.0));
+        assert_eq!(points[2].memtable_size_bytes, Some(10));
+        assert_eq!(points[2].bytes_read_per_sec, None);
+
+        assert!(history.points("other", 10_000, Duration::from_secs(60), SAMPLE_INTERVAL).is_empty());
+    }
+
+    #[test]
+    fn test_range_and_resolution() {
+        let mut history = MetricsHistory::new();
+        for i in 0..24 {
+            history.record("db", sample(i * 5, i * 50, i, 0));
+        }
+
+        // The last minute in 30-second buckets; the sample before the range
+        // is the baseline of the first point
+        let points = history.points("db", 115_000, Duration::from_secs(60), Duration::from_secs(30));
+        assert_eq!(points.iter().map(|p| p.timestamp).collect::<Vec<_>>(), vec![55_000, 85_000, 115_000]);
+        assert_eq!(points[0].bytes_written_per_sec, Some(10.0));
+        assert_eq!(points[2].commits_per_sec, Some(0.2));
+    }
+
+    #[test]
+    fn test_counter_restart_and_retention() {
+        let mut history = MetricsHistory::new();
+        history.record("db", sample(0, 1000, 10, 0));
+        history.record("db", sample(5, 10, 1, 0));
+        let points = history.points("db", 5_000, Duration::from_secs(60), SAMPLE_INTERVAL);
+        assert_eq!(points[1].bytes_written_per_sec, None);
+
+        for i in 0..MAX_SAMPLES as u64 + 10 {
+            history.record("db", sample(i, 0, 0, 0));
+        }
+        assert_eq!(history.samples["db"].len(), MAX_SAMPLES);
+
+        history.retain(&["other"]);
+        assert!(history.samples.is_empty());
+    }
+}
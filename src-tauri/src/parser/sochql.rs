@@ -86,10 +86,10 @@ fn parse_vector_search(input: &str) -> Result<SochQlStatement, String> {
             .map(|s| s.trim().parse::<f32>())
             .collect();
         VectorQuery::Vector(values.map_err(|e| e.to_string())?)
-    } else if after_near.starts_with('\'') {
+    } else if let Some(quoted) = after_near.strip_prefix('\'') {
         // Text format
-        let end = after_near[1..].find('\'').unwrap_or(after_near.len() - 1);
-        VectorQuery::Text(after_near[1..=end].to_string())
+        let end = quoted.find('\'').unwrap_or(quoted.len());
+        VectorQuery::Text(quoted[..end].to_string())
     } else {
         return Err("Invalid NEAR query format".to_string());
     };
@@ -126,7 +126,7 @@ fn parse_put(query: &str) -> Result<SochQlStatement, String> {
     // Find the '=' separator
     let eq_idx = after_put.find('=').ok_or("Missing '=' in PUT statement")?;
     
-    let path = extract_quoted_string(&after_put[..eq_idx].trim())?;
+    let path = extract_quoted_string(after_put[..eq_idx].trim())?;
     let value = after_put[eq_idx + 1..].trim().to_string();
     
     Ok(SochQlStatement::Put { path, value })
@@ -3,6 +3,7 @@
 //! Provides a complete SQL parser for DML/DDL statements with proper AST generation.
 //! This is the unified parser used across Studio, MCP, and query engine.
 
+use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 
 /// SQL statement type
@@ -46,6 +47,8 @@ pub enum SqlStatement {
         columns: Vec<String>,
         unique: bool,
     },
+    /// `BEGIN`, `COMMIT`, `ROLLBACK`, `SAVEPOINT` or `RELEASE SAVEPOINT`
+    Transaction(TransactionControl),
     /// Unsupported statement with explanation
     Unsupported {
         statement: String,
@@ -53,6 +56,17 @@ pub enum SqlStatement {
     },
 }
 
+/// Transaction control statement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionControl {
+    Begin,
+    Commit,
+    /// Roll back the transaction, or only to `savepoint` when given
+    Rollback { savepoint: Option<String> },
+    Savepoint { name: String },
+    Release { name: String },
+}
+
 /// Column definition for CREATE TABLE
 #[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -159,6 +173,19 @@ pub struct SqlParser<'a> {
     pos: usize,
     tokens: Vec<Token>,
     token_pos: usize,
+    placeholders: Vec<Placeholder>,
+    /// Char offsets of the `Token::Semicolon`s
+    semicolons: Vec<usize>,
+}
+
+/// A `$name`, `$1` or `?` placeholder in a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placeholder {
+    /// Parameter name: `name` for `$name`, the position for `$1` and `?`
+    pub name: String,
+    /// Char offsets of the placeholder in the query
+    start: usize,
+    end: usize,
 }
 
 #[allow(dead_code)]
@@ -185,6 +212,8 @@ impl<'a> SqlParser<'a> {
             pos: 0,
             tokens: Vec::new(),
             token_pos: 0,
+            placeholders: Vec::new(),
+            semicolons: Vec::new(),
         };
         parser.tokenize();
         parser
@@ -204,6 +233,14 @@ impl<'a> SqlParser<'a> {
                 continue;
             }
 
+            // Line comment
+            if c == '-' && self.pos + 1 < len && chars[self.pos + 1] == '-' {
+                while self.pos < len && chars[self.pos] != '\n' {
+                    self.pos += 1;
+                }
+                continue;
+            }
+
             // String literal
             if c == '\'' || c == '"' {
                 let quote = c;
@@ -267,6 +304,7 @@ impl<'a> SqlParser<'a> {
                     self.pos += 1;
                 }
                 ';' => {
+                    self.semicolons.push(self.pos);
                     self.tokens.push(Token::Semicolon);
                     self.pos += 1;
                 }
@@ -303,22 +341,29 @@ impl<'a> SqlParser<'a> {
                         self.pos += 1;
                     }
                 }
-                '!' => {
-                    if self.pos + 1 < len && chars[self.pos + 1] == '=' {
-                        self.tokens.push(Token::Operator("!=".to_string()));
-                        self.pos += 2;
-                    } else {
-                        self.pos += 1;
-                    }
+                '!' if self.pos + 1 < len && chars[self.pos + 1] == '=' => {
+                    self.tokens.push(Token::Operator("!=".to_string()));
+                    self.pos += 2;
                 }
                 '$' | '?' => {
-                    // Placeholder
+                    // Placeholder: `?`, `?1`, `$1` or `$name`
                     let start = self.pos;
                     self.pos += 1;
-                    while self.pos < len && chars[self.pos].is_ascii_digit() {
+                    while self.pos < len
+                        && (chars[self.pos].is_ascii_digit()
+                            || (c == '$' && (chars[self.pos].is_alphanumeric() || chars[self.pos] == '_')))
+                    {
                         self.pos += 1;
                     }
                     let s: String = chars[start..self.pos].iter().collect();
+                    let name = if s.len() > 1 {
+                        s[1..].to_string()
+                    } else {
+                        // Bare `?`s are numbered left to right
+                        let bare = self.placeholders.iter().filter(|p| p.end - p.start == 1).count();
+                        (bare + 1).to_string()
+                    };
+                    self.placeholders.push(Placeholder { name, start, end: self.pos });
                     self.tokens.push(Token::Identifier(s));
                 }
                 _ => {
@@ -349,6 +394,16 @@ impl<'a> SqlParser<'a> {
         }
     }
 
+    /// Consume `kw` if it is the current token
+    fn accept_keyword(&mut self, kw: &str) -> bool {
+        if matches!(self.current(), Token::Keyword(k) if k == kw) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
     fn expect_identifier(&mut self) -> Result<String, String> {
         match self.current().clone() {
             Token::Identifier(s) => {
@@ -410,7 +465,8 @@ fn is_keyword(s: &str) -> bool {
         "IF" | "EXISTS" | "PRIMARY" | "KEY" | "UNIQUE" |
         "DEFAULT" | "ON" | "TRUE" | "FALSE" | "INTEGER" | "INT" |
         "BIGINT" | "FLOAT" | "DOUBLE" | "TEXT" | "VARCHAR" | "BOOLEAN" |
-        "TIMESTAMP" | "BLOB" | "VECTOR" | "JSON"
+        "TIMESTAMP" | "BLOB" | "VECTOR" | "JSON" | "BEGIN" | "START" |
+        "TRANSACTION" | "COMMIT" | "ROLLBACK" | "SAVEPOINT" | "RELEASE" | "TO"
     )
 }
 
@@ -427,6 +483,9 @@ pub fn parse_sql(query: &str) -> Result<SqlStatement, String> {
             "DELETE" => parse_delete_stmt(&mut parser),
             "CREATE" => parse_create_stmt(&mut parser),
             "DROP" => parse_drop_stmt(&mut parser),
+            "BEGIN" | "START" | "COMMIT" | "ROLLBACK" | "SAVEPOINT" | "RELEASE" => {
+                parse_transaction_stmt(&mut parser)
+            }
             _ => Ok(SqlStatement::Unsupported {
                 statement: query.to_string(),
                 reason: format!("Unsupported statement type: {}", k),
@@ -436,6 +495,71 @@ pub fn parse_sql(query: &str) -> Result<SqlStatement, String> {
     }
 }
 
+/// Parameter names of a query's placeholders, in order of first appearance
+pub fn parameter_names(query: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for placeholder in SqlParser::new(query).placeholders {
+        if !names.contains(&placeholder.name) {
+            names.push(placeholder.name);
+        }
+    }
+    names
+}
+
+/// Split a script into its statements on `;`, dropping empty ones.
+///
+/// Semicolons inside string literals and `--` comments do not end a
+/// statement.
+pub fn split_statements(script: &str) -> Vec<String> {
+    let chars: Vec<char> = script.chars().collect();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    for end in SqlParser::new(script).semicolons.into_iter().chain([chars.len()]) {
+        let statement: String = chars[start..end].iter().collect();
+        let statement = statement.trim();
+        if !statement.is_empty() {
+            statements.push(statement.to_string());
+        }
+        start = end + 1;
+    }
+    statements
+}
+
+/// Substitute parameter values for a query's placeholders.
+///
+/// `params` is keyed by [`Placeholder::name`]. Placeholders inside string
+/// literals are left alone.
+pub fn bind_parameters(query: &str, params: &HashMap<String, serde_json::Value>) -> Result<String, String> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut bound = String::with_capacity(query.len());
+    let mut pos = 0;
+    for placeholder in SqlParser::new(query).placeholders {
+        let value = params
+            .get(&placeholder.name)
+            .ok_or_else(|| format!("Missing value for parameter '{}'", placeholder.name))?;
+        bound.extend(&chars[pos..placeholder.start]);
+        bound.push_str(&sql_literal(value).map_err(|e| format!("Parameter '{}': {}", placeholder.name, e))?);
+        pos = placeholder.end;
+    }
+    bound.extend(&chars[pos..]);
+    Ok(bound)
+}
+
+/// Render a JSON value as a literal the tokenizer reads back unchanged
+fn sql_literal(value: &serde_json::Value) -> Result<String, String> {
+    use serde_json::Value;
+
+    match value {
+        Value::Null => Ok("NULL".to_string()),
+        Value::Bool(b) => Ok(if *b { "TRUE" } else { "FALSE" }.to_string()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::String(s) => Ok(format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))),
+        // Vectors
+        Value::Array(items) if items.iter().all(|v| v.is_number()) => Ok(value.to_string()),
+        Value::Array(_) | Value::Object(_) => Err("only scalars and arrays of numbers can be bound".to_string()),
+    }
+}
+
 #[allow(dead_code)]
 fn parse_select_stmt(parser: &mut SqlParser) -> Result<SqlStatement, String> {
     parser.expect_keyword("SELECT")?;
@@ -998,6 +1122,36 @@ fn parse_drop_stmt(parser: &mut SqlParser) -> Result<SqlStatement, String> {
     Ok(SqlStatement::DropTable { name, if_exists })
 }
 
+fn parse_transaction_stmt(parser: &mut SqlParser) -> Result<SqlStatement, String> {
+    let control = if parser.accept_keyword("BEGIN") {
+        parser.accept_keyword("TRANSACTION");
+        TransactionControl::Begin
+    } else if parser.accept_keyword("START") {
+        parser.expect_keyword("TRANSACTION")?;
+        TransactionControl::Begin
+    } else if parser.accept_keyword("COMMIT") {
+        parser.accept_keyword("TRANSACTION");
+        TransactionControl::Commit
+    } else if parser.accept_keyword("ROLLBACK") {
+        parser.accept_keyword("TRANSACTION");
+        let savepoint = if parser.accept_keyword("TO") {
+            parser.accept_keyword("SAVEPOINT");
+            Some(parser.expect_identifier()?)
+        } else {
+            None
+        };
+        TransactionControl::Rollback { savepoint }
+    } else if parser.accept_keyword("SAVEPOINT") {
+        TransactionControl::Savepoint { name: parser.expect_identifier()? }
+    } else {
+        parser.expect_keyword("RELEASE")?;
+        parser.accept_keyword("SAVEPOINT");
+        TransactionControl::Release { name: parser.expect_identifier()? }
+    };
+
+    Ok(SqlStatement::Transaction(control))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1111,4 +1265,65 @@ mod tests {
         let result = parse_sql("SELECT * FROM users WHERE (age > 18 AND age < 65) OR status = 'vip'");
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_bind_parameters() {
+        let query = "SELECT * FROM users WHERE name = $name AND note = '$name?' AND age > ? AND id IN (?, $name)";
+        assert_eq!(parameter_names(query), vec!["name", "1", "2"]);
+
+        let params: HashMap<String, serde_json::Value> = [
+            ("name".to_string(), serde_json::json!("O'Brien")),
+            ("1".to_string(), serde_json::json!(18)),
+            ("2".to_string(), serde_json::Value::Null),
+        ]
+        .into_iter()
+        .collect();
+        let bound = bind_parameters(query, &params).unwrap();
+        assert_eq!(
+            bound,
+            "SELECT * FROM users WHERE name = 'O\\'Brien' AND note = '$name?' AND age > 18 AND id IN (NULL, 'O\\'Brien')"
+        );
+        assert!(matches!(parse_sql(&bound), Ok(SqlStatement::Select { .. })));
+
+        let err = bind_parameters("SELECT * FROM users WHERE id = $1", &HashMap::new()).unwrap_err();
+        assert!(err.contains("'1'"));
+        let nested = [("1".to_string(), serde_json::json!({ "a": 1 }))].into_iter().collect();
+        assert!(bind_parameters("SELECT * FROM users WHERE id = $1", &nested).is_err());
+    }
+
+    #[test]
+    fn test_transaction_statements_and_split() {
+        let script = "BEGIN;\n-- seed; not a statement\nINSERT INTO notes (body) VALUES ('a;b');\nSAVEPOINT before_drop;\n\
+                      DROP TABLE old;;\nROLLBACK TO SAVEPOINT before_drop;\nCOMMIT;\n";
+        let statements = split_statements(script);
+        assert_eq!(statements.len(), 6);
+        assert_eq!(statements[1], "-- seed; not a statement\nINSERT INTO notes (body) VALUES ('a;b')");
+
+        let controls: Vec<TransactionControl> = statements
+            .iter()
+            .filter_map(|s| match parse_sql(s) {
+                Ok(SqlStatement::Transaction(control)) => Some(control),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            controls,
+            vec![
+                TransactionControl::Begin,
+                TransactionControl::Savepoint { name: "before_drop".into() },
+                TransactionControl::Rollback { savepoint: Some("before_drop".into()) },
+                TransactionControl::Commit,
+            ]
+        );
+        assert!(matches!(parse_sql(&statements[1]), Ok(SqlStatement::Insert { .. })));
+        assert!(matches!(
+            parse_sql("START TRANSACTION"),
+            Ok(SqlStatement::Transaction(TransactionControl::Begin))
+        ));
+        assert!(matches!(
+            parse_sql("release sp1"),
+            Ok(SqlStatement::Transaction(TransactionControl::Release { name })) if name == "sp1"
+        ));
+        assert!(split_statements(" ; \n").is_empty());
+    }
 }
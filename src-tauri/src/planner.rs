@@ -0,0 +1,1080 @@
+//! Query plans for `explain_query`
+//!
+//! The planner builds the plan it expects the engine to run from the parsed
+//! statement, each table's `analyze` statistics and the backend's indexes.
+//! Rows live under `<table>/<id>`, so predicates on [`KEY_COLUMN`] become
+//! key lookups or prefix ranges instead of scans. Predicates the access
+//! path does not use are pushed down into it as a filter.
+//!
+//! `EXPLAIN ANALYZE` replays the plan in Studio over the backend's key
+//! scans rather than timing the engine, and says so with
+//! [`QueryPlan::simulated`]: actual row counts are what Studio's filter,
+//! sort and limit produce, times are Studio's own, and index lookups are
+//! replayed as a filtered scan of the table. Vector searches run on the
+//! engine.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::analyze::{CollectionAnalysis, ColumnStatistics};
+use crate::connection::backend::IndexDef;
+use crate::connection::pool::Session;
+use crate::parser::sochql::{self, SochQlStatement};
+use crate::parser::sql::{self, CompareOp, SortOrder, SqlStatement, SqlValue, WhereExpr};
+use crate::stats;
+
+/// Column stored as the row key
+pub const KEY_COLUMN: &str = "id";
+
+/// Row count assumed for tables Studio has no counts for
+const DEFAULT_ROWS: u64 = 1000;
+
+const DEFAULT_EQ_SELECTIVITY: f64 = 0.005;
+const DEFAULT_RANGE_SELECTIVITY: f64 = 1.0 / 3.0;
+const DEFAULT_LIKE_SELECTIVITY: f64 = 0.05;
+
+// Cost units: reading one row sequentially costs 1
+const SEQ_ROW_COST: f64 = 1.0;
+const RANDOM_READ_COST: f64 = 4.0;
+const CPU_ROW_COST: f64 = 0.01;
+
+/// Keys read per batch when replaying a scan
+const SCAN_BATCH_SIZE: usize = 1000;
+
+/// What a plan node does
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlanOperator {
+    FullScan { table: String },
+    PrefixRange { prefix: String },
+    KeyLookup { keys: Vec<String> },
+    IndexLookup { index: String, column: String },
+    VectorAnn { table: String, column: String, k: usize, metric: Option<String> },
+    Sort { columns: Vec<String> },
+    Limit { limit: Option<usize>, offset: Option<usize> },
+    Insert { table: String },
+    Update { table: String },
+    Delete { table: String },
+    CreateTable { table: String },
+    DropTable { table: String },
+    CreateIndex { index: String, table: String },
+}
+
+/// One node of a query plan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanNode {
+    #[serde(flatten)]
+    pub operator: PlanOperator,
+    /// Predicate evaluated inside this node
+    pub filter: Option<String>,
+    pub estimated_rows: f64,
+    /// Cost of this node including its children
+    pub estimated_cost: f64,
+    /// Rows produced, from `EXPLAIN ANALYZE`
+    pub actual_rows: Option<u64>,
+    /// Time spent in this node itself, excluding its children
+    pub actual_time_ms: Option<f64>,
+    pub children: Vec<PlanNode>,
+}
+
+/// Result of `explain_query`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryPlan {
+    pub root: PlanNode,
+    pub analyzed: bool,
+    /// Actual rows and times come from Studio replaying the plan, not from
+    /// the engine running it
+    pub simulated: bool,
+    /// Total time of the `EXPLAIN ANALYZE` run
+    pub execution_time_ms: Option<f64>,
+    /// Tables without `analyze` statistics, estimated with defaults
+    pub unanalyzed_tables: Vec<String>,
+}
+
+/// Row count and statistics of one table
+#[derive(Debug, Clone, Default)]
+pub struct TableStats {
+    /// Key count from the cached collection scan
+    pub row_count: Option<u64>,
+    pub analysis: Option<CollectionAnalysis>,
+}
+
+/// What the planner knows about the database
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    pub tables: HashMap<String, TableStats>,
+    pub indexes: Vec<IndexDef>,
+}
+
+impl Catalog {
+    fn row_count(&self, table: &str) -> u64 {
+        let stats = self.tables.get(table);
+        stats
+            .and_then(|s| s.analysis.as_ref().map(|a| a.row_count).or(s.row_count))
+            .unwrap_or(DEFAULT_ROWS)
+    }
+
+    fn analysis(&self, table: &str) -> Option<&CollectionAnalysis> {
+        self.tables.get(table)?.analysis.as_ref()
+    }
+
+    fn index_on(&self, table: &str, column: &str) -> Option<&IndexDef> {
+        self.indexes
+            .iter()
+            .find(|i| i.table == table && i.columns.first().is_some_and(|c| c == column))
+    }
+}
+
+/// A parsed SQL or SochQL statement
+#[derive(Debug, Clone)]
+pub enum Statement {
+    Sql(SqlStatement),
+    SochQl(SochQlStatement),
+}
+
+impl Statement {
+    /// Parse SochQL if the query is SochQL, SQL otherwise
+    pub fn parse(query: &str) -> Result<Self, String> {
+        match sochql::parse_sochql(query) {
+            Ok(SochQlStatement::Unknown(_)) | Err(_) => {}
+            Ok(statement) => return Ok(Statement::SochQl(statement)),
+        }
+        match sql::parse_sql(query)? {
+            SqlStatement::Unsupported { reason, .. } => Err(reason),
+            statement => Ok(Statement::Sql(statement)),
+        }
+    }
+
+    /// Tables the statement reads or writes
+    pub fn tables(&self) -> Vec<String> {
+        let table = match self {
+            Statement::Sql(
+                SqlStatement::Select { table, .. }
+                | SqlStatement::Insert { table, .. }
+                | SqlStatement::Update { table, .. }
+                | SqlStatement::Delete { table, .. }
+                | SqlStatement::CreateIndex { table, .. },
+            ) => Some(table.clone()),
+            Statement::Sql(SqlStatement::CreateTable { name, .. } | SqlStatement::DropTable { name, .. }) => {
+                Some(name.clone())
+            }
+            Statement::Sql(SqlStatement::Transaction(_) | SqlStatement::Unsupported { .. }) => None,
+            Statement::SochQl(SochQlStatement::VectorSearch { table, .. }) => Some(table.clone()),
+            Statement::SochQl(
+                SochQlStatement::Get { path }
+                | SochQlStatement::Put { path, .. }
+                | SochQlStatement::Delete { path }
+                | SochQlStatement::Scan { prefix: path },
+            ) => stats::collection_name(path.as_bytes()),
+            Statement::SochQl(SochQlStatement::Unknown(_)) => None,
+        };
+        table.into_iter().collect()
+    }
+}
+
+/// Split a leading `EXPLAIN` or `EXPLAIN ANALYZE` off a query
+pub fn strip_explain(query: &str) -> (&str, bool) {
+    let trimmed = query.trim_start();
+    let Some(rest) = strip_keyword(trimmed, "EXPLAIN") else {
+        return (query, false);
+    };
+    match strip_keyword(rest, "ANALYZE") {
+        Some(rest) => (rest, true),
+        None => (rest, false),
+    }
+}
+
+fn strip_keyword<'a>(text: &'a str, keyword: &str) -> Option<&'a str> {
+    let head = text.get(..keyword.len())?;
+    let rest = &text[keyword.len()..];
+    (head.eq_ignore_ascii_case(keyword) && rest.starts_with(char::is_whitespace)).then(|| rest.trim_start())
+}
+
+/// A plan: a chain of steps, root first
+#[derive(Debug, Clone)]
+pub struct Plan {
+    steps: Vec<Step>,
+    unanalyzed_tables: Vec<String>,
+    /// The statement writes, so `EXPLAIN ANALYZE` refuses to run it
+    writes: bool,
+    /// The query as given, for steps the engine runs
+    query: String,
+    /// Selected columns of a `SELECT` (`*` selects all)
+    columns: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Step {
+    node: PlanNode,
+    exec: Exec,
+}
+
+/// How `EXPLAIN ANALYZE` replays a step
+#[derive(Debug, Clone)]
+enum Exec {
+    /// Scan `prefix`, keep rows whose key is one of `keys` (if given) and
+    /// that match `predicate`
+    Scan {
+        prefix: String,
+        keys: Option<Vec<String>>,
+        predicate: Option<WhereExpr>,
+    },
+    /// Run the whole query on the engine
+    Engine,
+    Sort(Vec<(String, SortOrder)>),
+    Limit { limit: Option<usize>, offset: Option<usize> },
+    /// Writes and DDL are never replayed
+    None,
+}
+
+/// A read that can be streamed off key scans in key order
+#[derive(Debug, Clone)]
+pub struct ScanSpec {
+    /// Scanned one after another
+    pub prefixes: Vec<String>,
+    /// Keep only keys equal to a prefix (key lookups)
+    exact: bool,
+    predicate: Option<WhereExpr>,
+    /// Matching rows to skip, then return at most `limit`
+    pub offset: usize,
+    pub limit: Option<usize>,
+    /// Selected columns; empty or `*` selects all
+    pub columns: Vec<String>,
+}
+
+impl ScanSpec {
+    /// The decoded row, if the entry under `prefix` passes the filter
+    pub fn row(&self, prefix: &str, key: &[u8], value: &[u8]) -> Option<Value> {
+        if self.exact && key != prefix.as_bytes() {
+            return None;
+        }
+        let row = decode(key, value);
+        self.predicate.as_ref().is_none_or(|p| matches(p, &row)).then_some(row.1)
+    }
+}
+
+impl Plan {
+    /// The plan as a tree of estimates
+    pub fn explain(self) -> QueryPlan {
+        QueryPlan {
+            root: into_tree(self.steps),
+            analyzed: false,
+            simulated: false,
+            execution_time_ms: None,
+            unanalyzed_tables: self.unanalyzed_tables,
+        }
+    }
+
+    /// The statement only reads
+    pub fn is_read(&self) -> bool {
+        !self.writes
+    }
+
+    /// Most rows the statement can return, if a limit or `k` bounds it
+    pub fn row_limit(&self) -> Option<usize> {
+        self.steps
+            .iter()
+            .filter_map(|step| match (&step.node.operator, &step.exec) {
+                (_, Exec::Limit { limit, .. }) => *limit,
+                (PlanOperator::VectorAnn { k, .. }, _) => Some(*k),
+                _ => None,
+            })
+            .min()
+    }
+
+    /// How to stream the plan's rows straight off key scans, if it only
+    /// filters and limits one access path
+    pub fn scan_spec(&self) -> Option<ScanSpec> {
+        if self.writes {
+            return None;
+        }
+        let (mut offset, mut limit) = (0, None);
+        for step in &self.steps {
+            match &step.exec {
+                Exec::Limit { limit: l, offset: o } => {
+                    offset = o.unwrap_or(0);
+                    limit = *l;
+                }
+                Exec::Scan { prefix, keys, predicate } => {
+                    return Some(ScanSpec {
+                        prefixes: keys.clone().unwrap_or_else(|| vec![prefix.clone()]),
+                        exact: keys.is_some(),
+                        predicate: predicate.clone(),
+                        offset,
+                        limit,
+                        columns: self.columns.clone(),
+                    });
+                }
+                Exec::Engine | Exec::Sort(_) | Exec::None => return None,
+            }
+        }
+        None
+    }
+
+    /// Replay the plan and annotate each node with its actual rows and own time
+    pub async fn analyze(mut self, session: &Session) -> Result<QueryPlan, String> {
+        if self.writes {
+            return Err("EXPLAIN ANALYZE only runs read statements".to_string());
+        }
+        if !sql::parameter_names(&self.query).is_empty() {
+            return Err("Bind the query's parameters before EXPLAIN ANALYZE".to_string());
+        }
+
+        // With only a limit above the scan, it can stop once the limit is met,
+        // as the engine's would
+        let scan_bound = self
+            .scan_spec()
+            .and_then(|spec| spec.limit.map(|limit| spec.offset.saturating_add(limit)));
+
+        let start = Instant::now();
+        let mut rows: Vec<Row> = Vec::new();
+        for step in self.steps.iter_mut().rev() {
+            let step_start = Instant::now();
+            rows = match &step.exec {
+                Exec::Scan { prefix, keys, predicate } => {
+                    scan(session, prefix, keys.as_deref(), predicate.as_ref(), scan_bound).await?
+                }
+                Exec::Engine => engine_rows(session, &self.query).await?,
+                Exec::Sort(columns) => {
+                    rows.sort_by(|a, b| compare_rows(a, b, columns));
+                    rows
+                }
+                Exec::Limit { limit, offset } => rows
+                    .into_iter()
+                    .skip(offset.unwrap_or(0))
+                    .take(limit.unwrap_or(usize::MAX))
+                    .collect(),
+                Exec::None => unreachable!("write plans are refused above"),
+            };
+            step.node.actual_rows = Some(rows.len() as u64);
+            step.node.actual_time_ms = Some(step_start.elapsed().as_secs_f64() * 1000.0);
+        }
+
+        let simulated = self.steps.iter().any(|step| !matches!(step.exec, Exec::Engine));
+        Ok(QueryPlan {
+            root: into_tree(self.steps),
+            analyzed: true,
+            simulated,
+            execution_time_ms: Some(start.elapsed().as_secs_f64() * 1000.0),
+            unanalyzed_tables: self.unanalyzed_tables,
+        })
+    }
+}
+
+fn into_tree(steps: Vec<Step>) -> PlanNode {
+    let mut steps = steps.into_iter().rev();
+    let mut node = steps.next().expect("a plan has at least one step").node;
+    for step in steps {
+        let mut parent = step.node;
+        parent.children.push(node);
+        node = parent;
+    }
+    node
+}
+
+fn node(operator: PlanOperator, estimated_rows: f64, estimated_cost: f64) -> PlanNode {
+    PlanNode {
+        operator,
+        filter: None,
+        estimated_rows,
+        estimated_cost,
+        actual_rows: None,
+        actual_time_ms: None,
+        children: Vec::new(),
+    }
+}
+
+/// Build the plan for `query`
+pub fn plan(query: &str, catalog: &Catalog) -> Result<Plan, String> {
+    let statement = Statement::parse(query)?;
+    let unanalyzed_tables = statement
+        .tables()
+        .into_iter()
+        .filter(|t| catalog.analysis(t).is_none())
+        .collect();
+    let mut planner = Planner { catalog, steps: Vec::new(), writes: false, columns: Vec::new() };
+
+    match statement {
+        Statement::Sql(SqlStatement::Select { table, columns, where_clause, limit, offset, order_by }) => {
+            planner.columns = columns;
+            if limit.is_some() || offset.is_some() {
+                planner.push(PlanOperator::Limit { limit, offset }, Exec::Limit { limit, offset }, 0.0, 0.0);
+            }
+            if let Some(order_by) = order_by {
+                let columns = order_by
+                    .columns
+                    .iter()
+                    .map(|(c, o)| format!("{} {}", c, if matches!(o, SortOrder::Desc) { "DESC" } else { "ASC" }))
+                    .collect();
+                planner.push(PlanOperator::Sort { columns }, Exec::Sort(order_by.columns), 0.0, 0.0);
+            }
+            planner.access(&table, where_clause);
+        }
+        Statement::Sql(SqlStatement::Insert { table, values, .. }) => {
+            let rows = values.len() as f64;
+            let cost = planner.write_cost(&table, rows);
+            planner.write(PlanOperator::Insert { table }, rows, cost);
+        }
+        Statement::Sql(SqlStatement::Update { table, where_clause, .. }) => {
+            planner.write(PlanOperator::Update { table: table.clone() }, 0.0, 0.0);
+            planner.access(&table, where_clause);
+        }
+        Statement::Sql(SqlStatement::Delete { table, where_clause }) => {
+            planner.write(PlanOperator::Delete { table: table.clone() }, 0.0, 0.0);
+            planner.access(&table, where_clause);
+        }
+        Statement::Sql(SqlStatement::CreateTable { name, .. }) => {
+            planner.write(PlanOperator::CreateTable { table: name }, 0.0, RANDOM_READ_COST);
+        }
+        Statement::Sql(SqlStatement::DropTable { name, .. }) => {
+            let rows = catalog.row_count(&name) as f64;
+            planner.write(PlanOperator::DropTable { table: name }, rows, rows * RANDOM_READ_COST);
+        }
+        Statement::Sql(SqlStatement::CreateIndex { name, table, .. }) => {
+            let rows = catalog.row_count(&table) as f64;
+            let cost = rows * SEQ_ROW_COST + sort_cost(rows) + rows * RANDOM_READ_COST;
+            planner.write(PlanOperator::CreateIndex { index: name, table }, rows, cost);
+        }
+        Statement::Sql(SqlStatement::Transaction(_)) => {
+            return Err("Transaction control statements have no plan".to_string());
+        }
+        Statement::Sql(SqlStatement::Unsupported { reason, .. }) => return Err(reason),
+        Statement::SochQl(SochQlStatement::VectorSearch { table, column, limit, metric, .. }) => {
+            let total = catalog.row_count(&table) as f64;
+            let rows = (limit as f64).min(total);
+            let cost = RANDOM_READ_COST * (total.max(2.0).log2() + limit as f64);
+            planner.push(PlanOperator::VectorAnn { table, column, k: limit, metric }, Exec::Engine, rows, cost);
+        }
+        Statement::SochQl(SochQlStatement::Get { path }) => {
+            planner.key_lookup(vec![path]);
+        }
+        Statement::SochQl(SochQlStatement::Scan { prefix }) => {
+            let prefix = prefix.trim_end_matches('*').to_string();
+            let rows = prefix_rows(catalog, &prefix);
+            let cost = RANDOM_READ_COST + rows * SEQ_ROW_COST;
+            let exec = Exec::Scan { prefix: prefix.clone(), keys: None, predicate: None };
+            planner.push(PlanOperator::PrefixRange { prefix }, exec, rows, cost);
+        }
+        Statement::SochQl(SochQlStatement::Put { path, .. }) => {
+            let table = stats::collection_name(path.as_bytes()).unwrap_or_default();
+            let cost = planner.write_cost(&table, 1.0);
+            planner.write(PlanOperator::Insert { table }, 1.0, cost);
+        }
+        Statement::SochQl(SochQlStatement::Delete { path }) => {
+            let table = stats::collection_name(path.as_bytes()).unwrap_or_default();
+            planner.write(PlanOperator::Delete { table }, 0.0, 0.0);
+            planner.key_lookup(vec![path]);
+        }
+        Statement::SochQl(SochQlStatement::Unknown(query)) => {
+            return Err(format!("Cannot plan statement: {}", query));
+        }
+    }
+
+    let writes = planner.writes;
+    let mut steps = std::mem::take(&mut planner.steps);
+    cascade_estimates(&mut steps);
+    Ok(Plan { steps, unanalyzed_tables, writes, query: query.to_string(), columns: planner.columns })
+}
+
+struct Planner<'a> {
+    catalog: &'a Catalog,
+    /// Root first; estimates of nodes above the access path are filled in
+    /// by [`cascade_estimates`]
+    steps: Vec<Step>,
+    writes: bool,
+    columns: Vec<String>,
+}
+
+impl Planner<'_> {
+    fn push(&mut self, operator: PlanOperator, exec: Exec, rows: f64, cost: f64) -> &mut PlanNode {
+        self.steps.push(Step { node: node(operator, rows, cost), exec });
+        &mut self.steps.last_mut().expect("just pushed").node
+    }
+
+    fn write(&mut self, operator: PlanOperator, rows: f64, cost: f64) {
+        self.writes = true;
+        self.push(operator, Exec::None, rows, cost);
+    }
+
+    /// Cost of writing `rows` rows and their index entries
+    fn write_cost(&self, table: &str, rows: f64) -> f64 {
+        let indexes = self.catalog.indexes.iter().filter(|i| i.table == table).count();
+        rows * RANDOM_READ_COST * (1 + indexes) as f64
+    }
+
+    fn key_lookup(&mut self, keys: Vec<String>) {
+        let rows = keys.len() as f64;
+        let exec = Exec::Scan { prefix: String::new(), keys: Some(keys.clone()), predicate: None };
+        self.push(PlanOperator::KeyLookup { keys }, exec, rows, rows * RANDOM_READ_COST);
+    }
+
+    /// Pick the cheapest way to read `table`'s rows matching `where_clause`
+    fn access(&mut self, table: &str, where_clause: Option<WhereExpr>) {
+        let total = self.catalog.row_count(table) as f64;
+        let analysis = self.catalog.analysis(table);
+        let conjuncts = match where_clause {
+            Some(WhereExpr::And(exprs)) => exprs,
+            Some(expr) => vec![expr],
+            None => Vec::new(),
+        };
+        let table_prefix = format!("{}/", table);
+
+        let full_scan = Access {
+            operator: PlanOperator::FullScan { table: table.to_string() },
+            prefix: table_prefix.clone(),
+            keys: None,
+            used: None,
+            rows: total,
+            cost: total * SEQ_ROW_COST,
+        };
+        let mut best = full_scan;
+        for (i, conjunct) in conjuncts.iter().enumerate() {
+            let candidate = match conjunct {
+                WhereExpr::Comparison { column, op: CompareOp::Eq, value } if column == KEY_COLUMN => {
+                    key_access(&table_prefix, vec![value.clone()], i)
+                }
+                WhereExpr::In { column, values, negated: false } if column == KEY_COLUMN => {
+                    key_access(&table_prefix, values.clone(), i)
+                }
+                WhereExpr::Like { column, pattern, negated: false } if column == KEY_COLUMN => {
+                    literal_prefix(pattern).map(|prefix| {
+                        let rows = total * DEFAULT_LIKE_SELECTIVITY;
+                        Access {
+                            operator: PlanOperator::PrefixRange { prefix: format!("{}{}", table_prefix, prefix) },
+                            prefix: format!("{}{}", table_prefix, prefix),
+                            keys: None,
+                            used: Some(i),
+                            rows,
+                            cost: RANDOM_READ_COST + rows * SEQ_ROW_COST,
+                        }
+                    })
+                }
+                _ => expr_column(conjunct)
+                    .and_then(|column| self.catalog.index_on(table, column))
+                    .map(|index| {
+                        let rows = total * selectivity(conjunct, analysis, total);
+                        Access {
+                            operator: PlanOperator::IndexLookup {
+                                index: index.name.clone(),
+                                column: index.columns[0].clone(),
+                            },
+                            prefix: table_prefix.clone(),
+                            keys: None,
+                            used: Some(i),
+                            rows,
+                            cost: RANDOM_READ_COST * (total.max(2.0).log2() + rows),
+                        }
+                    }),
+            };
+            if let Some(candidate) = candidate {
+                if candidate.cost < best.cost {
+                    best = candidate;
+                }
+            }
+        }
+
+        // Whatever the access path does not use is filtered inside it
+        let mut rest = conjuncts;
+        let used = best.used.map(|i| rest.remove(i));
+        let filter = match rest.len() {
+            0 => None,
+            1 => rest.pop(),
+            _ => Some(WhereExpr::And(rest)),
+        };
+        let rows_in = best.rows;
+        let rows_out = rows_in * filter.as_ref().map_or(1.0, |f| selectivity(f, analysis, total));
+        let cost = best.cost + if filter.is_some() { rows_in * CPU_ROW_COST } else { 0.0 };
+
+        // An index lookup is replayed as a scan, so its predicate is re-checked
+        let predicate = match (&best.operator, used, filter.clone()) {
+            (PlanOperator::IndexLookup { .. }, Some(used), Some(filter)) => Some(WhereExpr::And(vec![used, filter])),
+            (PlanOperator::IndexLookup { .. }, Some(used), None) => Some(used),
+            (_, _, filter) => filter,
+        };
+        let exec = Exec::Scan { prefix: best.prefix, keys: best.keys, predicate };
+        let node = self.push(best.operator, exec, rows_out, cost);
+        node.filter = filter.as_ref().map(describe);
+    }
+}
+
+/// A candidate access path
+struct Access {
+    operator: PlanOperator,
+    prefix: String,
+    keys: Option<Vec<String>>,
+    /// Index of the conjunct the path evaluates itself
+    used: Option<usize>,
+    rows: f64,
+    cost: f64,
+}
+
+fn key_access(table_prefix: &str, values: Vec<SqlValue>, used: usize) -> Option<Access> {
+    let keys: Vec<String> = values
+        .iter()
+        .map(|v| match v {
+            SqlValue::Integer(i) => Some(format!("{}{}", table_prefix, i)),
+            SqlValue::Text(s) => Some(format!("{}{}", table_prefix, s)),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    let rows = keys.len() as f64;
+    Some(Access {
+        operator: PlanOperator::KeyLookup { keys: keys.clone() },
+        prefix: table_prefix.to_string(),
+        keys: Some(keys),
+        used: Some(used),
+        rows,
+        cost: rows * RANDOM_READ_COST,
+    })
+}
+
+/// The literal prefix of a `LIKE 'abc%'` pattern, if that is its only wildcard
+fn literal_prefix(pattern: &str) -> Option<&str> {
+    let prefix = pattern.strip_suffix('%')?;
+    (!prefix.is_empty() && !prefix.contains(['%', '_'])).then_some(prefix)
+}
+
+/// Rows under a SochQL scan prefix, using the analyzed key distribution
+fn prefix_rows(catalog: &Catalog, prefix: &str) -> f64 {
+    let Some(table) = stats::collection_name(prefix.as_bytes()) else {
+        return catalog.tables.keys().map(|t| catalog.row_count(t)).sum::<u64>().max(DEFAULT_ROWS) as f64;
+    };
+    let total = catalog.row_count(&table) as f64;
+    let rest = prefix.trim_start_matches('/')[table.len()..].trim_start_matches('/');
+    if rest.is_empty() {
+        return total;
+    }
+    let segment = rest.split('/').next().unwrap_or(rest);
+    catalog
+        .analysis(&table)
+        .and_then(|a| a.key_prefixes.iter().find(|p| p.prefix == segment))
+        .map_or(total * DEFAULT_LIKE_SELECTIVITY, |p| p.count as f64)
+}
+
+/// Fill in the rows and cost of the nodes above the access path
+fn cascade_estimates(steps: &mut [Step]) {
+    for i in (0..steps.len().saturating_sub(1)).rev() {
+        let (rows, cost) = (steps[i + 1].node.estimated_rows, steps[i + 1].node.estimated_cost);
+        let node = &mut steps[i].node;
+        match &node.operator {
+            PlanOperator::Sort { .. } => {
+                node.estimated_rows = rows;
+                node.estimated_cost = cost + sort_cost(rows);
+            }
+            PlanOperator::Limit { limit, offset } => {
+                let after_offset = (rows - offset.unwrap_or(0) as f64).max(0.0);
+                node.estimated_rows = limit.map_or(after_offset, |l| after_offset.min(l as f64));
+                node.estimated_cost = cost;
+            }
+            PlanOperator::Update { .. } | PlanOperator::Delete { .. } => {
+                node.estimated_rows = rows;
+                node.estimated_cost = cost + rows * RANDOM_READ_COST;
+            }
+            _ => {
+                node.estimated_rows = rows;
+                node.estimated_cost += cost;
+            }
+        }
+    }
+}
+
+fn sort_cost(rows: f64) -> f64 {
+    rows * rows.max(2.0).log2() * CPU_ROW_COST
+}
+
+/// The single column a predicate tests
+fn expr_column(expr: &WhereExpr) -> Option<&str> {
+    match expr {
+        WhereExpr::Comparison { column, op, .. } if !matches!(op, CompareOp::Ne) => Some(column),
+        WhereExpr::In { column, negated: false, .. } | WhereExpr::Between { column, .. } => Some(column),
+        _ => None,
+    }
+}
+
+/// Estimated fraction of rows matching `expr`
+fn selectivity(expr: &WhereExpr, analysis: Option<&CollectionAnalysis>, total: f64) -> f64 {
+    let stats = |column: &str| -> Option<&ColumnStatistics> {
+        analysis?.columns.iter().find(|c| c.name == column)
+    };
+    let eq = |column: &str, value: &SqlValue| -> f64 {
+        if column == KEY_COLUMN {
+            return 1.0 / total.max(1.0);
+        }
+        match (stats(column), to_json(value)) {
+            (Some(stats), Some(value)) => stats.equality_selectivity(&value),
+            _ => DEFAULT_EQ_SELECTIVITY,
+        }
+    };
+    let range = |column: &str, low: Option<&SqlValue>, high: Option<&SqlValue>| -> f64 {
+        stats(column)
+            .and_then(|s| s.range_selectivity(low.and_then(to_f64), high.and_then(to_f64)))
+            .unwrap_or(DEFAULT_RANGE_SELECTIVITY)
+    };
+
+    match expr {
+        WhereExpr::Comparison { column, op, value } => match op {
+            CompareOp::Eq => eq(column, value),
+            CompareOp::Ne => 1.0 - eq(column, value),
+            CompareOp::Lt | CompareOp::Le => range(column, None, Some(value)),
+            CompareOp::Gt | CompareOp::Ge => range(column, Some(value), None),
+        },
+        WhereExpr::In { column, values, negated } => {
+            let s = values.iter().map(|v| eq(column, v)).sum::<f64>().min(1.0);
+            if *negated { 1.0 - s } else { s }
+        }
+        WhereExpr::Like { negated, .. } => {
+            if *negated { 1.0 - DEFAULT_LIKE_SELECTIVITY } else { DEFAULT_LIKE_SELECTIVITY }
+        }
+        WhereExpr::IsNull { column, negated } => {
+            let s = stats(column).map_or(DEFAULT_EQ_SELECTIVITY, |s| s.null_fraction);
+            if *negated { 1.0 - s } else { s }
+        }
+        WhereExpr::Between { column, low, high } => range(column, Some(low), Some(high)),
+        WhereExpr::And(exprs) => exprs.iter().map(|e| selectivity(e, analysis, total)).product(),
+        WhereExpr::Or(exprs) => {
+            1.0 - exprs.iter().map(|e| 1.0 - selectivity(e, analysis, total)).product::<f64>()
+        }
+        WhereExpr::Not(expr) => 1.0 - selectivity(expr, analysis, total),
+    }
+    .clamp(0.0, 1.0)
+}
+
+fn to_json(value: &SqlValue) -> Option<Value> {
+    match value {
+        SqlValue::Null => Some(Value::Null),
+        SqlValue::Integer(i) => Some(json!(i)),
+        SqlValue::Float(f) => Some(json!(f)),
+        SqlValue::Text(s) => Some(json!(s)),
+        SqlValue::Boolean(b) => Some(json!(b)),
+        SqlValue::Placeholder(_) => None,
+    }
+}
+
+fn to_f64(value: &SqlValue) -> Option<f64> {
+    match value {
+        SqlValue::Integer(i) => Some(*i as f64),
+        SqlValue::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+/// A predicate as SQL text
+fn describe(expr: &WhereExpr) -> String {
+    let value = |v: &SqlValue| match v {
+        SqlValue::Null => "NULL".to_string(),
+        SqlValue::Integer(i) => i.to_string(),
+        SqlValue::Float(f) => f.to_string(),
+        SqlValue::Text(s) => format!("'{}'", s),
+        SqlValue::Boolean(b) => if *b { "TRUE" } else { "FALSE" }.to_string(),
+        SqlValue::Placeholder(p) => p.clone(),
+    };
+    let not = |negated: bool| if negated { "NOT " } else { "" };
+    match expr {
+        WhereExpr::Comparison { column, op, value: v } => {
+            let op = match op {
+                CompareOp::Eq => "=",
+                CompareOp::Ne => "!=",
+                CompareOp::Lt => "<",
+                CompareOp::Le => "<=",
+                CompareOp::Gt => ">",
+                CompareOp::Ge => ">=",
+            };
+            format!("{} {} {}", column, op, value(v))
+        }
+        WhereExpr::In { column, values, negated } => {
+            let values: Vec<String> = values.iter().map(value).collect();
+            format!("{} {}IN ({})", column, not(*negated), values.join(", "))
+        }
+        WhereExpr::Like { column, pattern, negated } => format!("{} {}LIKE '{}'", column, not(*negated), pattern),
+        WhereExpr::IsNull { column, negated } => format!("{} IS {}NULL", column, not(*negated)),
+        WhereExpr::Between { column, low, high } => format!("{} BETWEEN {} AND {}", column, value(low), value(high)),
+        WhereExpr::And(exprs) => exprs.iter().map(|e| format!("({})", describe(e))).collect::<Vec<_>>().join(" AND "),
+        WhereExpr::Or(exprs) => exprs.iter().map(|e| format!("({})", describe(e))).collect::<Vec<_>>().join(" OR "),
+        WhereExpr::Not(expr) => format!("NOT ({})", describe(expr)),
+    }
+}
+
+/// A replayed row: its key and decoded value
+type Row = (String, Value);
+
+/// Rows under `prefix`, or under exactly `keys`, that match `predicate`,
+/// stopping once `bound` rows are found
+async fn scan(
+    session: &Session,
+    prefix: &str,
+    keys: Option<&[String]>,
+    predicate: Option<&WhereExpr>,
+    bound: Option<usize>,
+) -> Result<Vec<Row>, String> {
+    let bound = bound.unwrap_or(usize::MAX);
+    let mut rows = Vec::new();
+    match keys {
+        Some(keys) => {
+            for key in keys {
+                if rows.len() >= bound {
+                    break;
+                }
+                if let Some(value) = session.backend.get(key.as_bytes()).await? {
+                    let row = decode(key.as_bytes(), &value);
+                    if predicate.is_none_or(|p| matches(p, &row)) {
+                        rows.push(row);
+                    }
+                }
+            }
+        }
+        None => {
+            // Dropping the stream stops the rest of the scan
+            let mut stream = session.backend.scan_stream(prefix.as_bytes(), SCAN_BATCH_SIZE).await?;
+            while rows.len() < bound {
+                let Some(batch) = stream.recv().await else { break };
+                for (key, value) in batch? {
+                    let row = decode(&key, &value);
+                    if predicate.is_none_or(|p| matches(p, &row)) {
+                        rows.push(row);
+                        if rows.len() >= bound {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// Values that are not JSON are returned as text
+fn decode(key: &[u8], value: &[u8]) -> Row {
+    let value = serde_json::from_slice(value)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(value).into_owned()));
+    (String::from_utf8_lossy(key).into_owned(), value)
+}
+
+/// Rows the engine returns for `query`
+async fn engine_rows(session: &Session, query: &str) -> Result<Vec<Row>, String> {
+    let result = session
+        .call_tool("sochdb_query", json!({ "query": query, "format": "json" }))
+        .await?;
+    let text = result["content"][0]["text"].as_str().unwrap_or("");
+    Ok(match serde_json::from_str(text) {
+        Ok(Value::Array(items)) => items.into_iter().map(|item| (String::new(), item)).collect(),
+        Ok(value) => vec![(String::new(), value)],
+        Err(_) => vec![(String::new(), Value::String(text.to_string()))],
+    })
+}
+
+/// A column of a replayed row; the key column falls back to the row key
+fn column(row: &Row, name: &str) -> Option<Value> {
+    match row.1.get(name) {
+        Some(value) => Some(value.clone()),
+        None if name == KEY_COLUMN => row.0.rsplit('/').next().map(|id| json!(id)),
+        None => None,
+    }
+}
+
+fn matches(expr: &WhereExpr, row: &Row) -> bool {
+    let compare = |column_name: &str, value: &SqlValue| -> Option<Ordering> {
+        compare_values(&column(row, column_name)?, &to_json(value)?)
+    };
+    match expr {
+        WhereExpr::Comparison { column, op, value } => {
+            let ordering = compare(column, value);
+            match op {
+                CompareOp::Eq => ordering == Some(Ordering::Equal),
+                CompareOp::Ne => ordering.is_some_and(|o| o != Ordering::Equal),
+                CompareOp::Lt => ordering == Some(Ordering::Less),
+                CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                CompareOp::Gt => ordering == Some(Ordering::Greater),
+                CompareOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            }
+        }
+        WhereExpr::In { column, values, negated } => {
+            values.iter().any(|v| compare(column, v) == Some(Ordering::Equal)) != *negated
+        }
+        WhereExpr::Like { column: name, pattern, negated } => {
+            let text = column(row, name).and_then(|v| v.as_str().map(str::to_string));
+            text.is_some_and(|t| like(&t, pattern)) != *negated
+        }
+        WhereExpr::IsNull { column: name, negated } => {
+            column(row, name).is_none_or(|v| v.is_null()) != *negated
+        }
+        WhereExpr::Between { column, low, high } => {
+            matches!(compare(column, low), Some(Ordering::Greater | Ordering::Equal))
+                && matches!(compare(column, high), Some(Ordering::Less | Ordering::Equal))
+        }
+        WhereExpr::And(exprs) => exprs.iter().all(|e| matches(e, row)),
+        WhereExpr::Or(exprs) => exprs.iter().any(|e| matches(e, row)),
+        WhereExpr::Not(expr) => !matches(expr, row),
+    }
+}
+
+/// Order two JSON values of the same kind; ids read from keys compare as numbers
+fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        (Value::String(x), Value::Number(y)) => x.parse::<f64>().ok()?.partial_cmp(&y.as_f64()?),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(y)),
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+/// Nulls and missing values sort first
+fn compare_rows(a: &Row, b: &Row, columns: &[(String, SortOrder)]) -> Ordering {
+    for (name, order) in columns {
+        let ordering = match (column(a, name), column(b, name)) {
+            (Some(x), Some(y)) => compare_values(&x, &y).unwrap_or(Ordering::Equal),
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        let ordering = if matches!(order, SortOrder::Desc) { ordering.reverse() } else { ordering };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// SQL `LIKE`: `%` matches any run of characters, `_` exactly one
+fn like(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    // matched[j]: text[..i] matches pattern[..j]
+    let mut matched = vec![false; pattern.len() + 1];
+    matched[0] = true;
+    for j in 1..=pattern.len() {
+        matched[j] = matched[j - 1] && pattern[j - 1] == '%';
+    }
+    for c in text {
+        let mut next = vec![false; pattern.len() + 1];
+        for j in 1..=pattern.len() {
+            next[j] = match pattern[j - 1] {
+                '%' => next[j - 1] || matched[j],
+                '_' => matched[j - 1],
+                p => matched[j - 1] && p == c,
+            };
+        }
+        matched = next;
+    }
+    matched[pattern.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyze::analyze_collection;
+
+    fn catalog() -> Catalog {
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = (0..1000)
+            .map(|i| {
+                let value = json!({ "id": i, "age": i % 100, "role": if i % 10 == 0 { "admin" } else { "user" } });
+                (format!("users/{}", i).into_bytes(), value.to_string().into_bytes())
+            })
+            .collect();
+        let mut tables = HashMap::new();
+        tables.insert(
+            "users".to_string(),
+            TableStats { row_count: Some(1000), analysis: Some(analyze_collection("users", &entries, 1000)) },
+        );
+        Catalog {
+            tables,
+            indexes: vec![IndexDef {
+                name: "users_role".to_string(),
+                table: "users".to_string(),
+                columns: vec!["role".to_string()],
+            }],
+        }
+    }
+
+    fn root(query: &str) -> PlanNode {
+        plan(query, &catalog()).unwrap().explain().root
+    }
+
+    #[test]
+    fn test_access_paths() {
+        let scan = root("SELECT * FROM users WHERE age > 49");
+        assert_eq!(scan.operator, PlanOperator::FullScan { table: "users".to_string() });
+        assert_eq!(scan.filter.as_deref(), Some("age > 49"));
+        assert!((scan.estimated_rows - 500.0).abs() < 20.0);
+
+        let lookup = root("SELECT * FROM users WHERE id = 7 AND age > 3");
+        assert_eq!(lookup.operator, PlanOperator::KeyLookup { keys: vec!["users/7".to_string()] });
+        assert_eq!(lookup.filter.as_deref(), Some("age > 3"));
+
+        let index = root("SELECT * FROM users WHERE role = 'admin'");
+        assert_eq!(
+            index.operator,
+            PlanOperator::IndexLookup { index: "users_role".to_string(), column: "role".to_string() }
+        );
+        assert!((index.estimated_rows - 100.0).abs() < 1e-6);
+        assert!(index.estimated_cost < scan.estimated_cost);
+
+        // Most rows match, so scanning beats the index
+        let common = root("SELECT * FROM users WHERE role = 'user'");
+        assert!(matches!(common.operator, PlanOperator::FullScan { .. }));
+
+        let range = root("SELECT * FROM users WHERE id LIKE '12%'");
+        assert_eq!(range.operator, PlanOperator::PrefixRange { prefix: "users/12".to_string() });
+
+        let ann = root("VECTOR_SEARCH users USING embedding NEAR [0.1, 0.2] LIMIT 5");
+        assert!(matches!(ann.operator, PlanOperator::VectorAnn { k: 5, .. }));
+        assert_eq!(ann.estimated_rows, 5.0);
+    }
+
+    #[test]
+    fn test_sort_limit_and_writes() {
+        let select = plan("SELECT name FROM users WHERE age < 10 ORDER BY name DESC LIMIT 5 OFFSET 2", &catalog()).unwrap();
+        assert!(!select.writes);
+        assert_eq!(select.row_limit(), Some(5));
+        assert_eq!(plan("SELECT * FROM users ORDER BY name", &catalog()).unwrap().row_limit(), None);
+        let limit = select.explain().root;
+        assert_eq!(limit.operator, PlanOperator::Limit { limit: Some(5), offset: Some(2) });
+        assert_eq!(limit.estimated_rows, 5.0);
+        let sort = &limit.children[0];
+        assert_eq!(sort.operator, PlanOperator::Sort { columns: vec!["name DESC".to_string()] });
+        assert!(sort.estimated_cost > sort.children[0].estimated_cost);
+        assert_eq!(limit.estimated_cost, sort.estimated_cost);
+
+        let delete = plan("DELETE FROM users WHERE id IN (1, 2)", &catalog()).unwrap();
+        assert!(delete.writes);
+        let delete = delete.explain().root;
+        assert_eq!(delete.estimated_rows, 2.0);
+        assert!(matches!(delete.children[0].operator, PlanOperator::KeyLookup { .. }));
+
+        let unanalyzed = plan("SELECT * FROM orders", &catalog()).unwrap().explain();
+        assert_eq!(unanalyzed.unanalyzed_tables, vec!["orders"]);
+        assert_eq!(unanalyzed.root.estimated_rows, DEFAULT_ROWS as f64);
+
+        assert_eq!(strip_explain("explain analyze SELECT 1"), ("SELECT 1", true));
+        assert_eq!(strip_explain("EXPLAIN SELECT 1"), ("SELECT 1", false));
+        assert_eq!(strip_explain("EXPLAINED"), ("EXPLAINED", false));
+    }
+
+    #[test]
+    fn test_replay_helpers() {
+        let row: Row = ("users/42".to_string(), json!({ "name": "Ada", "age": 36 }));
+        let parsed = |query: &str| match sql::parse_sql(query).unwrap() {
+            SqlStatement::Select { where_clause: Some(expr), .. } => expr,
+            other => panic!("unexpected statement {:?}", other),
+        };
+        assert!(matches(&parsed("SELECT * FROM users WHERE id = 42 AND age BETWEEN 30 AND 40"), &row));
+        assert!(matches(&parsed("SELECT * FROM users WHERE name LIKE 'A_a%' OR age < 3"), &row));
+        assert!(matches(&parsed("SELECT * FROM users WHERE email IS NULL"), &row));
+        assert!(!matches(&parsed("SELECT * FROM users WHERE name IN ('Bob', 'Eve')"), &row));
+
+        assert!(like("users/eu/1", "users/%/1"));
+        assert!(!like("users", "user"));
+
+        let mut rows = [
+            ("a".to_string(), json!({ "n": 2 })),
+            ("b".to_string(), json!({})),
+            ("c".to_string(), json!({ "n": 10 })),
+        ];
+        rows.sort_by(|a, b| compare_rows(a, b, &[("n".to_string(), SortOrder::Desc)]));
+        assert_eq!(rows.iter().map(|r| r.0.as_str()).collect::<Vec<_>>(), vec!["c", "a", "b"]);
+    }
+}
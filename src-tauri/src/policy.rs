@@ -157,29 +157,53 @@ impl CommandPolicy {
         // Admin operations that require confirmation
         matches!(
             command,
-            "checkpoint" | "gc" | "compact" | "disconnect"
+            "checkpoint" | "gc" | "compact" | "disconnect" | "close_session"
         )
     }
 
     /// Get the category for a command
     pub fn get_command_category(command: &str) -> CommandCategory {
         match command {
-            "execute_query" | "explain_query" => CommandCategory::Query,
+            "execute_query" | "execute_script" | "explain_query" | "list_running_queries" | "cancel_query"
+            | "open_cursor" | "fetch_cursor" | "stream_cursor" | "close_cursor"
+            | "search_query_history" | "pin_query_history"
+            | "delete_query_history" | "clear_query_history" | "rerun_query_history"
+            | "list_saved_queries" | "save_query" | "delete_saved_query" | "run_saved_query"
+            | "export_saved_queries" | "import_saved_queries" => CommandCategory::Query,
             "get_schema" | "get_table_info" => CommandCategory::Schema,
-            "checkpoint" | "gc" | "compact" | "get_stats" => CommandCategory::Admin,
+            "checkpoint" | "gc" | "compact" | "analyze" | "get_stats" | "get_collection_stats" | "get_storage_breakdown"
+            | "get_metrics_history" | "get_exporter_status" | "set_exporter_config"
+            | "backup_database" | "restore_database" | "verify_database" | "list_jobs" | "get_job" | "cancel_job"
+            | "list_schedules" | "save_schedule" | "delete_schedule" | "get_maintenance_history" => CommandCategory::Admin,
             "vector_search" | "get_vector_columns" => CommandCategory::Vector,
             "mcp_list_tools" | "mcp_call_tool" | "get_service_status" => CommandCategory::Mcp,
             "save_llm_config" | "get_llm_config" | "clear_llm_config" | "test_llm_connection" | "chat_completion" => CommandCategory::Llm,
-            "connect" | "disconnect" => CommandCategory::Connection,
+            "connect" | "connect_read_only" | "disconnect" | "list_sessions" | "switch_session" | "close_session"
+            | "list_saved_connections" | "add_saved_connection" | "update_saved_connection"
+            | "delete_saved_connection" | "duplicate_saved_connection" | "reorder_saved_connections"
+            | "group_saved_connections" | "set_statement_timeout" => CommandCategory::Connection,
             _ => CommandCategory::Query, // Default to most permissive category
         }
     }
 
-    /// Check if a command is mutating (writes data)
+    /// Check if a command is mutating: it writes to the database or to state
+    /// Studio persists (settings, saved connections and queries, query
+    /// history, schedules). Commands that run queries are not; their
+    /// statements are checked when they reach a read-only session.
     pub fn is_mutating_command(command: &str) -> bool {
         matches!(
             command,
-            "checkpoint" | "gc" | "compact" | "mcp_call_tool" | "save_llm_config" | "clear_llm_config"
+            // Database
+            "checkpoint" | "gc" | "compact" | "analyze" | "restore_database" | "mcp_call_tool"
+                // Saved connections
+                | "add_saved_connection" | "update_saved_connection" | "delete_saved_connection"
+                | "duplicate_saved_connection" | "reorder_saved_connections" | "group_saved_connections"
+                | "set_statement_timeout"
+                // Query history and library
+                | "pin_query_history" | "delete_query_history" | "clear_query_history"
+                | "save_query" | "delete_saved_query" | "import_saved_queries"
+                // Settings and schedules
+                | "save_schedule" | "delete_schedule" | "set_exporter_config" | "save_llm_config" | "clear_llm_config"
         )
     }
 
@@ -187,13 +211,24 @@ impl CommandPolicy {
     pub fn get_allowed_commands(&self) -> Vec<&'static str> {
         let all_commands = [
             // Connection
-            "connect", "disconnect",
+            "connect", "connect_read_only", "disconnect", "list_sessions", "switch_session", "close_session",
+            "list_saved_connections", "add_saved_connection", "update_saved_connection",
+            "delete_saved_connection", "duplicate_saved_connection", "reorder_saved_connections",
+            "group_saved_connections", "set_statement_timeout",
             // Query
-            "execute_query", "explain_query",
+            "execute_query", "execute_script", "explain_query", "list_running_queries", "cancel_query",
+            "open_cursor", "fetch_cursor", "stream_cursor", "close_cursor",
+            "search_query_history", "pin_query_history",
+            "delete_query_history", "clear_query_history", "rerun_query_history",
+            "list_saved_queries", "save_query", "delete_saved_query", "run_saved_query",
+            "export_saved_queries", "import_saved_queries",
             // Schema
             "get_schema", "get_table_info",
             // Admin
-            "checkpoint", "gc", "compact", "get_stats",
+            "checkpoint", "gc", "compact", "analyze", "get_stats", "get_collection_stats", "get_storage_breakdown",
+            "get_metrics_history", "get_exporter_status", "set_exporter_config",
+            "backup_database", "restore_database", "verify_database", "list_jobs", "get_job", "cancel_job",
+            "list_schedules", "save_schedule", "delete_schedule", "get_maintenance_history",
             // Vector
             "vector_search", "get_vector_columns",
             // MCP
@@ -264,6 +299,56 @@ pub fn check_command(policy: &CommandPolicy, command: &str) -> PolicyCheck {
     }
 }
 
+/// MCP tools that only read from the database
+const READ_ONLY_TOOLS: &[&str] = &["sochdb_get", "sochdb_list_tables", "sochdb_describe", "vector_search"];
+
+/// Check whether an MCP tool call may run on a read-only connection.
+///
+/// `sochdb_query` is allowed only when its statement is a read; any other
+/// tool must be listed in [`READ_ONLY_TOOLS`].
+pub fn check_read_only_tool(tool_name: &str, arguments: &serde_json::Value) -> Result<(), String> {
+    if tool_name == "sochdb_query" {
+        let query = arguments.get("query").and_then(|v| v.as_str()).unwrap_or("");
+        return check_read_only_statement(query);
+    }
+
+    if READ_ONLY_TOOLS.contains(&tool_name) {
+        Ok(())
+    } else {
+        Err(format!("tool '{}' is not known to be read-only", tool_name))
+    }
+}
+
+/// Check whether a SQL or SochQL statement is read-only.
+///
+/// Statements that cannot be parsed are refused, since they cannot be
+/// verified as reads.
+pub fn check_read_only_statement(query: &str) -> Result<(), String> {
+    use crate::parser::sochql::{parse_sochql, SochQlStatement};
+    use crate::parser::sql::{parse_sql, SqlStatement};
+
+    match parse_sochql(query) {
+        Ok(SochQlStatement::Put { .. }) | Ok(SochQlStatement::Delete { .. }) => {
+            return Err("PUT and DELETE statements modify data".to_string());
+        }
+        Ok(SochQlStatement::Get { .. })
+        | Ok(SochQlStatement::Scan { .. })
+        | Ok(SochQlStatement::VectorSearch { .. }) => return Ok(()),
+        _ => {}
+    }
+
+    match parse_sql(query) {
+        // Transaction control writes nothing by itself
+        Ok(SqlStatement::Select { .. }) | Ok(SqlStatement::Transaction(_)) => Ok(()),
+        Ok(SqlStatement::Unsupported { reason, .. }) => Err(format!(
+            "statement could not be verified as read-only ({})",
+            reason
+        )),
+        Ok(_) => Err("only SELECT statements are allowed".to_string()),
+        Err(e) => Err(format!("statement could not be verified as read-only ({})", e)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -324,6 +409,40 @@ mod tests {
         // Read-only cannot mutate
         assert!(!policy.is_command_allowed("checkpoint"));
         assert!(!policy.is_command_allowed("mcp_call_tool"));
+        assert!(!policy.is_command_allowed("save_query"));
+        assert!(!policy.is_command_allowed("add_saved_connection"));
+        assert!(!policy.is_command_allowed("clear_query_history"));
+        assert!(policy.is_command_allowed("search_query_history"));
+    }
+
+    #[test]
+    fn test_read_only_tools() {
+        let none = serde_json::Value::Null;
+        assert!(check_read_only_tool("sochdb_get", &none).is_ok());
+        assert!(check_read_only_tool("sochdb_list_tables", &none).is_ok());
+        assert!(check_read_only_tool("sochdb_put", &none).is_err());
+        assert!(check_read_only_tool("sochdb_delete", &none).is_err());
+        // Tools not known to read are refused, whatever their name
+        assert!(check_read_only_tool("sochdb_batch_append", &none).is_err());
+        assert!(check_read_only_tool("sochdb_exec", &none).is_err());
+
+        let select = serde_json::json!({ "query": "SELECT * FROM users" });
+        let insert = serde_json::json!({ "query": "INSERT INTO users (id) VALUES (1)" });
+        assert!(check_read_only_tool("sochdb_query", &select).is_ok());
+        assert!(check_read_only_tool("sochdb_query", &insert).is_err());
+    }
+
+    #[test]
+    fn test_read_only_statements() {
+        assert!(check_read_only_statement("GET 'users/1'").is_ok());
+        assert!(check_read_only_statement("SCAN 'users/'").is_ok());
+        assert!(check_read_only_statement("PUT 'users/1' = {}").is_err());
+        assert!(check_read_only_statement("DELETE 'users/1'").is_err());
+        assert!(check_read_only_statement("DELETE FROM users WHERE id = 1").is_err());
+        assert!(check_read_only_statement("DROP TABLE users").is_err());
+        assert!(check_read_only_statement("not a statement").is_err());
+        assert!(check_read_only_statement("BEGIN").is_ok());
+        assert!(check_read_only_statement("ROLLBACK TO SAVEPOINT sp1").is_ok());
     }
 
     #[test]
@@ -0,0 +1,293 @@
+//! Query results as typed columns
+//!
+//! The engine answers a query with JSON text. Rows that are objects are
+//! split into columns: the `SELECT` list when it names them, otherwise the
+//! union of every row's keys with [`KEY_COLUMN`] first. A column's type is
+//! the one the table's schema declares; undeclared columns are typed from
+//! the returned values, falling back to the table's `analyze` statistics
+//! for columns that came back empty.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::analyze::{self, CollectionAnalysis};
+use crate::connection::pool::Session;
+use crate::planner::KEY_COLUMN;
+
+/// Column of rows that are not objects
+pub const RESULT_COLUMN: &str = "result";
+
+/// One column of a query result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnDescriptor {
+    pub name: String,
+    /// `number`, `string`, `boolean`, `array`, `object`, `mixed`, or `null`
+    /// if no row has a value and the table was never analyzed
+    pub data_type: String,
+    /// Some row lacks a value, or the table's statistics saw nulls
+    pub nullable: bool,
+}
+
+/// A column as the table's schema declares it
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeclaredColumn {
+    pub name: String,
+    /// The declared SQL type in [`ColumnDescriptor::data_type`] terms
+    pub data_type: String,
+    pub nullable: bool,
+}
+
+/// A response split into columns and rows
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultSet {
+    pub columns: Vec<ColumnDescriptor>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl ResultSet {
+    /// Split a `sochdb_query` response. `selected` is the statement's
+    /// `SELECT` list (empty for other statements).
+    pub fn parse(text: &str, selected: &[String]) -> Self {
+        let items = match serde_json::from_str::<Value>(text) {
+            Ok(Value::Array(items)) => items,
+            Ok(value) => vec![value],
+            Err(_) => vec![Value::String(text.to_string())],
+        };
+
+        let names = column_names(&items, selected);
+        let rows: Vec<Vec<Value>> = items
+            .into_iter()
+            .map(|item| match item {
+                Value::Object(mut object) => names
+                    .iter()
+                    .map(|name| object.remove(name).unwrap_or(Value::Null))
+                    .collect(),
+                item => names
+                    .iter()
+                    .map(|name| if name == RESULT_COLUMN { item.clone() } else { Value::Null })
+                    .collect(),
+            })
+            .collect();
+
+        let columns = names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let mut types: Vec<&str> = rows
+                    .iter()
+                    .map(|row| &row[i])
+                    .filter(|v| !v.is_null())
+                    .map(analyze::json_type)
+                    .collect();
+                types.sort_unstable();
+                types.dedup();
+                ColumnDescriptor {
+                    name,
+                    data_type: match types.as_slice() {
+                        [] => "null",
+                        [single] => *single,
+                        _ => "mixed",
+                    }
+                    .to_string(),
+                    nullable: rows.iter().any(|row| row[i].is_null()),
+                }
+            })
+            .collect();
+
+        Self { columns, rows }
+    }
+
+    /// Columns whose type is unknown from the rows alone
+    pub fn has_untyped_columns(&self) -> bool {
+        self.columns.iter().any(|c| c.data_type == "null")
+    }
+
+    /// Type columns the table's schema declares, overriding what the rows
+    /// suggest
+    pub fn apply_schema(&mut self, declared: &[DeclaredColumn]) {
+        for column in &mut self.columns {
+            if let Some(declared) = declared.iter().find(|d| d.name == column.name) {
+                column.data_type = declared.data_type.clone();
+                column.nullable |= declared.nullable;
+            }
+        }
+    }
+
+    /// Take types and nullability the rows could not show from `analysis`
+    pub fn apply_statistics(&mut self, analysis: &CollectionAnalysis) {
+        for column in &mut self.columns {
+            let Some(stats) = analysis.columns.iter().find(|s| s.name == column.name) else {
+                continue;
+            };
+            if column.data_type == "null" {
+                column.data_type = stats.data_type.clone();
+            }
+            column.nullable |= stats.null_fraction > 0.0;
+        }
+    }
+
+    pub fn column_names(&self) -> Vec<String> {
+        self.columns.iter().map(|c| c.name.clone()).collect()
+    }
+}
+
+/// Selected columns in order, with `*` expanded to the keys the rows carry
+pub fn column_names(items: &[Value], selected: &[String]) -> Vec<String> {
+    let all = selected.is_empty() || selected.iter().any(|c| c == "*");
+    let objects: Vec<&Map<String, Value>> = items.iter().filter_map(Value::as_object).collect();
+    if objects.is_empty() {
+        // An empty result still has the columns it selected
+        if items.is_empty() && !all {
+            return selected.to_vec();
+        }
+        return vec![RESULT_COLUMN.to_string()];
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    let mut push = |name: &str| {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    };
+    for column in selected {
+        if column == "*" {
+            break;
+        }
+        push(column);
+    }
+    if all {
+        if objects.iter().any(|o| o.contains_key(KEY_COLUMN)) {
+            push(KEY_COLUMN);
+        }
+        for object in &objects {
+            for key in object.keys() {
+                push(key);
+            }
+        }
+        for column in selected.iter().skip_while(|c| c.as_str() != "*").skip(1) {
+            push(column);
+        }
+    }
+    if objects.len() < items.len() {
+        push(RESULT_COLUMN);
+    }
+    names
+}
+
+/// Load a table's declared columns with `sochdb_describe`, or `None` if
+/// the table has no declared schema
+pub async fn load_schema(session: &Session, table: &str) -> Result<Option<Vec<DeclaredColumn>>, String> {
+    let result = session.call_tool("sochdb_describe", json!({ "table": table })).await?;
+    let text = result["content"][0]["text"].as_str().unwrap_or("null");
+    if result["isError"].as_bool().unwrap_or(false) {
+        return Err(format!("Failed to describe {}: {}", table, text));
+    }
+    Ok(parse_schema(text))
+}
+
+/// Columns of a `sochdb_describe` response: `{"columns": [...]}` or a bare
+/// list, each with a `name` and a `type` (or `data_type`)
+fn parse_schema(text: &str) -> Option<Vec<DeclaredColumn>> {
+    let value: Value = serde_json::from_str(text).ok()?;
+    let columns = value.get("columns").unwrap_or(&value).as_array()?;
+    let declared: Vec<DeclaredColumn> = columns
+        .iter()
+        .filter_map(|column| {
+            let name = column["name"].as_str()?;
+            let sql_type = column["type"].as_str().or_else(|| column["data_type"].as_str())?;
+            Some(DeclaredColumn {
+                name: name.to_string(),
+                data_type: declared_type(sql_type)?.to_string(),
+                nullable: column["nullable"].as_bool().unwrap_or(true),
+            })
+        })
+        .collect();
+    (!declared.is_empty()).then_some(declared)
+}
+
+/// The JSON type values of a declared SQL type are returned as
+fn declared_type(sql_type: &str) -> Option<&'static str> {
+    let base = sql_type.split('(').next().unwrap_or_default().trim().to_ascii_uppercase();
+    Some(match base.as_str() {
+        "INTEGER" | "INT" | "BIGINT" | "SMALLINT" | "FLOAT" | "DOUBLE" | "REAL" | "DECIMAL" | "NUMERIC" => "number",
+        "TEXT" | "VARCHAR" | "CHAR" | "STRING" | "TIMESTAMP" | "BLOB" => "string",
+        "BOOLEAN" | "BOOL" => "boolean",
+        "VECTOR" | "ARRAY" => "array",
+        "JSON" | "OBJECT" => "object",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyze::analyze_collection;
+
+    fn selected(columns: &[&str]) -> Vec<String> {
+        columns.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_select_list_order_and_types() {
+        let text = r#"[{"name": "Ada", "id": 1, "age": 36}, {"name": "Bob", "id": 2, "age": null}]"#;
+        let result = ResultSet::parse(text, &selected(&["name", "id", "email"]));
+        assert_eq!(result.column_names(), vec!["name", "id", "email"]);
+        assert_eq!(result.rows[0], vec![Value::from("Ada"), Value::from(1), Value::Null]);
+        assert_eq!(
+            result.columns[1],
+            ColumnDescriptor { name: "id".into(), data_type: "number".into(), nullable: false }
+        );
+        assert_eq!(result.columns[2].data_type, "null");
+        assert!(result.has_untyped_columns());
+
+        // The table's statistics type a column no row had a value for
+        let entries = vec![(b"users/1".to_vec(), br#"{"email": "ada@example.com"}"#.to_vec())];
+        let mut result = result;
+        result.apply_statistics(&analyze_collection("users", &entries, 10));
+        assert_eq!(result.columns[2].data_type, "string");
+        assert!(result.columns[2].nullable);
+    }
+
+    #[test]
+    fn test_declared_schema_wins() {
+        let text = r#"[{"id": 1, "score": "12", "email": null}]"#;
+        let mut result = ResultSet::parse(text, &selected(&["id", "score", "email", "note"]));
+        let describe = r#"{"columns": [
+            {"name": "score", "type": "INTEGER", "nullable": false},
+            {"name": "email", "type": "VARCHAR(255)", "nullable": true},
+            {"name": "note", "data_type": "GEOMETRY"}
+        ]}"#;
+        result.apply_schema(&parse_schema(describe).unwrap());
+        assert_eq!(result.columns[1].data_type, "number");
+        assert!(!result.columns[1].nullable);
+        assert_eq!(result.columns[2].data_type, "string");
+        // Types Studio cannot map are left to the rows and statistics
+        assert_eq!(result.columns[3].data_type, "null");
+
+        assert_eq!(parse_schema("null"), None);
+        assert_eq!(parse_schema(r#"[{"name": "id", "type": "BIGINT"}]"#).unwrap()[0].data_type, "number");
+    }
+
+    #[test]
+    fn test_star_unions_keys() {
+        let text = r#"[{"name": "Ada", "id": 1}, {"id": 2, "email": "bob@example.com", "tags": ["x"]}, 7]"#;
+        let result = ResultSet::parse(text, &selected(&["*"]));
+        assert_eq!(result.column_names(), vec!["id", "name", "email", "tags", "result"]);
+        assert_eq!(result.rows[1][2], Value::from("bob@example.com"));
+        assert_eq!(result.rows[2], vec![Value::Null, Value::Null, Value::Null, Value::Null, Value::from(7)]);
+        assert_eq!(result.columns[3].data_type, "array");
+        assert!(result.columns[1].nullable);
+
+        let unselected = ResultSet::parse(r#"[{"b": 1, "a": "x"}, {"a": 2}]"#, &[]);
+        assert_eq!(unselected.column_names(), vec!["a", "b"]);
+        assert_eq!(unselected.columns[0].data_type, "mixed");
+
+        let empty = ResultSet::parse("[]", &selected(&["name", "id"]));
+        assert_eq!(empty.column_names(), vec!["name", "id"]);
+        assert!(empty.rows.is_empty());
+
+        let scalar = ResultSet::parse("OK: 3 rows written", &[]);
+        assert_eq!(scalar.column_names(), vec!["result"]);
+        assert_eq!(scalar.rows, vec![vec![Value::from("OK: 3 rows written")]]);
+    }
+}
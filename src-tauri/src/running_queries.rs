@@ -0,0 +1,272 @@
+//! Queries in flight
+//!
+//! Every query runs through [`RunningQueries::run`], which lists it until it
+//! finishes and races it against cancellation and the session's statement
+//! timeout. Stopping a query drops its request: a remote server sees the
+//! call cancelled. An embedded engine cannot be interrupted, so it finishes
+//! the statement on a blocking thread; the query stays listed as
+//! `cancelling` until that thread returns.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::util::now_millis;
+
+/// A query that has not finished yet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunningQuery {
+    pub id: String,
+    pub query: String,
+    pub connection_id: String,
+    /// Unix timestamp (ms) the query started
+    pub started_at: u64,
+    pub elapsed_ms: f64,
+    /// Statement timeout the query runs under
+    pub timeout_ms: Option<u64>,
+    /// Stopped, but the engine is still finishing the statement
+    pub cancelling: bool,
+}
+
+struct Entry {
+    query: RunningQuery,
+    started: Instant,
+    cancel: Arc<Notify>,
+}
+
+/// Listed queries, shared with the registrations that keep them listed
+#[derive(Default)]
+struct Queries {
+    entries: Mutex<HashMap<String, Entry>>,
+    /// Signalled whenever a query comes off the list
+    removed: Notify,
+}
+
+/// Registry of running queries
+#[derive(Default)]
+pub struct RunningQueries {
+    queries: Arc<Queries>,
+    next_id: AtomicU64,
+}
+
+/// Keeps a query listed; the last holder to drop it takes it off the list
+pub struct Registration {
+    queries: Arc<Queries>,
+    id: String,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.queries.entries.lock().remove(&self.id);
+        self.queries.removed.notify_waiters();
+    }
+}
+
+tokio::task_local! {
+    /// The query whose operation is being polled
+    static CURRENT: Arc<Registration>;
+}
+
+/// The query being run, for blocking work that carries on after its caller
+/// stops waiting. Holding it keeps the query listed until the work returns.
+pub fn current() -> Option<Arc<Registration>> {
+    CURRENT.try_with(Arc::clone).ok()
+}
+
+impl RunningQueries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `operation` as a listed query, stopping it on [`cancel`](Self::cancel)
+    /// or once `timeout` elapses
+    pub async fn run<T, F>(&self, connection_id: &str, query: &str, timeout: Option<Duration>, operation: F) -> Result<T, String>
+    where
+        F: Future<Output = Result<T, String>>,
+    {
+        let id = format!("run-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        let cancel = Arc::new(Notify::new());
+        self.queries.entries.lock().insert(
+            id.clone(),
+            Entry {
+                query: RunningQuery {
+                    id: id.clone(),
+                    query: query.to_string(),
+                    connection_id: connection_id.to_string(),
+                    started_at: now_millis(),
+                    elapsed_ms: 0.0,
+                    timeout_ms: timeout.map(|t| t.as_millis() as u64),
+                    cancelling: false,
+                },
+                started: Instant::now(),
+                cancel: cancel.clone(),
+            },
+        );
+        let registration = Arc::new(Registration {
+            queries: self.queries.clone(),
+            id: id.clone(),
+        });
+
+        let deadline = async {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        let stopped = tokio::select! {
+            result = CURRENT.scope(registration.clone(), operation) => return result,
+            _ = cancel.notified() => Err("Query cancelled".to_string()),
+            _ = deadline => Err(format!(
+                "Query cancelled: exceeded the statement timeout of {} ms",
+                timeout.unwrap_or_default().as_millis()
+            )),
+        };
+        // Listed until work still holding the registration returns
+        if let Some(entry) = self.queries.entries.lock().get_mut(&id) {
+            entry.query.cancelling = true;
+        }
+        stopped
+    }
+
+    /// Running queries, oldest first
+    pub fn list(&self) -> Vec<RunningQuery> {
+        let mut queries: Vec<RunningQuery> = self
+            .queries
+            .entries
+            .lock()
+            .values()
+            .map(|entry| RunningQuery {
+                elapsed_ms: entry.started.elapsed().as_secs_f64() * 1000.0,
+                ..entry.query.clone()
+            })
+            .collect();
+        queries.sort_by(|a, b| b.elapsed_ms.total_cmp(&a.elapsed_ms));
+        queries
+    }
+
+    /// Wait up to `timeout` for stopped queries on `connection_id` that the
+    /// engine is still finishing. Returns `false` if one is still running.
+    pub async fn settle(&self, connection_id: &str, timeout: Duration) -> bool {
+        let settled = async {
+            loop {
+                let removed = self.queries.removed.notified();
+                tokio::pin!(removed);
+                // Registered before checking, so a removal in between is not missed
+                removed.as_mut().enable();
+                let busy = self
+                    .queries
+                    .entries
+                    .lock()
+                    .values()
+                    .any(|entry| entry.query.cancelling && entry.query.connection_id == connection_id);
+                if !busy {
+                    return;
+                }
+                removed.await;
+            }
+        };
+        tokio::time::timeout(timeout, settled).await.is_ok()
+    }
+
+    /// Stop a running query; its caller gets a "Query cancelled" error
+    pub fn cancel(&self, id: &str) -> Result<RunningQuery, String> {
+        let queries = self.queries.entries.lock();
+        let entry = queries
+            .get(id)
+            .ok_or_else(|| format!("Query {} is not running", id))?;
+        // Stores a permit, so a query that has not polled yet still stops
+        entry.cancel.notify_one();
+        Ok(RunningQuery {
+            elapsed_ms: entry.started.elapsed().as_secs_f64() * 1000.0,
+            ..entry.query.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_and_list() {
+        let registry = Arc::new(RunningQueries::new());
+        let result = registry.run("main", "SELECT 1", None, async { Ok(1) }).await;
+        assert_eq!(result, Ok(1));
+        assert!(registry.list().is_empty());
+
+        let (started, wait) = tokio::sync::oneshot::channel::<()>();
+        let running = registry.clone();
+        let task = tokio::spawn(async move {
+            running
+                .run("main", "SELECT * FROM huge", Some(Duration::from_secs(60)), async {
+                    let _ = started.send(());
+                    std::future::pending::<Result<(), String>>().await
+                })
+                .await
+        });
+        wait.await.unwrap();
+
+        let listed = registry.list();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].query, "SELECT * FROM huge");
+        assert_eq!(listed[0].timeout_ms, Some(60_000));
+
+        registry.cancel(&listed[0].id).unwrap();
+        assert_eq!(task.await.unwrap(), Err("Query cancelled".to_string()));
+        assert!(registry.list().is_empty());
+        assert!(registry.cancel(&listed[0].id).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_until_blocking_work_returns() {
+        let registry = Arc::new(RunningQueries::new());
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let (started, wait) = tokio::sync::oneshot::channel::<()>();
+        let running = registry.clone();
+        let task = tokio::spawn(async move {
+            running
+                .run("main", "SELECT * FROM huge", None, async {
+                    let held = current().expect("run sets the current query");
+                    tokio::task::spawn_blocking(move || {
+                        let _held = held;
+                        let _ = released.recv();
+                    });
+                    let _ = started.send(());
+                    std::future::pending::<Result<(), String>>().await
+                })
+                .await
+        });
+        wait.await.unwrap();
+        assert!(current().is_none());
+
+        let id = registry.list()[0].id.clone();
+        registry.cancel(&id).unwrap();
+        assert_eq!(task.await.unwrap(), Err("Query cancelled".to_string()));
+        let listed = registry.list();
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0].cancelling);
+        assert!(registry.settle("replica", Duration::ZERO).await);
+        assert!(!registry.settle("main", Duration::from_millis(20)).await);
+
+        release.send(()).unwrap();
+        assert!(registry.settle("main", Duration::from_secs(5)).await);
+        assert!(registry.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_statement_timeout() {
+        let registry = RunningQueries::new();
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok(())
+        };
+        let error = registry.run("main", "SELECT 1", Some(Duration::from_millis(50)), slow).await.unwrap_err();
+        assert!(error.contains("50 ms"), "{}", error);
+        assert!(registry.list().is_empty());
+    }
+}
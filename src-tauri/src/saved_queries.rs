@@ -0,0 +1,352 @@
+//! Saved query library
+//!
+//! Named queries organised in folders, with descriptions, tags and
+//! `$name`/`$1`/`?` placeholders bound when the query is run. The library
+//! exports to and imports from a JSON file so it can be shared.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::parser::sql;
+
+/// Version written to exported library files
+pub const LIBRARY_VERSION: u32 = 1;
+
+/// A named query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedQuery {
+    pub id: String,
+    pub name: String,
+    /// Slash-separated folder path (`null` for the top level)
+    pub folder: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub query: String,
+    /// Placeholder names, in order of first appearance
+    #[serde(default)]
+    pub parameters: Vec<String>,
+    /// Unix timestamps (ms)
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// Create (no `id`) or update a saved query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedQueryRequest {
+    pub id: Option<String>,
+    pub name: String,
+    pub folder: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub query: String,
+}
+
+/// Exported library file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryFile {
+    pub version: u32,
+    pub queries: Vec<SavedQuery>,
+}
+
+/// Outcome of importing a library file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub added: usize,
+    /// Queries that replaced one with the same folder and name
+    pub updated: usize,
+}
+
+/// All saved queries
+#[derive(Debug, Default, Clone)]
+pub struct QueryLibrary {
+    queries: Vec<SavedQuery>,
+    /// Bumped on every change, so a copy saved after the lock is released
+    /// can be told apart from a newer one
+    revision: u64,
+}
+
+impl QueryLibrary {
+    pub fn new(queries: Vec<SavedQuery>) -> Self {
+        Self { queries, revision: 0 }
+    }
+
+    /// Number of changes since the library was loaded
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Saved queries ordered by folder then name, optionally only those in
+    /// `folder` (including subfolders) or carrying `tag`
+    pub fn list(&self, folder: Option<&str>, tag: Option<&str>) -> Vec<SavedQuery> {
+        let folder = folder.map(normalize_folder);
+        let mut queries: Vec<SavedQuery> = self
+            .queries
+            .iter()
+            .filter(|q| {
+                folder.as_ref().is_none_or(|f| {
+                    q.folder.as_deref().is_some_and(|own| own == f || own.starts_with(&format!("{}/", f)))
+                })
+            })
+            .filter(|q| tag.is_none_or(|t| q.tags.iter().any(|own| own.eq_ignore_ascii_case(t))))
+            .cloned()
+            .collect();
+        queries.sort_by(|a, b| (&a.folder, a.name.to_lowercase()).cmp(&(&b.folder, b.name.to_lowercase())));
+        queries
+    }
+
+    pub fn get(&self, id: &str) -> Result<&SavedQuery, String> {
+        self.queries
+            .iter()
+            .find(|q| q.id == id)
+            .ok_or_else(|| format!("Unknown saved query: {}", id))
+    }
+
+    /// Create or update a saved query. Names are unique within a folder.
+    pub fn save(&mut self, request: SavedQueryRequest, now: u64) -> Result<SavedQuery, String> {
+        let name = request.name.trim().to_string();
+        if name.is_empty() {
+            return Err("Saved query name cannot be empty".to_string());
+        }
+        if request.query.trim().is_empty() {
+            return Err("Saved query cannot be empty".to_string());
+        }
+        let folder = request.folder.as_deref().map(normalize_folder).filter(|f| !f.is_empty());
+        if let Some(existing) = self.find_by_name(folder.as_deref(), &name) {
+            if request.id.as_ref() != Some(&existing.id) {
+                return Err(format!("A saved query named '{}' already exists in this folder", name));
+            }
+        }
+
+        let mut tags: Vec<String> = Vec::new();
+        for tag in request.tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            if !tags.iter().any(|own| own.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.to_string());
+            }
+        }
+        let parameters = sql::parameter_names(&request.query);
+
+        if let Some(id) = &request.id {
+            let saved = self
+                .queries
+                .iter_mut()
+                .find(|q| &q.id == id)
+                .ok_or_else(|| format!("Unknown saved query: {}", id))?;
+            saved.name = name;
+            saved.folder = folder;
+            saved.description = request.description;
+            saved.tags = tags;
+            saved.query = request.query;
+            saved.parameters = parameters;
+            saved.updated_at = now;
+            let saved = saved.clone();
+            self.revision += 1;
+            return Ok(saved);
+        }
+
+        let saved = SavedQuery {
+            id: self.next_id(now),
+            name,
+            folder,
+            description: request.description,
+            tags,
+            query: request.query,
+            parameters,
+            created_at: now,
+            updated_at: now,
+        };
+        self.queries.push(saved.clone());
+        self.revision += 1;
+        Ok(saved)
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<(), String> {
+        let before = self.queries.len();
+        self.queries.retain(|q| q.id != id);
+        if self.queries.len() == before {
+            return Err(format!("Unknown saved query: {}", id));
+        }
+        self.revision += 1;
+        Ok(())
+    }
+
+    /// The query text with `params` bound to its placeholders
+    pub fn bind(&self, id: &str, params: &HashMap<String, serde_json::Value>) -> Result<String, String> {
+        sql::bind_parameters(&self.get(id)?.query, params)
+    }
+
+    pub fn export(&self) -> LibraryFile {
+        LibraryFile {
+            version: LIBRARY_VERSION,
+            queries: self.list(None, None),
+        }
+    }
+
+    /// Merge an exported library. A query with the same folder and name as
+    /// an existing one replaces its text, description and tags.
+    ///
+    /// Either every query is merged or, if one is invalid, none is.
+    pub fn import(&mut self, file: LibraryFile, now: u64) -> Result<ImportSummary, String> {
+        if file.version > LIBRARY_VERSION {
+            return Err(format!(
+                "Library file version {} is newer than this Studio supports ({})",
+                file.version, LIBRARY_VERSION
+            ));
+        }
+
+        let mut merged = self.clone();
+        let mut summary = ImportSummary::default();
+        for query in file.queries {
+            let folder = query.folder.as_deref().map(normalize_folder).filter(|f| !f.is_empty());
+            let id = merged.find_by_name(folder.as_deref(), query.name.trim()).map(|q| q.id.clone());
+            let updating = id.is_some();
+            merged.save(
+                SavedQueryRequest {
+                    id,
+                    name: query.name,
+                    folder,
+                    description: query.description,
+                    tags: query.tags,
+                    query: query.query,
+                },
+                now,
+            )?;
+            if updating {
+                summary.updated += 1;
+            } else {
+                summary.added += 1;
+            }
+        }
+        *self = merged;
+        Ok(summary)
+    }
+
+    fn find_by_name(&self, folder: Option<&str>, name: &str) -> Option<&SavedQuery> {
+        self.queries
+            .iter()
+            .find(|q| q.folder.as_deref() == folder && q.name.eq_ignore_ascii_case(name))
+    }
+
+    fn next_id(&self, now: u64) -> String {
+        let base = format!("saved-{}", now);
+        let mut id = base.clone();
+        let mut n = 1;
+        while self.queries.iter().any(|q| q.id == id) {
+            id = format!("{}-{}", base, n);
+            n += 1;
+        }
+        id
+    }
+}
+
+/// Trim and drop empty segments: ` /reports//daily/ ` becomes `reports/daily`
+fn normalize_folder(folder: &str) -> String {
+    folder
+        .split('/')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(name: &str, folder: Option<&str>, query: &str) -> SavedQueryRequest {
+        SavedQueryRequest {
+            id: None,
+            name: name.to_string(),
+            folder: folder.map(str::to_string),
+            description: None,
+            tags: vec!["Ops".to_string(), "ops".to_string(), " ".to_string()],
+            query: query.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_save_and_list() {
+        let mut library = QueryLibrary::default();
+        let recent = library
+            .save(request("Recent orders", Some(" reports//orders/ "), "SELECT * FROM orders WHERE day > $since"), 1)
+            .unwrap();
+        assert_eq!(recent.folder.as_deref(), Some("reports/orders"));
+        assert_eq!(recent.tags, vec!["Ops"]);
+        assert_eq!(recent.parameters, vec!["since"]);
+        library.save(request("users", None, "SELECT * FROM users"), 2).unwrap();
+        library.save(request("By id", Some("reports"), "SELECT * FROM users WHERE id = ?"), 3).unwrap();
+
+        let names = |queries: Vec<SavedQuery>| queries.into_iter().map(|q| q.name).collect::<Vec<_>>();
+        assert_eq!(names(library.list(None, None)), vec!["users", "By id", "Recent orders"]);
+        assert_eq!(names(library.list(Some("reports"), None)), vec!["By id", "Recent orders"]);
+        assert_eq!(names(library.list(Some("reports/orders"), Some("OPS"))), vec!["Recent orders"]);
+
+        // Names are unique per folder
+        assert!(library.save(request("recent ORDERS", Some("reports/orders"), "SELECT 1"), 4).is_err());
+        let renamed = SavedQueryRequest { id: Some(recent.id.clone()), ..request("Recent orders", Some("reports/orders"), "SELECT $1") };
+        let updated = library.save(renamed, 5).unwrap();
+        assert_eq!((updated.created_at, updated.updated_at), (1, 5));
+        assert_eq!(updated.parameters, vec!["1"]);
+
+        library.remove(&recent.id).unwrap();
+        assert!(library.get(&recent.id).is_err());
+    }
+
+    #[test]
+    fn test_bind() {
+        let mut library = QueryLibrary::default();
+        let saved = library
+            .save(request("By name", None, "SELECT * FROM users WHERE name = $name LIMIT ?"), 1)
+            .unwrap();
+        let params = [
+            ("name".to_string(), serde_json::json!("ada")),
+            ("1".to_string(), serde_json::json!(10)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(library.bind(&saved.id, &params).unwrap(), "SELECT * FROM users WHERE name = 'ada' LIMIT 10");
+        assert!(library.bind(&saved.id, &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_export_import() {
+        let mut team = QueryLibrary::default();
+        team.save(request("users", Some("shared"), "SELECT * FROM users"), 1).unwrap();
+        team.save(request("orders", Some("shared"), "SELECT * FROM orders"), 2).unwrap();
+        let file: LibraryFile = serde_json::from_str(&serde_json::to_string(&team.export()).unwrap()).unwrap();
+
+        let mut mine = QueryLibrary::default();
+        mine.save(request("Users", Some("shared"), "SELECT id FROM users"), 3).unwrap();
+        let summary = mine.import(file, 4).unwrap();
+        assert_eq!(summary, ImportSummary { added: 1, updated: 1 });
+        assert_eq!(mine.list(None, None).len(), 2);
+        assert_eq!(mine.list(None, None)[1].query, "SELECT * FROM users");
+
+        let future = LibraryFile { version: LIBRARY_VERSION + 1, queries: Vec::new() };
+        let revision = mine.revision();
+        assert!(mine.import(future, 5).is_err());
+        assert_eq!(mine.revision(), revision);
+    }
+
+    #[test]
+    fn test_import_is_all_or_nothing() {
+        let mut library = QueryLibrary::default();
+        library.save(request("users", None, "SELECT id FROM users"), 1).unwrap();
+        let before = library.list(None, None);
+
+        let mut source = QueryLibrary::default();
+        source.save(request("users", None, "SELECT * FROM users"), 2).unwrap();
+        source.save(request("orders", None, "SELECT * FROM orders"), 3).unwrap();
+        let mut queries = source.list(None, None);
+        let mut bad = queries[0].clone();
+        bad.name = "  ".to_string();
+        queries.insert(1, bad);
+        let file = LibraryFile { version: LIBRARY_VERSION, queries };
+
+        let revision = library.revision();
+        assert!(library.import(file, 4).is_err());
+        assert_eq!(library.list(None, None), before);
+        assert_eq!(library.revision(), revision);
+    }
+}
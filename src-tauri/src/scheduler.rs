@@ -0,0 +1,432 @@
+//! Scheduled maintenance
+//!
+//! Each connection can have maintenance schedules: run a task every N
+//! minutes, once a day, or whenever the WAL grows past a threshold. While
+//! Studio is open, a background loop checks the schedules of every open
+//! session every [`SCHEDULER_TICK`], submits due tasks as jobs, and records
+//! each run in a history log.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::jobs::{Job, JobKind, JobStatus};
+use crate::maintenance::MaintenanceTask;
+
+/// Time between schedule checks
+pub const SCHEDULER_TICK: Duration = Duration::from_secs(30);
+
+/// Runs kept in the history log
+const MAX_HISTORY: usize = 500;
+
+const MINUTE_MS: u64 = 60 * 1000;
+const DAY_MS: u64 = 24 * 60 * MINUTE_MS;
+
+/// Minimum time between runs of a WAL-size schedule, so a task that does
+/// not shrink the WAL is not resubmitted every tick
+const WAL_TRIGGER_COOLDOWN_MS: u64 = 15 * MINUTE_MS;
+
+/// When a schedule fires
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScheduleTrigger {
+    /// Every `minutes` minutes
+    Interval { minutes: u64 },
+    /// Once a day at `hour:minute` UTC
+    Daily { hour: u8, minute: u8 },
+    /// Whenever the WAL is larger than `threshold_mb` megabytes, at most
+    /// once every 15 minutes
+    WalSize { threshold_mb: u64 },
+}
+
+impl ScheduleTrigger {
+    fn validate(&self) -> Result<(), String> {
+        match *self {
+            ScheduleTrigger::Interval { minutes: 0 } => Err("Interval must be at least one minute".to_string()),
+            ScheduleTrigger::Daily { hour, minute } if hour > 23 || minute > 59 => {
+                Err(format!("Invalid time of day: {:02}:{:02}", hour, minute))
+            }
+            ScheduleTrigger::WalSize { threshold_mb: 0 } => Err("WAL threshold must be at least 1 MB".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the trigger needs the connection's WAL size
+    pub fn needs_wal_size(&self) -> bool {
+        matches!(self, ScheduleTrigger::WalSize { .. })
+    }
+}
+
+/// A maintenance task run on a schedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceSchedule {
+    pub id: String,
+    /// Connection the task runs against; it only runs while that connection is open
+    pub connection_id: String,
+    pub task: MaintenanceTask,
+    pub trigger: ScheduleTrigger,
+    pub enabled: bool,
+    /// Unix timestamps (ms)
+    pub created_at: u64,
+    pub last_run_at: Option<u64>,
+}
+
+impl MaintenanceSchedule {
+    /// Whether the schedule should fire at `now`.
+    ///
+    /// `wal_size_bytes` is the connection's WAL size, if known; WAL-size
+    /// triggers never fire without it.
+    pub fn is_due(&self, now: u64, wal_size_bytes: Option<u64>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let reference = self.last_run_at.unwrap_or(self.created_at);
+        match self.trigger {
+            ScheduleTrigger::Interval { minutes } => {
+                now >= reference.saturating_add(minutes.saturating_mul(MINUTE_MS))
+            }
+            ScheduleTrigger::Daily { hour, minute } => {
+                let offset = (hour as u64 * 60 + minute as u64) * MINUTE_MS;
+                let mut latest = now - now % DAY_MS + offset;
+                if latest > now {
+                    latest = latest.saturating_sub(DAY_MS);
+                }
+                reference < latest
+            }
+            ScheduleTrigger::WalSize { threshold_mb } => {
+                let cooled_down = self
+                    .last_run_at
+                    .is_none_or(|last| now >= last.saturating_add(WAL_TRIGGER_COOLDOWN_MS));
+                cooled_down && wal_size_bytes.is_some_and(|size| size > threshold_mb.saturating_mul(1024 * 1024))
+            }
+        }
+    }
+}
+
+/// Fields a user can set on a schedule
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleRequest {
+    /// Existing schedule to update; a new one is created if `None`
+    #[serde(default)]
+    pub id: Option<String>,
+    pub connection_id: String,
+    pub task: MaintenanceTask,
+    pub trigger: ScheduleTrigger,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// One run of a schedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledRun {
+    pub schedule_id: String,
+    pub connection_id: String,
+    pub kind: JobKind,
+    /// Job the run was submitted as (`None` if submission failed)
+    pub job_id: Option<String>,
+    pub status: JobStatus,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+    /// Unix timestamps (ms)
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+}
+
+/// Maintenance schedules and their run history
+#[derive(Debug, Default, Clone)]
+pub struct Scheduler {
+    schedules: Vec<MaintenanceSchedule>,
+    history: VecDeque<ScheduledRun>,
+    /// Bumped on every change, so a copy saved after the lock is released
+    /// can be told apart from a newer one
+    revision: u64,
+}
+
+impl Scheduler {
+    pub fn new(schedules: Vec<MaintenanceSchedule>, history: Vec<ScheduledRun>) -> Self {
+        let mut scheduler = Self {
+            schedules,
+            history: history.into(),
+            revision: 0,
+        };
+        scheduler.prune();
+        scheduler
+    }
+
+    /// Schedules, optionally only those for one connection
+    pub fn schedules(&self, connection_id: Option<&str>) -> Vec<MaintenanceSchedule> {
+        self.schedules
+            .iter()
+            .filter(|s| connection_id.is_none_or(|id| s.connection_id == id))
+            .cloned()
+            .collect()
+    }
+
+    /// Number of changes since the scheduler was loaded
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Create a schedule, or update the one `request.id` names
+    pub fn save(&mut self, request: ScheduleRequest, now: u64) -> Result<MaintenanceSchedule, String> {
+        request.trigger.validate()?;
+
+        if let Some(id) = &request.id {
+            let schedule = self
+                .schedules
+                .iter_mut()
+                .find(|s| &s.id == id)
+                .ok_or_else(|| format!("Unknown schedule: {}", id))?;
+            schedule.connection_id = request.connection_id;
+            schedule.task = request.task;
+            schedule.trigger = request.trigger;
+            schedule.enabled = request.enabled;
+            let schedule = schedule.clone();
+            self.revision += 1;
+            return Ok(schedule);
+        }
+
+        let schedule = MaintenanceSchedule {
+            id: self.next_id(now),
+            connection_id: request.connection_id,
+            task: request.task,
+            trigger: request.trigger,
+            enabled: request.enabled,
+            created_at: now,
+            last_run_at: None,
+        };
+        self.schedules.push(schedule.clone());
+        self.revision += 1;
+        Ok(schedule)
+    }
+
+    /// Delete a schedule. Its past runs stay in the history.
+    pub fn remove(&mut self, id: &str) -> Result<(), String> {
+        let before = self.schedules.len();
+        self.schedules.retain(|s| s.id != id);
+        if self.schedules.len() == before {
+            return Err(format!("Unknown schedule: {}", id));
+        }
+        self.revision += 1;
+        Ok(())
+    }
+
+    /// Past runs, newest first, optionally only those for one connection
+    pub fn history(&self, connection_id: Option<&str>, limit: Option<usize>) -> Vec<ScheduledRun> {
+        self.history
+            .iter()
+            .rev()
+            .filter(|r| connection_id.is_none_or(|id| r.connection_id == id))
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
+    /// Whether the schedule's previous run is still going
+    pub fn is_running(&self, schedule_id: &str) -> bool {
+        self.history
+            .iter()
+            .any(|r| r.schedule_id == schedule_id && !r.status.is_finished())
+    }
+
+    /// Record that a schedule fired, with the job it was submitted as
+    pub fn record_run(&mut self, schedule_id: &str, submitted: Result<Job, String>, now: u64) {
+        let Some(schedule) = self.schedules.iter_mut().find(|s| s.id == schedule_id) else {
+            return;
+        };
+        schedule.last_run_at = Some(now);
+
+        let mut run = ScheduledRun {
+            schedule_id: schedule.id.clone(),
+            connection_id: schedule.connection_id.clone(),
+            kind: schedule.task.kind(),
+            job_id: None,
+            status: JobStatus::Running,
+            result: None,
+            error: None,
+            started_at: now,
+            finished_at: None,
+        };
+        match submitted {
+            Ok(job) => run.job_id = Some(job.id),
+            Err(e) => {
+                run.status = JobStatus::Failed;
+                run.error = Some(e);
+                run.finished_at = Some(now);
+            }
+        }
+        self.history.push_back(run);
+        self.prune();
+        self.revision += 1;
+    }
+
+    /// Copy the outcome of finished jobs into their runs.
+    ///
+    /// Returns whether any run changed.
+    pub fn sync_jobs(&mut self, job: impl Fn(&str) -> Option<Job>) -> bool {
+        let mut changed = false;
+        for run in self.history.iter_mut().filter(|r| !r.status.is_finished()) {
+            let Some(job_id) = &run.job_id else {
+                continue;
+            };
+            match job(job_id) {
+                Some(job) if job.status.is_finished() => {
+                    run.status = job.status;
+                    run.result = job.result;
+                    run.error = job.error;
+                    run.finished_at = job.finished_at;
+                }
+                Some(_) => continue,
+                // Studio was closed while the job ran, or the job has
+                // dropped out of the job history
+                None => {
+                    run.status = JobStatus::Failed;
+                    run.error = Some("The job's outcome is no longer known".to_string());
+                }
+            }
+            changed = true;
+        }
+        if changed {
+            self.revision += 1;
+        }
+        changed
+    }
+
+    fn next_id(&self, now: u64) -> String {
+        let base = format!("sched-{}", now);
+        let mut id = base.clone();
+        let mut n = 1;
+        while self.schedules.iter().any(|s| s.id == id) {
+            id = format!("{}-{}", base, n);
+            n += 1;
+        }
+        id
+    }
+
+    fn prune(&mut self) {
+        while self.history.len() > MAX_HISTORY {
+            self.history.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(trigger: ScheduleTrigger, created_at: u64) -> MaintenanceSchedule {
+        MaintenanceSchedule {
+            id: "s".to_string(),
+            connection_id: "db".to_string(),
+            task: MaintenanceTask::Gc,
+            trigger,
+            enabled: true,
+            created_at,
+            last_run_at: None,
+        }
+    }
+
+    fn job(id: &str, status: JobStatus) -> Job {
+        Job {
+            id: id.to_string(),
+            kind: JobKind::Gc,
+            connection_id: "db".to_string(),
+            status,
+            progress: None,
+            message: None,
+            cancel_requested: false,
+            result: None,
+            error: None,
+            started_at: 0,
+            finished_at: status.is_finished().then_some(1),
+        }
+    }
+
+    #[test]
+    fn test_triggers() {
+        let mut hourly = schedule(ScheduleTrigger::Interval { minutes: 60 }, 0);
+        assert!(!hourly.is_due(59 * MINUTE_MS, None));
+        assert!(hourly.is_due(60 * MINUTE_MS, None));
+        hourly.last_run_at = Some(60 * MINUTE_MS);
+        assert!(!hourly.is_due(90 * MINUTE_MS, None));
+        hourly.enabled = false;
+        assert!(!hourly.is_due(DAY_MS, None));
+
+        // Created at 01:00 on day 0; due from 03:30 onwards, once per day
+        let mut nightly = schedule(ScheduleTrigger::Daily { hour: 3, minute: 30 }, 60 * MINUTE_MS);
+        assert!(!nightly.is_due(3 * 60 * MINUTE_MS, None));
+        assert!(nightly.is_due(210 * MINUTE_MS, None));
+        nightly.last_run_at = Some(211 * MINUTE_MS);
+        assert!(!nightly.is_due(DAY_MS + 200 * MINUTE_MS, None));
+        assert!(nightly.is_due(DAY_MS + 210 * MINUTE_MS, None));
+
+        let mut wal = schedule(ScheduleTrigger::WalSize { threshold_mb: 64 }, 0);
+        assert!(!wal.is_due(0, None));
+        assert!(!wal.is_due(0, Some(64 * 1024 * 1024)));
+        assert!(wal.is_due(0, Some(64 * 1024 * 1024 + 1)));
+        // A WAL that stays large does not refire the schedule every tick
+        wal.last_run_at = Some(MINUTE_MS);
+        assert!(!wal.is_due(2 * MINUTE_MS, Some(128 * 1024 * 1024)));
+        assert!(wal.is_due(16 * MINUTE_MS, Some(128 * 1024 * 1024)));
+    }
+
+    #[test]
+    fn test_save_validates_and_updates() {
+        let mut scheduler = Scheduler::default();
+        let request = |id: Option<String>, trigger| ScheduleRequest {
+            id,
+            connection_id: "db".to_string(),
+            task: MaintenanceTask::Checkpoint,
+            trigger,
+            enabled: true,
+        };
+
+        assert!(scheduler.save(request(None, ScheduleTrigger::Daily { hour: 24, minute: 0 }), 0).is_err());
+        let created = scheduler.save(request(None, ScheduleTrigger::Interval { minutes: 5 }), 10).unwrap();
+        let second = scheduler.save(request(None, ScheduleTrigger::Interval { minutes: 5 }), 10).unwrap();
+        assert_ne!(created.id, second.id);
+
+        let updated = scheduler
+            .save(request(Some(created.id.clone()), ScheduleTrigger::WalSize { threshold_mb: 8 }), 20)
+            .unwrap();
+        assert_eq!(updated.created_at, 10);
+        assert_eq!(updated.trigger, ScheduleTrigger::WalSize { threshold_mb: 8 });
+        assert!(scheduler.save(request(Some("missing".to_string()), ScheduleTrigger::Interval { minutes: 1 }), 0).is_err());
+
+        let revision = scheduler.revision();
+        scheduler.remove(&created.id).unwrap();
+        assert_eq!(scheduler.schedules(Some("db")).len(), 1);
+        assert!(scheduler.revision() > revision);
+    }
+
+    #[test]
+    fn test_history_tracks_job_outcome() {
+        let mut scheduler = Scheduler::new(vec![schedule(ScheduleTrigger::Interval { minutes: 1 }, 0)], vec![]);
+
+        scheduler.record_run("s", Ok(job("job-1", JobStatus::Running)), 100);
+        assert!(scheduler.is_running("s"));
+        assert_eq!(scheduler.schedules(None)[0].last_run_at, Some(100));
+
+        assert!(!scheduler.sync_jobs(|id| Some(job(id, JobStatus::Running))));
+        assert!(scheduler.sync_jobs(|id| Some(job(id, JobStatus::Succeeded))));
+        assert!(!scheduler.is_running("s"));
+
+        // A failed submission is recorded as a finished, failed run
+        scheduler.record_run("s", Err("read-only".to_string()), 200);
+        let history = scheduler.history(Some("db"), None);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].status, JobStatus::Failed);
+        assert_eq!(history[1].status, JobStatus::Succeeded);
+        assert_eq!(scheduler.history(None, Some(1)).len(), 1);
+
+        for i in 0..MAX_HISTORY {
+            scheduler.record_run("s", Err("x".to_string()), i as u64);
+        }
+        assert_eq!(scheduler.history(None, None).len(), MAX_HISTORY);
+    }
+}
@@ -0,0 +1,339 @@
+//! Multi-statement scripts
+//!
+//! A script is split on `;` and its statements run one after another on a
+//! single session. Transaction control (`BEGIN`, `COMMIT`, `ROLLBACK`,
+//! `SAVEPOINT`, `RELEASE`) is sent to the engine like any other statement;
+//! Studio follows it to refuse unbalanced scripts before anything runs and
+//! to roll back whatever is still open when a statement fails. A script
+//! with no `BEGIN` of its own runs inside one transaction Studio opens and
+//! commits, so a migration is applied entirely or not at all.
+//!
+//! Callers hold the connection for the whole script, so other queries
+//! cannot land inside its transaction.
+
+use std::future::Future;
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+
+use crate::parser::sql::{self, SqlStatement, TransactionControl};
+
+/// Outcome of one statement of a script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementStatus {
+    Succeeded,
+    Failed,
+    /// Not run because an earlier statement failed
+    Skipped,
+}
+
+/// One statement of a script and what running it returned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptStatement<T> {
+    /// Position in the script, from 0
+    pub index: usize,
+    pub statement: String,
+    pub status: StatementStatus,
+    pub result: Option<T>,
+    pub error: Option<String>,
+    pub execution_time_ms: f64,
+}
+
+/// Result of running a script
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptResult<T> {
+    pub statements: Vec<ScriptStatement<T>>,
+    /// Studio wrapped the script in a transaction of its own
+    pub implicit_transaction: bool,
+    /// A failed statement rolled back the transaction it ran in
+    pub rolled_back: bool,
+    /// Error from a `BEGIN`, `COMMIT` or `ROLLBACK` Studio issued itself
+    pub transaction_error: Option<String>,
+    pub execution_time_ms: f64,
+}
+
+impl<T> ScriptResult<T> {
+    /// The first statement error, if any statement failed
+    pub fn first_error(&self) -> Option<&str> {
+        self.statements.iter().find_map(|s| s.error.as_deref())
+    }
+}
+
+/// A script split into statements, checked for balanced transactions
+#[derive(Debug, Clone)]
+pub struct Script {
+    statements: Vec<(String, Option<TransactionControl>)>,
+    implicit_transaction: bool,
+}
+
+/// Transaction a script has open
+#[derive(Debug, Default)]
+struct OpenTransaction {
+    open: bool,
+    savepoints: Vec<String>,
+}
+
+impl OpenTransaction {
+    /// Follow `control`, or say why it cannot run here
+    fn apply(&mut self, control: &TransactionControl, implicit: bool) -> Result<(), String> {
+        match control {
+            TransactionControl::Begin if self.open => Err("BEGIN inside a transaction".to_string()),
+            TransactionControl::Begin => {
+                self.open = true;
+                Ok(())
+            }
+            TransactionControl::Commit | TransactionControl::Rollback { savepoint: None } if implicit => Err(
+                "COMMIT and ROLLBACK need a BEGIN; without one the script runs in a single transaction".to_string(),
+            ),
+            TransactionControl::Commit | TransactionControl::Rollback { savepoint: None } if !self.open => {
+                Err("no transaction is open".to_string())
+            }
+            TransactionControl::Commit | TransactionControl::Rollback { savepoint: None } => {
+                *self = Self::default();
+                Ok(())
+            }
+            TransactionControl::Savepoint { .. } | TransactionControl::Rollback { .. } | TransactionControl::Release { .. }
+                if !self.open =>
+            {
+                Err("savepoints need an open transaction".to_string())
+            }
+            TransactionControl::Savepoint { name } => {
+                self.savepoints.push(name.clone());
+                Ok(())
+            }
+            TransactionControl::Rollback { savepoint: Some(name) } | TransactionControl::Release { name } => {
+                let position = self
+                    .savepoints
+                    .iter()
+                    .rposition(|s| s == name)
+                    .ok_or_else(|| format!("no savepoint named '{}'", name))?;
+                // Rolling back keeps the savepoint; releasing drops it too
+                let keep = if matches!(control, TransactionControl::Release { .. }) { position } else { position + 1 };
+                self.savepoints.truncate(keep);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Script {
+    /// Split `script` into statements and check its transaction control
+    pub fn parse(script: &str) -> Result<Self, String> {
+        let statements: Vec<(String, Option<TransactionControl>)> = sql::split_statements(script)
+            .into_iter()
+            .map(|statement| {
+                let control = match sql::parse_sql(&statement) {
+                    Ok(SqlStatement::Transaction(control)) => Some(control),
+                    _ => None,
+                };
+                (statement, control)
+            })
+            .collect();
+        if statements.is_empty() {
+            return Err("Script has no statements".to_string());
+        }
+
+        let implicit_transaction = !statements
+            .iter()
+            .any(|(_, control)| matches!(control, Some(TransactionControl::Begin)));
+        let mut transaction = OpenTransaction { open: implicit_transaction, savepoints: Vec::new() };
+        for (i, (_, control)) in statements.iter().enumerate() {
+            if let Some(control) = control {
+                transaction
+                    .apply(control, implicit_transaction)
+                    .map_err(|e| format!("Statement {}: {}", i + 1, e))?;
+            }
+        }
+        if transaction.open && !implicit_transaction {
+            return Err("Script leaves a transaction open; end it with COMMIT or ROLLBACK".to_string());
+        }
+
+        Ok(Self { statements, implicit_transaction })
+    }
+
+    /// Statements in script order
+    pub fn statements(&self) -> impl Iterator<Item = &str> {
+        self.statements.iter().map(|(statement, _)| statement.as_str())
+    }
+
+    /// Run the statements in order with `execute`.
+    ///
+    /// On a failure the open transaction is rolled back and the remaining
+    /// statements skipped, unless `continue_on_error` is set; then the
+    /// failure is recorded and the script carries on.
+    pub async fn run<T, F, Fut>(&self, continue_on_error: bool, mut execute: F) -> ScriptResult<T>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: Future<Output = Result<T, String>>,
+    {
+        let start = Instant::now();
+        let mut result = ScriptResult {
+            statements: Vec::with_capacity(self.statements.len()),
+            implicit_transaction: self.implicit_transaction,
+            rolled_back: false,
+            transaction_error: None,
+            execution_time_ms: 0.0,
+        };
+
+        let mut transaction = OpenTransaction::default();
+        if self.implicit_transaction {
+            match execute("BEGIN".to_string()).await {
+                Ok(_) => transaction.open = true,
+                Err(e) => result.transaction_error = Some(format!("Failed to begin transaction: {}", e)),
+            }
+        }
+
+        let mut stopped = result.transaction_error.is_some();
+        for (index, (statement, control)) in self.statements.iter().enumerate() {
+            if stopped {
+                result.statements.push(ScriptStatement {
+                    index,
+                    statement: statement.clone(),
+                    status: StatementStatus::Skipped,
+                    result: None,
+                    error: None,
+                    execution_time_ms: 0.0,
+                });
+                continue;
+            }
+
+            let statement_start = Instant::now();
+            let outcome = execute(statement.clone()).await;
+            let execution_time_ms = statement_start.elapsed().as_secs_f64() * 1000.0;
+            let (status, value, error) = match outcome {
+                Ok(value) => {
+                    if let Some(control) = control {
+                        // Checked in `parse`
+                        let _ = transaction.apply(control, false);
+                    }
+                    (StatementStatus::Succeeded, Some(value), None)
+                }
+                Err(e) => (StatementStatus::Failed, None, Some(e)),
+            };
+            if status == StatementStatus::Failed && !continue_on_error {
+                stopped = true;
+                if transaction.open {
+                    match execute("ROLLBACK".to_string()).await {
+                        Ok(_) => result.rolled_back = true,
+                        Err(e) => result.transaction_error = Some(format!("Failed to roll back: {}", e)),
+                    }
+                    transaction = OpenTransaction::default();
+                }
+            }
+            result.statements.push(ScriptStatement {
+                index,
+                statement: statement.clone(),
+                status,
+                result: value,
+                error,
+                execution_time_ms,
+            });
+        }
+
+        if self.implicit_transaction && transaction.open {
+            if let Err(e) = execute("COMMIT".to_string()).await {
+                result.transaction_error = Some(format!("Failed to commit: {}", e));
+            }
+        }
+
+        result.execution_time_ms = start.elapsed().as_secs_f64() * 1000.0;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Run `script`, failing statements that mention `fail`; returns the
+    /// result and every statement the engine saw
+    async fn run(script: &str, continue_on_error: bool) -> (ScriptResult<usize>, Vec<String>) {
+        let script = Script::parse(script).unwrap();
+        let sent = Mutex::new(Vec::new());
+        let result = script
+            .run(continue_on_error, |statement| {
+                sent.lock().unwrap().push(statement.clone());
+                async move {
+                    if statement.contains("fail") { Err(format!("{} failed", statement)) } else { Ok(statement.len()) }
+                }
+            })
+            .await;
+        (result, sent.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_implicit_transaction() {
+        let (result, sent) = run("INSERT INTO t (id) VALUES (1); UPDATE t SET a = 1;", false).await;
+        assert!(result.implicit_transaction);
+        assert_eq!(sent, vec!["BEGIN", "INSERT INTO t (id) VALUES (1)", "UPDATE t SET a = 1", "COMMIT"]);
+        assert!(result.statements.iter().all(|s| s.status == StatementStatus::Succeeded));
+        assert_eq!(result.statements[1].result, Some(18));
+
+        // A failure rolls everything back and skips the rest
+        let (result, sent) = run("INSERT INTO t (id) VALUES (1); UPDATE fail SET a = 1; DELETE FROM t", false).await;
+        assert_eq!(sent.last().map(String::as_str), Some("ROLLBACK"));
+        assert!(!sent.contains(&"COMMIT".to_string()));
+        assert!(result.rolled_back);
+        let statuses: Vec<StatementStatus> = result.statements.iter().map(|s| s.status).collect();
+        assert_eq!(statuses, vec![StatementStatus::Succeeded, StatementStatus::Failed, StatementStatus::Skipped]);
+        assert_eq!(result.first_error(), Some("UPDATE fail SET a = 1 failed"));
+
+        // Continuing records the failure and still commits
+        let (result, sent) = run("UPDATE fail SET a = 1; DELETE FROM t", true).await;
+        assert_eq!(sent.last().map(String::as_str), Some("COMMIT"));
+        assert!(!result.rolled_back);
+        assert_eq!(result.statements[1].status, StatementStatus::Succeeded);
+    }
+
+    #[tokio::test]
+    async fn test_explicit_transactions_and_savepoints() {
+        let script = "CREATE TABLE t (id INT); BEGIN; INSERT INTO t (id) VALUES (1); SAVEPOINT sp; \
+                      INSERT INTO fail (id) VALUES (2); COMMIT";
+        let (result, sent) = run(script, false).await;
+        assert!(!result.implicit_transaction);
+        // The table was created outside the transaction the failure rolled back
+        assert_eq!(sent.first().map(String::as_str), Some("CREATE TABLE t (id INT)"));
+        assert_eq!(sent.last().map(String::as_str), Some("ROLLBACK"));
+        assert!(result.rolled_back);
+        assert_eq!(result.statements[5].status, StatementStatus::Skipped);
+
+        let (result, sent) = run("BEGIN; SAVEPOINT a; ROLLBACK TO a; RELEASE SAVEPOINT a; COMMIT", false).await;
+        assert_eq!(sent.len(), 5);
+        assert!(result.transaction_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failed_rollback_is_reported() {
+        let script = Script::parse("INSERT INTO t (id) VALUES (1); UPDATE t SET a = 1").unwrap();
+        let result = script
+            .run(false, |statement| async move {
+                match statement.as_str() {
+                    "BEGIN" | "INSERT INTO t (id) VALUES (1)" => Ok(()),
+                    _ => Err("Transaction state unknown".to_string()),
+                }
+            })
+            .await;
+        assert!(!result.rolled_back);
+        assert_eq!(result.transaction_error.as_deref(), Some("Failed to roll back: Transaction state unknown"));
+    }
+
+    #[test]
+    fn test_unbalanced_scripts_are_refused() {
+        let errors = [
+            ("BEGIN; INSERT INTO t (id) VALUES (1)", "leaves a transaction open"),
+            ("BEGIN; BEGIN; COMMIT", "Statement 2: BEGIN inside"),
+            ("BEGIN; COMMIT; COMMIT", "Statement 3: no transaction"),
+            ("INSERT INTO t (id) VALUES (1); COMMIT", "need a BEGIN"),
+            ("BEGIN; RELEASE a; COMMIT", "no savepoint named 'a'"),
+            ("BEGIN; COMMIT; SAVEPOINT a", "need an open transaction"),
+            (" ; ", "no statements"),
+        ];
+        for (script, expected) in errors {
+            let error = Script::parse(script).unwrap_err();
+            assert!(error.contains(expected), "{}: {}", script, error);
+        }
+        // Savepoints are allowed inside the implicit transaction
+        assert!(Script::parse("SAVEPOINT a; DELETE FROM t; ROLLBACK TO a").is_ok());
+    }
+}
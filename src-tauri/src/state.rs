@@ -1,59 +1,84 @@
 //! Unified Application State for SochDB Studio
-//! 
-//! Holds shared state for database connections and MCP server.
+//!
+//! Holds shared state for database sessions and their MCP servers.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use sochdb::connection::EmbeddedConnection;
-use sochdb_mcp::McpServer;
 
+use crate::backup::{self, BackupFormat, RestoreResult};
+use crate::connection::backend::{ClosedBackend, DatabaseBackend, EmbeddedBackend};
+use crate::cursors::{self, CursorRegistry};
+use crate::connection::health::{ConnectionHealth, HealthEvent, HealthTracker};
+use crate::connection::manager::{ConnectionManager, ConnectionType};
+use crate::connection::pool::{Session, SessionInfo};
+use crate::connection::remote::RemoteBackend;
+use crate::exporter::{Exporter, ExporterConfig, ExporterStatus};
+use crate::history::QueryHistory;
+use crate::jobs::{Job, JobKind, JobManager};
+use crate::maintenance::{self, MaintenanceTask};
+use crate::metrics::{MetricsHistory, MetricsSample, SAMPLE_INTERVAL};
 use crate::policy::CommandPolicy;
+use crate::running_queries::RunningQueries;
+use crate::saved_queries::QueryLibrary;
+use crate::scheduler::{Scheduler, SCHEDULER_TICK};
+use crate::stats::{CachedStats, StatsCache};
+use crate::util::now_millis;
+use crate::verify;
 
 /// Service status for UI display
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, Default, serde::Serialize)]
 pub struct ServiceStatus {
     pub db_connected: bool,
     pub db_path: Option<String>,
     pub mcp_ready: bool,
-}
-
-impl Default for ServiceStatus {
-    fn default() -> Self {
-        Self {
-            db_connected: false,
-            db_path: None,
-            mcp_ready: false,
-        }
-    }
+    pub active_connection_id: Option<String>,
+    /// Health of the active session's connection
+    pub health: Option<ConnectionHealth>,
+    pub session_count: usize,
 }
 
 /// Unified application state
 pub struct AppState {
-    /// Database connection (shared with MCP)
-    pub connection: Arc<RwLock<Option<EmbeddedConnection>>>,
-    /// MCP server instance
-    pub mcp_server: Arc<RwLock<Option<McpServer>>>,
-    /// Current database path
-    pub db_path: Arc<RwLock<Option<PathBuf>>>,
-    /// Time the current DB session was established
-    pub connected_at: Arc<RwLock<Option<Instant>>>,
-    /// Service status for UI
-    pub status: Arc<RwLock<ServiceStatus>>,
+    /// Saved connections, open sessions and the active session id
+    pub connections: Arc<RwLock<ConnectionManager>>,
     /// Command policy for access control
     pub policy: Arc<RwLock<CommandPolicy>>,
+    /// Per-collection key counts, computed in the background
+    pub collection_stats: Arc<RwLock<StatsCache>>,
+    /// Running and finished admin jobs
+    pub jobs: Arc<JobManager>,
+    /// Maintenance schedules and their run history
+    pub scheduler: Arc<RwLock<Scheduler>>,
+    /// Sampled counters of every open session
+    pub metrics: Arc<RwLock<MetricsHistory>>,
+    /// Optional OpenMetrics endpoint for Prometheus
+    pub exporter: Exporter,
+    /// Queries run through `execute_query`
+    pub query_history: Arc<RwLock<QueryHistory>>,
+    /// Named queries saved by the user
+    pub query_library: Arc<RwLock<QueryLibrary>>,
+    /// Open result cursors
+    pub cursors: Arc<RwLock<CursorRegistry>>,
+    /// Queries in flight, for listing and cancelling
+    pub running_queries: Arc<RunningQueries>,
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
-            connection: Arc::new(RwLock::new(None)),
-            mcp_server: Arc::new(RwLock::new(None)),
-            db_path: Arc::new(RwLock::new(None)),
-            connected_at: Arc::new(RwLock::new(None)),
-            status: Arc::new(RwLock::new(ServiceStatus::default())),
+            connections: Arc::new(RwLock::new(ConnectionManager::new())),
             policy: Arc::new(RwLock::new(CommandPolicy::default())),
+            collection_stats: Arc::new(RwLock::new(StatsCache::new())),
+            jobs: Arc::new(JobManager::new()),
+            scheduler: Arc::new(RwLock::new(Scheduler::default())),
+            metrics: Arc::new(RwLock::new(MetricsHistory::new())),
+            exporter: Exporter::default(),
+            query_history: Arc::new(RwLock::new(QueryHistory::default())),
+            query_library: Arc::new(RwLock::new(QueryLibrary::default())),
+            cursors: Arc::new(RwLock::new(CursorRegistry::new())),
+            running_queries: Arc::new(RunningQueries::new()),
         }
     }
 
@@ -67,79 +92,460 @@ impl AppState {
         *self.policy.write().await = policy;
     }
 
-    /// Initialize connection and MCP server for a database path
-    pub async fn connect(&self, path: PathBuf) -> Result<(), String> {
-        // Open database connection
-        let conn = EmbeddedConnection::open(&path)
-            .map_err(|e| format!("Failed to open database: {}", e))?;
-        
-        let conn_arc = Arc::new(conn);
-        
-        // Create MCP server with shared connection
-        let mcp = McpServer::new(conn_arc.clone());
-        
-        // Update state
-        {
-            // We need to store the connection differently since MCP takes ownership
-            // For now, we'll just track that we're connected
-            let mut status = self.status.write().await;
-            status.db_connected = true;
-            status.db_path = Some(path.to_string_lossy().to_string());
-            status.mcp_ready = true;
+    /// Open a database session under `id` and make it the active one.
+    ///
+    /// `path` is a database directory for embedded connections, `host:port`
+    /// for TCP and a socket path for Unix socket connections.
+    ///
+    /// Any session already registered under the same id, or already holding
+    /// the same path, is closed first so the database is never opened twice.
+    /// A `read_only` session refuses writes; for embedded connections it also
+    /// opens the database without the writer lock, so it can inspect a
+    /// database another process is writing.
+    pub async fn connect(
+        &self,
+        id: String,
+        connection_type: ConnectionType,
+        path: String,
+        read_only: bool,
+    ) -> Result<Arc<Session>, String> {
+        let pool = self.connections.read().await.pool();
+
+        // Release the previous handle before reopening the database
+        pool.remove(&id).await;
+        self.collection_stats.write().await.remove(&id);
+        if let Some(existing) = pool.find_by_path(&path).await {
+            pool.remove(&existing.id).await;
+            self.collection_stats.write().await.remove(&existing.id);
         }
-        
-        {
-            let mut mcp_lock = self.mcp_server.write().await;
-            *mcp_lock = Some(mcp);
+
+        let backend = open_backend(connection_type, &path, read_only).await?;
+        let session = Session::new(id.clone(), path, connection_type, backend, read_only);
+        let timeout_ms = self
+            .connections
+            .read()
+            .await
+            .saved_connection(&id)
+            .and_then(|c| c.statement_timeout_ms);
+        session.set_statement_timeout(timeout_ms.map(Duration::from_millis));
+        pool.add(session).await;
+        self.connections.write().await.set_active_connection(id.clone());
+
+        pool.get(&id)
+            .await
+            .ok_or_else(|| format!("Session '{}' was closed while connecting", id))
+    }
+
+    /// Reopen a session's backend, keeping its id, path and mode.
+    ///
+    /// The old backend is released before the database is reopened, so it
+    /// is never open twice; until a reopen succeeds the session runs on a
+    /// [`ClosedBackend`]. An embedded database whose directory has gone is
+    /// not reopened, since that would create an empty database in its place.
+    pub async fn reconnect(&self, connection_id: &str) -> Result<Arc<Session>, String> {
+        let pool = self.connections.read().await.pool();
+        let old = pool
+            .get(connection_id)
+            .await
+            .ok_or_else(|| format!("Unknown connection: {}", connection_id))?;
+        let (connection_type, path, read_only) = (old.connection_type, old.path.clone(), old.read_only);
+        let statement_timeout = old.statement_timeout();
+        drop(old);
+
+        let embedded = matches!(connection_type, ConnectionType::LocalFile | ConnectionType::Embedded);
+        if embedded && !Path::new(&path).exists() {
+            return Err(format!("Database {} no longer exists", path));
         }
-        
-        {
-            let mut path_lock = self.db_path.write().await;
-            *path_lock = Some(path);
+
+        let session = |backend: Box<dyn DatabaseBackend>| {
+            let session = Session::new(connection_id.to_string(), path.clone(), connection_type, backend, read_only);
+            session.set_statement_timeout(statement_timeout);
+            session
+        };
+        let closed = session(Box::new(ClosedBackend));
+        closed.set_health(ConnectionHealth::Reconnecting);
+        // Don't resurrect a session that was closed in the meantime
+        if pool.add(closed).await.is_none() {
+            pool.remove(connection_id).await;
+            return Err(format!("Session '{}' was closed while reconnecting", connection_id));
         }
 
-        {
-            let mut connected_at = self.connected_at.write().await;
-            *connected_at = Some(Instant::now());
+        let backend = open_backend(connection_type, &path, read_only).await?;
+        if pool.get(connection_id).await.is_none() {
+            return Err(format!("Session '{}' was closed while reconnecting", connection_id));
         }
-        
+        pool.add(session(backend)).await;
+
+        pool.get(connection_id)
+            .await
+            .ok_or_else(|| format!("Session '{}' was closed while reconnecting", connection_id))
+    }
+
+    /// Probe the active session forever, reopening it when it is lost.
+    ///
+    /// `emit` is called whenever the active connection's health changes and
+    /// on every reconnect attempt.
+    pub async fn run_health_monitor<F>(self: Arc<Self>, emit: F)
+    where
+        F: Fn(HealthEvent) + Send + Sync + 'static,
+    {
+        let mut tracker = HealthTracker::new();
+        let mut monitored: Option<String> = None;
+
+        loop {
+            tokio::time::sleep(tracker.next_delay()).await;
+
+            let Ok(session) = self.session(None).await else {
+                monitored = None;
+                tracker = HealthTracker::new();
+                continue;
+            };
+            if monitored.as_deref() != Some(session.id.as_str()) {
+                monitored = Some(session.id.clone());
+                tracker = HealthTracker::new();
+            }
+
+            let id = session.id.clone();
+            let event = |health, error, attempt| HealthEvent {
+                connection_id: id.clone(),
+                health,
+                error,
+                attempt,
+            };
+
+            if tracker.needs_reconnect() {
+                let attempt = tracker.reconnect_started();
+                session.set_health(ConnectionHealth::Reconnecting);
+                emit(event(ConnectionHealth::Reconnecting, None, Some(attempt)));
+
+                // Let go of the old backend so the reopen can take its place
+                drop(session);
+                match self.reconnect(&id).await {
+                    Ok(_) => {
+                        tracker.reconnected();
+                        emit(event(ConnectionHealth::Healthy, None, Some(attempt)));
+                    }
+                    Err(e) => emit(event(ConnectionHealth::Reconnecting, Some(e), Some(attempt))),
+                }
+                continue;
+            }
+
+            let (changed, error) = match session.backend.health_check().await {
+                Ok(()) => (tracker.probe_succeeded(), None),
+                Err(e) => (tracker.probe_failed(), Some(e)),
+            };
+            if let Some(health) = changed {
+                session.set_health(health);
+                emit(event(health, error, None));
+            }
+        }
+    }
+
+    /// Close a session (the active one if `connection_id` is `None`).
+    ///
+    /// If the closed session was active, the most recently opened remaining
+    /// session becomes active.
+    pub async fn disconnect(&self, connection_id: Option<&str>) {
+        let mut manager = self.connections.write().await;
+        let Some(id) = manager.resolve(connection_id) else {
+            return;
+        };
+
+        let pool = manager.pool();
+        pool.remove(&id).await;
+        self.collection_stats.write().await.remove(&id);
+
+        if manager.active_connection_id() == Some(id.as_str()) {
+            match pool.list().await.last() {
+                Some(next) => manager.set_active_connection(next.id.clone()),
+                None => manager.clear_active_connection(),
+            }
+        }
+    }
+
+    /// Make an open session the active one
+    pub async fn switch_session(&self, connection_id: &str) -> Result<(), String> {
+        let mut manager = self.connections.write().await;
+        if manager.pool().get(connection_id).await.is_none() {
+            return Err(format!("Unknown connection: {}", connection_id));
+        }
+        manager.set_active_connection(connection_id.to_string());
         Ok(())
     }
 
-    /// Disconnect from database
-    pub async fn disconnect(&self) {
-        let mut status = self.status.write().await;
-        status.db_connected = false;
-        status.db_path = None;
-        status.mcp_ready = false;
-        
-        let mut mcp_lock = self.mcp_server.write().await;
-        *mcp_lock = None;
-        
-        let mut conn_lock = self.connection.write().await;
-        *conn_lock = None;
-        
-        let mut path_lock = self.db_path.write().await;
-        *path_lock = None;
+    /// Look up a session by id, or the active session if `connection_id` is `None`
+    pub async fn session(&self, connection_id: Option<&str>) -> Result<Arc<Session>, String> {
+        let manager = self.connections.read().await;
+        let id = manager
+            .resolve(connection_id)
+            .ok_or_else(|| "No active connection".to_string())?;
+        manager
+            .pool()
+            .get(&id)
+            .await
+            .ok_or_else(|| format!("Unknown connection: {}", id))
+    }
+
+    /// Cached collection statistics for a session.
+    ///
+    /// Starts a background refresh if the cache is empty or stale; the
+    /// caller gets whatever is cached now rather than waiting for the scan.
+    pub async fn collection_stats(self: &Arc<Self>, session: &Arc<Session>) -> CachedStats {
+        let cached = self.collection_stats.read().await.get(&session.id);
+        let stale = cached.snapshot.as_ref().is_none_or(|s| s.is_stale());
+        if stale && !cached.refreshing {
+            self.refresh_collection_stats(session.clone()).await;
+            return self.collection_stats.read().await.get(&session.id);
+        }
+        cached
+    }
 
-        let mut connected_at = self.connected_at.write().await;
-        *connected_at = None;
+    /// Recount a session's collections in the background
+    pub async fn refresh_collection_stats(self: &Arc<Self>, session: Arc<Session>) {
+        if !self.collection_stats.write().await.begin_refresh(&session.id) {
+            return;
+        }
+
+        let state = self.clone();
+        tokio::spawn(async move {
+            let result = session.backend.collection_counts().await;
+            state.collection_stats.write().await.finish_refresh(&session.id, result);
+        });
     }
 
-    /// Get current status
-    pub async fn get_status(&self) -> ServiceStatus {
-        self.status.read().await.clone()
+    /// Start a maintenance task on a session as a background job
+    pub async fn submit_maintenance(
+        &self,
+        connection_id: Option<&str>,
+        task: MaintenanceTask,
+    ) -> Result<Job, String> {
+        let session = self.session(connection_id).await?;
+        maintenance::submit(&self.jobs, session, task)
     }
 
-    /// Get seconds since the current DB session connected
-    pub async fn uptime_seconds(&self) -> u64 {
-        self.connected_at
-            .read()
+    /// Sample the counters of every open session, forever
+    pub async fn run_metrics_collector(self: Arc<Self>) {
+        loop {
+            tokio::time::sleep(SAMPLE_INTERVAL).await;
+
+            let sessions = self.connections.read().await.pool().list().await;
+            let mut samples = Vec::with_capacity(sessions.len());
+            for session in &sessions {
+                // An unreachable session just leaves a gap in its chart
+                if let Ok(stats) = session.backend.stats().await {
+                    samples.push((session.id.as_str(), MetricsSample::now(&stats)));
+                }
+            }
+
+            let mut metrics = self.metrics.write().await;
+            for (id, sample) in samples {
+                metrics.record(id, sample);
+            }
+            let open: Vec<&str> = sessions.iter().map(|s| s.id.as_str()).collect();
+            metrics.retain(&open);
+        }
+    }
+
+    /// Drop cursors that sat idle too long or whose session closed
+    pub async fn run_cursor_reaper(self: Arc<Self>) {
+        loop {
+            tokio::time::sleep(cursors::SWEEP_INTERVAL).await;
+
+            let sessions = self.connections.read().await.pool().list().await;
+            let open: Vec<&str> = sessions.iter().map(|s| s.id.as_str()).collect();
+            self.cursors.write().await.expire(Instant::now(), &open);
+        }
+    }
+
+    /// Start, restart or stop the metrics endpoint to match `config`
+    pub async fn configure_exporter(self: &Arc<Self>, config: ExporterConfig) -> Result<ExporterStatus, String> {
+        self.exporter.apply(self.clone(), config).await?;
+        Ok(self.exporter.status())
+    }
+
+    /// Back up a session's database to `target` as a background job
+    pub async fn submit_backup(
+        &self,
+        connection_id: Option<&str>,
+        target: PathBuf,
+        format: BackupFormat,
+    ) -> Result<Job, String> {
+        let session = self.session(connection_id).await?;
+        session.embedded("backup")?;
+        let script = session.statement_guard()?;
+
+        let id = session.id.clone();
+        self.jobs.spawn(JobKind::Backup, &id, move |ctx| async move {
+            let _script = script;
+            let result = tokio::task::spawn_blocking(move || backup::backup(&session, &target, format, &ctx))
+                .await
+                .map_err(|e| format!("Backup task failed: {}", e))??;
+            serde_json::to_value(result).map_err(|e| e.to_string())
+        })
+    }
+
+    /// Check a session's database for corruption as a background job
+    pub async fn submit_verify(&self, connection_id: Option<&str>) -> Result<Job, String> {
+        let session = self.session(connection_id).await?;
+        session.embedded("verify_database")?;
+        let script = session.statement_guard()?;
+
+        let id = session.id.clone();
+        self.jobs.spawn(JobKind::Verify, &id, move |ctx| async move {
+            let _script = script;
+            let report = tokio::task::spawn_blocking(move || verify::verify(&session, &ctx))
+                .await
+                .map_err(|e| format!("Verification task failed: {}", e))??;
+            serde_json::to_value(report).map_err(|e| e.to_string())
+        })
+    }
+
+    /// Restore a backup into a new database at `target` as a background job.
+    ///
+    /// If `connect_as` is set, the restored database is then opened as a
+    /// session under that id.
+    pub fn submit_restore(
+        self: &Arc<Self>,
+        source: PathBuf,
+        target: PathBuf,
+        connect_as: Option<String>,
+    ) -> Result<Job, String> {
+        let job_target = connect_as.clone().unwrap_or_else(|| target.display().to_string());
+        let state = self.clone();
+        self.jobs.spawn(JobKind::Restore, &job_target, move |ctx| async move {
+            let start = Instant::now();
+            let restore_target = target.clone();
+            let (manifest, entries_restored) =
+                tokio::task::spawn_blocking(move || backup::restore(&source, &restore_target, &ctx))
+                    .await
+                    .map_err(|e| format!("Restore task failed: {}", e))??;
+
+            let path = target.display().to_string();
+            let connection_id = match connect_as {
+                Some(id) => Some(state.connect(id, ConnectionType::LocalFile, path.clone(), false).await?.id.clone()),
+                None => None,
+            };
+            let result = RestoreResult {
+                path,
+                manifest,
+                entries_restored,
+                connection_id,
+                duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+            };
+            serde_json::to_value(result).map_err(|e| e.to_string())
+        })
+    }
+
+    /// Run due maintenance schedules against open sessions, forever.
+    ///
+    /// `on_change` is called with the scheduler whenever a schedule fires or
+    /// a run finishes, so the caller can persist it.
+    pub async fn run_scheduler<F>(self: Arc<Self>, on_change: F)
+    where
+        F: Fn(&Scheduler) + Send + Sync + 'static,
+    {
+        let on_change = Arc::new(on_change);
+        loop {
+            tokio::time::sleep(SCHEDULER_TICK).await;
+
+            let schedules = self.scheduler.read().await.schedules(None);
+            let mut changed = false;
+            for schedule in schedules {
+                if !schedule.enabled || self.scheduler.read().await.is_running(&schedule.id) {
+                    continue;
+                }
+                // Schedules only run while their connection is open
+                let Ok(session) = self.session(Some(&schedule.connection_id)).await else {
+                    continue;
+                };
+                let wal_size_bytes = if schedule.trigger.needs_wal_size() {
+                    session.backend.stats().await.ok().and_then(|s| s.wal_size_bytes)
+                } else {
+                    None
+                };
+
+                let now = now_millis();
+                if !schedule.is_due(now, wal_size_bytes) {
+                    continue;
+                }
+                let submitted = maintenance::submit(&self.jobs, session, schedule.task.clone());
+                self.scheduler.write().await.record_run(&schedule.id, submitted, now);
+                changed = true;
+            }
+
+            let snapshot = {
+                let mut scheduler = self.scheduler.write().await;
+                changed |= scheduler.sync_jobs(|id| self.jobs.get(id));
+                changed.then(|| scheduler.clone())
+            };
+            // Saved from a copy, so commands are not held up by the disk write
+            if let Some(snapshot) = snapshot {
+                let on_change = on_change.clone();
+                if let Err(e) = tokio::task::spawn_blocking(move || on_change(&snapshot)).await {
+                    eprintln!("Failed to save maintenance history: {}", e);
+                }
+            }
+        }
+    }
+
+    /// List all open sessions
+    pub async fn list_sessions(&self) -> Vec<SessionInfo> {
+        let manager = self.connections.read().await;
+        let active = manager.active_connection_id();
+        manager
+            .pool()
+            .list()
             .await
-            .as_ref()
-            .map(|started| started.elapsed().as_secs())
-            .unwrap_or(0)
+            .iter()
+            .map(|s| SessionInfo {
+                connection_id: s.id.clone(),
+                path: s.path.clone(),
+                connection_type: s.connection_type,
+                active: active == Some(s.id.as_str()),
+                read_only: s.read_only,
+                health: s.health(),
+                uptime_seconds: s.uptime_seconds(),
+            })
+            .collect()
     }
+
+    /// Get current status of the active session
+    pub async fn get_status(&self) -> ServiceStatus {
+        let session_count = self.connections.read().await.pool().list().await.len();
+        match self.session(None).await {
+            Ok(session) => {
+                let health = session.health();
+                let reachable = matches!(health, ConnectionHealth::Healthy | ConnectionHealth::Degraded);
+                ServiceStatus {
+                    db_connected: reachable,
+                    db_path: Some(session.path.clone()),
+                    mcp_ready: reachable,
+                    active_connection_id: Some(session.id.clone()),
+                    health: Some(health),
+                    session_count,
+                }
+            }
+            Err(_) => ServiceStatus {
+                session_count,
+                ..ServiceStatus::default()
+            },
+        }
+    }
+}
+
+/// Open the backend for a connection
+async fn open_backend(
+    connection_type: ConnectionType,
+    path: &str,
+    read_only: bool,
+) -> Result<Box<dyn DatabaseBackend>, String> {
+    Ok(match connection_type {
+        ConnectionType::LocalFile | ConnectionType::Embedded => {
+            Box::new(EmbeddedBackend::open(Path::new(path), read_only)?)
+        }
+        ConnectionType::TcpIp => Box::new(RemoteBackend::connect_tcp(path).await?),
+        ConnectionType::UnixSocket => Box::new(RemoteBackend::connect_unix(Path::new(path)).await?),
+    })
 }
 
 impl Default for AppState {
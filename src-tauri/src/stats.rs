@@ -0,0 +1,553 @@
+//! Cached collection statistics
+//!
+//! Counting keys per collection means reading the whole database, which
+//! takes minutes on large databases. The counts are computed in the
+//! background, cached per connection, and served from the cache; a caller
+//! that finds them stale kicks off a refresh instead of waiting for one.
+//! Large embedded databases are estimated from a sample of their SST files
+//! rather than counted, and the snapshot says how far to trust it.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+use crate::analyze::RESERVED_PREFIX;
+use crate::util::now_millis;
+
+/// Age after which cached counts are refreshed
+pub const STATS_MAX_AGE: Duration = Duration::from_secs(60);
+
+/// Snapshots are kept at least this many times as long as they took to
+/// compute, so a slow database is not rescanned back to back
+pub const STATS_REFRESH_FACTOR: u32 = 10;
+
+/// Stored bytes read for one estimate. Databases no larger than this are
+/// counted exactly.
+pub const STATS_SAMPLE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Share of the stored data a sample must cover for [`Confidence::High`]
+const HIGH_CONFIDENCE_FRACTION: f64 = 0.25;
+
+/// Default depth of a [`PrefixNode`] tree, and the depth cached with the
+/// collection counts
+pub const DEFAULT_PREFIX_DEPTH: usize = 3;
+
+/// Prefixes tracked per tree; keys under prefixes seen after that are
+/// grouped under [`OTHER_PREFIX`]
+const MAX_PREFIX_NODES: usize = 10_000;
+
+/// Name of the node grouping prefixes past [`MAX_PREFIX_NODES`]
+pub const OTHER_PREFIX: &str = "(other)";
+
+/// Key count and size of one top-level collection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectionStats {
+    pub name: String,
+    pub key_count: u64,
+    /// Combined size of keys and values
+    pub size_bytes: u64,
+}
+
+/// How far the counts in a [`CollectionSnapshot`] can be trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Confidence {
+    /// Every key was counted
+    Exact,
+    /// Scaled up from a sample of at least a quarter of the stored data
+    High,
+    /// Scaled up from a smaller sample; collections with few keys may be
+    /// missing
+    Low,
+}
+
+/// Per-collection counts from one pass over the database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionSnapshot {
+    /// Collections ordered by name
+    pub collections: Vec<CollectionStats>,
+    pub total_keys: u64,
+    pub total_bytes: u64,
+    pub confidence: Confidence,
+    /// Share of the stored data that was read (1.0 when exact)
+    pub sampled_fraction: f64,
+    /// Unix timestamp (ms) the scan finished
+    pub computed_at: u64,
+    pub scan_duration_ms: f64,
+    /// Size by key prefix, [`DEFAULT_PREFIX_DEPTH`] levels deep. Served by
+    /// the storage breakdown rather than with the counts.
+    #[serde(skip)]
+    pub prefixes: Arc<PrefixNode>,
+}
+
+impl CollectionSnapshot {
+    /// Whether the snapshot is older than [`STATS_MAX_AGE`], or than
+    /// [`STATS_REFRESH_FACTOR`] times its own scan if that took longer
+    pub fn is_stale(&self) -> bool {
+        let max_age = (STATS_MAX_AGE.as_millis() as f64).max(self.scan_duration_ms * STATS_REFRESH_FACTOR as f64);
+        now_millis().saturating_sub(self.computed_at) as f64 > max_age
+    }
+}
+
+/// Running per-collection counts and prefix sizes, fed one key at a time
+/// so a scan never holds more than one batch of entries
+#[derive(Debug)]
+pub struct CollectionCounter {
+    by_name: BTreeMap<String, CollectionStats>,
+    total_keys: u64,
+    total_bytes: u64,
+    prefixes: PrefixTreeBuilder,
+}
+
+impl Default for CollectionCounter {
+    fn default() -> Self {
+        Self {
+            by_name: BTreeMap::new(),
+            total_keys: 0,
+            total_bytes: 0,
+            prefixes: PrefixTreeBuilder::new(DEFAULT_PREFIX_DEPTH),
+        }
+    }
+}
+
+impl CollectionCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a key that takes `size` bytes. Studio's own keys are skipped.
+    pub fn add(&mut self, key: &[u8], size: u64) {
+        if is_reserved(key) {
+            return;
+        }
+        self.total_keys += 1;
+        self.total_bytes += size;
+        self.prefixes.add(key, size);
+
+        let Some(name) = collection_name(key) else {
+            return;
+        };
+        let stats = self.by_name.entry(name.clone()).or_insert_with(|| CollectionStats {
+            name,
+            key_count: 0,
+            size_bytes: 0,
+        });
+        stats.key_count += 1;
+        stats.size_bytes += size;
+    }
+
+    /// Snapshot of a pass that counted every key
+    pub fn exact(self, scan_duration: Duration) -> CollectionSnapshot {
+        self.finish(1.0, Confidence::Exact, scan_duration)
+    }
+
+    /// Snapshot scaled up from a sample of `sampled_bytes` out of
+    /// `total_bytes` stored
+    pub fn estimate(self, sampled_bytes: u64, total_bytes: u64, scan_duration: Duration) -> CollectionSnapshot {
+        if sampled_bytes >= total_bytes {
+            return self.exact(scan_duration);
+        }
+        let fraction = sampled_bytes as f64 / total_bytes as f64;
+        let confidence = if fraction >= HIGH_CONFIDENCE_FRACTION { Confidence::High } else { Confidence::Low };
+        self.finish(fraction, confidence, scan_duration)
+    }
+
+    fn finish(mut self, fraction: f64, confidence: Confidence, scan_duration: Duration) -> CollectionSnapshot {
+        let scale = |n: u64| if fraction > 0.0 { (n as f64 / fraction).round() as u64 } else { n };
+        self.prefixes.root.scale(&scale);
+        CollectionSnapshot {
+            collections: self
+                .by_name
+                .into_values()
+                .map(|stats| CollectionStats {
+                    key_count: scale(stats.key_count),
+                    size_bytes: scale(stats.size_bytes),
+                    ..stats
+                })
+                .collect(),
+            total_keys: scale(self.total_keys),
+            total_bytes: scale(self.total_bytes),
+            confidence,
+            sampled_fraction: fraction,
+            computed_at: now_millis(),
+            scan_duration_ms: scan_duration.as_secs_f64() * 1000.0,
+            prefixes: Arc::new(self.prefixes.finish()),
+        }
+    }
+}
+
+/// Small seeded generator for picking samples (SplitMix64)
+pub struct SampleRng(u64);
+
+impl SampleRng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Seeded from the clock, so repeated samples differ
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`; `n` must be non-zero
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Shuffle `items` in place
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+/// Cached snapshot for one connection
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CachedStats {
+    pub snapshot: Option<CollectionSnapshot>,
+    /// A background scan is running
+    pub refreshing: bool,
+    /// Error from the last background scan
+    pub last_error: Option<String>,
+}
+
+/// Collection statistics cache, keyed by connection id
+#[derive(Debug, Default)]
+pub struct StatsCache {
+    entries: HashMap<String, CachedStats>,
+}
+
+impl StatsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cached statistics for a connection
+    pub fn get(&self, connection_id: &str) -> CachedStats {
+        self.entries.get(connection_id).cloned().unwrap_or_default()
+    }
+
+    /// Mark a refresh as started. Returns `false` if one is already running.
+    pub fn begin_refresh(&mut self, connection_id: &str) -> bool {
+        let entry = self.entries.entry(connection_id.to_string()).or_default();
+        if entry.refreshing {
+            return false;
+        }
+        entry.refreshing = true;
+        true
+    }
+
+    /// Record the outcome of a refresh
+    pub fn finish_refresh(&mut self, connection_id: &str, result: Result<CollectionSnapshot, String>) {
+        // The connection may have been closed while the scan ran
+        let Some(entry) = self.entries.get_mut(connection_id) else {
+            return;
+        };
+        entry.refreshing = false;
+        match result {
+            Ok(snapshot) => {
+                entry.snapshot = Some(snapshot);
+                entry.last_error = None;
+            }
+            Err(e) => entry.last_error = Some(e),
+        }
+    }
+
+    /// Drop the cached statistics for a connection
+    pub fn remove(&mut self, connection_id: &str) {
+        self.entries.remove(connection_id);
+    }
+}
+
+/// Logical size of the keys under a `/`-separated prefix
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrefixNode {
+    /// Last segment of the prefix (empty for the root)
+    pub name: String,
+    /// Full prefix, without a leading `/` (empty for the root)
+    pub path: String,
+    pub key_count: u64,
+    /// Combined size of keys and values
+    pub size_bytes: u64,
+    /// Sub-prefixes, largest first
+    pub children: Vec<PrefixNode>,
+}
+
+impl PrefixNode {
+    /// The tree cut off `max_depth` levels below this node
+    pub fn truncated(&self, max_depth: usize) -> PrefixNode {
+        PrefixNode {
+            name: self.name.clone(),
+            path: self.path.clone(),
+            key_count: self.key_count,
+            size_bytes: self.size_bytes,
+            children: if max_depth == 0 {
+                Vec::new()
+            } else {
+                self.children.iter().map(|child| child.truncated(max_depth - 1)).collect()
+            },
+        }
+    }
+}
+
+/// Builds a [`PrefixNode`] tree one key at a time, `max_depth` levels deep.
+///
+/// The first level is the collection, as in [`CollectionSnapshot`]; below
+/// that, the last segment of a key is the key itself rather than a prefix.
+/// At most [`MAX_PREFIX_NODES`] prefixes are tracked.
+#[derive(Debug)]
+pub struct PrefixTreeBuilder {
+    root: NodeBuilder,
+    max_depth: usize,
+    nodes: usize,
+}
+
+impl PrefixTreeBuilder {
+    pub fn new(max_depth: usize) -> Self {
+        Self {
+            root: NodeBuilder::default(),
+            max_depth: max_depth.max(1),
+            nodes: 0,
+        }
+    }
+
+    /// Count a key that takes `size` bytes
+    pub fn add(&mut self, key: &[u8], size: u64) {
+        self.root.add(size);
+
+        let path = String::from_utf8_lossy(key);
+        let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        if segments[0].is_empty() {
+            return;
+        }
+        let depth = (segments.len() - 1).clamp(1, self.max_depth);
+
+        let mut node = &mut self.root;
+        for segment in &segments[..depth] {
+            if !node.children.contains_key(*segment) {
+                if self.nodes >= MAX_PREFIX_NODES {
+                    node.children.entry(OTHER_PREFIX.to_string()).or_default().add(size);
+                    return;
+                }
+                self.nodes += 1;
+            }
+            node = node.children.entry(segment.to_string()).or_default();
+            node.add(size);
+        }
+    }
+
+    pub fn finish(self) -> PrefixNode {
+        self.root.finish(String::new(), String::new())
+    }
+}
+
+#[derive(Debug, Default)]
+struct NodeBuilder {
+    key_count: u64,
+    size_bytes: u64,
+    children: BTreeMap<String, NodeBuilder>,
+}
+
+impl NodeBuilder {
+    fn add(&mut self, size: u64) {
+        self.key_count += 1;
+        self.size_bytes += size;
+    }
+
+    fn scale(&mut self, scale: &impl Fn(u64) -> u64) {
+        self.key_count = scale(self.key_count);
+        self.size_bytes = scale(self.size_bytes);
+        for child in self.children.values_mut() {
+            child.scale(scale);
+        }
+    }
+
+    fn finish(self, name: String, path: String) -> PrefixNode {
+        let mut children: Vec<PrefixNode> = self
+            .children
+            .into_iter()
+            .map(|(child, builder)| {
+                let child_path = if path.is_empty() { child.clone() } else { format!("{}/{}", path, child) };
+                builder.finish(child, child_path)
+            })
+            .collect();
+        children.sort_by(|a, b| b.size_bytes.cmp(&a.size_bytes).then_with(|| a.name.cmp(&b.name)));
+
+        PrefixNode {
+            name,
+            path,
+            key_count: self.key_count,
+            size_bytes: self.size_bytes,
+            children,
+        }
+    }
+}
+
+/// The collection a key belongs to: its first path segment. Keys under
+/// [`RESERVED_PREFIX`] belong to none.
+pub fn collection_name(key: &[u8]) -> Option<String> {
+    if is_reserved(key) {
+        return None;
+    }
+    let path = String::from_utf8_lossy(key);
+    path.trim_start_matches('/')
+        .split('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_string())
+}
+
+/// Whether a key is under [`RESERVED_PREFIX`]
+fn is_reserved(key: &[u8]) -> bool {
+    let key = key.strip_prefix(b"/").unwrap_or(key);
+    key.starts_with(RESERVED_PREFIX.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, value: &str) -> (Vec<u8>, Vec<u8>) {
+        (key.as_bytes().to_vec(), value.as_bytes().to_vec())
+    }
+
+    fn count(entries: &[(Vec<u8>, Vec<u8>)]) -> CollectionCounter {
+        let mut counter = CollectionCounter::new();
+        for (key, value) in entries {
+            counter.add(key, (key.len() + value.len()) as u64);
+        }
+        counter
+    }
+
+    #[test]
+    fn test_snapshot_groups_by_top_level_prefix() {
+        let entries = vec![
+            entry("/users/1", "alice"),
+            entry("users/2", "bob"),
+            entry("orders/1", "book"),
+            entry("/", "root"),
+            entry("_studio/stats/users", "{}"),
+        ];
+        let snapshot = count(&entries).exact(Duration::ZERO);
+
+        assert_eq!(snapshot.total_keys, 4);
+        assert_eq!(snapshot.confidence, Confidence::Exact);
+        let names: Vec<&str> = snapshot.collections.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["orders", "users"]);
+        assert_eq!(snapshot.collections[1].key_count, 2);
+        assert_eq!(snapshot.collections[1].size_bytes, (8 + 5 + 7 + 3) as u64);
+        assert!(!snapshot.is_stale());
+    }
+
+    #[test]
+    fn test_estimate_scales_sample() {
+        let entries = vec![entry("users/1", "alice"), entry("users/2", "bob"), entry("orders/1", "book")];
+
+        let snapshot = count(&entries).estimate(100, 400, Duration::ZERO);
+        assert_eq!(snapshot.confidence, Confidence::High);
+        assert_eq!(snapshot.sampled_fraction, 0.25);
+        assert_eq!(snapshot.total_keys, 12);
+        assert_eq!(snapshot.collections[1].key_count, 8);
+
+        assert_eq!(count(&entries).estimate(10, 400, Duration::ZERO).confidence, Confidence::Low);
+        // Reading everything is an exact count
+        let snapshot = count(&entries).estimate(400, 400, Duration::ZERO);
+        assert_eq!(snapshot.confidence, Confidence::Exact);
+        assert_eq!(snapshot.total_keys, 3);
+
+        // Slow scans are refreshed less often
+        let mut snapshot = count(&entries).exact(Duration::from_secs(30));
+        snapshot.computed_at -= 2 * STATS_MAX_AGE.as_millis() as u64;
+        assert!(!snapshot.is_stale());
+        snapshot.computed_at -= 5 * STATS_MAX_AGE.as_millis() as u64;
+        assert!(snapshot.is_stale());
+    }
+
+    #[test]
+    fn test_prefix_tree() {
+        let entries = vec![
+            entry("/users/active/1", "aaaaaaaaaa"),
+            entry("users/active/2", "b"),
+            entry("users/banned/3", "c"),
+            entry("users/4", "d"),
+            entry("orders", "x"),
+            entry("/", "root"),
+        ];
+        let build = |max_depth| {
+            let mut builder = PrefixTreeBuilder::new(max_depth);
+            for (key, value) in &entries {
+                builder.add(key, (key.len() + value.len()) as u64);
+            }
+            builder.finish()
+        };
+        let tree = build(2);
+
+        assert_eq!(tree.key_count, 6);
+        let names: Vec<&str> = tree.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["users", "orders"]);
+
+        let users = &tree.children[0];
+        assert_eq!(users.key_count, 4);
+        // "users/4" is a key, not a prefix
+        let paths: Vec<&str> = users.children.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, vec!["users/active", "users/banned"]);
+        assert_eq!(users.children[0].size_bytes, (15 + 10 + 14 + 1) as u64);
+
+        // Depth is capped
+        assert!(build(1).children[0].children.is_empty());
+        assert_eq!(tree.truncated(1), build(1));
+    }
+
+    #[test]
+    fn test_snapshot_prefix_tree_is_bounded_and_scaled() {
+        let mut counter = CollectionCounter::new();
+        for i in 0..MAX_PREFIX_NODES + 5 {
+            counter.add(format!("c{}/1", i).as_bytes(), 10);
+        }
+        let snapshot = counter.estimate(1, 2, Duration::ZERO);
+
+        let tree = &snapshot.prefixes;
+        assert_eq!(tree.key_count, 2 * (MAX_PREFIX_NODES + 5) as u64);
+        assert_eq!(tree.children.len(), MAX_PREFIX_NODES + 1);
+        let other = tree.children.iter().find(|c| c.name == OTHER_PREFIX).unwrap();
+        assert_eq!(other.key_count, 10);
+        assert_eq!(other.size_bytes, 100);
+    }
+
+    #[test]
+    fn test_cache_runs_one_refresh_at_a_time() {
+        let mut cache = StatsCache::new();
+        assert!(cache.begin_refresh("a"));
+        assert!(!cache.begin_refresh("a"));
+        assert!(cache.get("a").snapshot.is_none());
+
+        cache.finish_refresh("a", Ok(CollectionCounter::new().exact(Duration::ZERO)));
+        assert!(!cache.get("a").refreshing);
+        assert!(cache.get("a").snapshot.is_some());
+
+        // A failed refresh keeps the previous snapshot
+        assert!(cache.begin_refresh("a"));
+        cache.finish_refresh("a", Err("scan failed".to_string()));
+        assert!(cache.get("a").snapshot.is_some());
+        assert_eq!(cache.get("a").last_error.as_deref(), Some("scan failed"));
+
+        // Results for a closed connection are dropped
+        cache.remove("a");
+        cache.finish_refresh("a", Ok(CollectionCounter::new().exact(Duration::ZERO)));
+        assert!(cache.get("a").snapshot.is_none());
+    }
+}
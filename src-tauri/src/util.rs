@@ -0,0 +1,11 @@
+//! Small helpers shared across modules
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current Unix timestamp in milliseconds
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
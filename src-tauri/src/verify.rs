@@ -0,0 +1,470 @@
+//! Database integrity checks
+//!
+//! `verify_database` reads every WAL segment and SST file and checks each
+//! secondary index against the rows it points to:
+//!
+//! - WAL records must pass their checksums and have increasing LSNs. A
+//!   record cut off by the end of the newest segment is what a crash
+//!   normally leaves behind, so it is only a warning.
+//! - SST blocks must pass their checksums and keys must be strictly
+//!   increasing within a file.
+//! - Every index entry must point to an existing row.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use sochdb_storage::sstable::SstReader;
+use sochdb_storage::wal::{WalReader, RECORD_HEADER_SIZE};
+
+use crate::connection::pool::Session;
+use crate::jobs::JobContext;
+use crate::util::now_millis;
+
+/// Problems listed in a report; the counts keep going past this
+const MAX_PROBLEMS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Data is lost or unreadable
+    Error,
+    /// Suspicious, but expected after an unclean shutdown
+    Warning,
+}
+
+/// Part of the database a problem was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckKind {
+    Wal,
+    Sst,
+    Index,
+}
+
+/// One problem found by a check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Problem {
+    pub severity: Severity,
+    pub check: CheckKind,
+    /// File the problem is in, relative to the database directory
+    pub file: Option<String>,
+    /// Byte offset within `file`
+    pub offset: Option<u64>,
+    pub message: String,
+}
+
+/// Outcome of `verify_database`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerifyReport {
+    pub wal_segments_checked: usize,
+    pub wal_records_checked: u64,
+    pub sst_files_checked: usize,
+    pub sst_entries_checked: u64,
+    pub indexes_checked: usize,
+    pub index_entries_checked: u64,
+    pub error_count: usize,
+    pub warning_count: usize,
+    /// The first [`MAX_PROBLEMS`] problems found
+    pub problems: Vec<Problem>,
+    /// More problems were found than are listed
+    pub truncated: bool,
+    pub duration_ms: f64,
+    /// Unix timestamp (ms) the check finished
+    pub verified_at: u64,
+}
+
+impl VerifyReport {
+    /// Whether no errors were found (warnings are allowed)
+    pub fn is_healthy(&self) -> bool {
+        self.error_count == 0
+    }
+
+    fn add(&mut self, problem: Problem) {
+        match problem.severity {
+            Severity::Error => self.error_count += 1,
+            Severity::Warning => self.warning_count += 1,
+        }
+        if self.problems.len() < MAX_PROBLEMS {
+            self.problems.push(problem);
+        } else {
+            self.truncated = true;
+        }
+    }
+}
+
+/// A record position, or a read failure at an offset
+pub type ReadResult<T> = Result<(T, u64), (u64, String)>;
+
+/// A WAL record that failed to read
+#[derive(Debug, Clone)]
+pub struct WalFailure {
+    pub offset: u64,
+    /// Payload length from the record's header, if the header could be read
+    pub record_len: Option<u64>,
+    pub message: String,
+}
+
+impl WalFailure {
+    /// Whether the record runs past the end of a file of `file_len` bytes,
+    /// as a write cut short by a crash leaves it
+    fn is_truncated(&self, file_len: u64) -> bool {
+        let header_end = self.offset + RECORD_HEADER_SIZE;
+        match self.record_len {
+            Some(len) => header_end + len > file_len,
+            None => header_end > file_len,
+        }
+    }
+}
+
+/// Check one WAL segment's records.
+///
+/// `last_lsn` carries the highest LSN seen across segments, which are
+/// checked oldest first. `newest_segment_len` is the file length of the
+/// newest segment, the only one a crash can leave a torn record in.
+pub fn check_wal_segment(
+    report: &mut VerifyReport,
+    file: &str,
+    records: impl Iterator<Item = Result<(u64, u64), WalFailure>>,
+    last_lsn: &mut Option<u64>,
+    newest_segment_len: Option<u64>,
+) {
+    report.wal_segments_checked += 1;
+    for record in records {
+        let (lsn, offset) = match record {
+            Ok(record) => record,
+            Err(failure) => {
+                // Only a record cut off by the end of the newest segment is
+                // a torn tail; a bad record with the whole of it on disk is
+                // corruption. Reading cannot resume after either.
+                let torn_tail = newest_segment_len.is_some_and(|len| failure.is_truncated(len));
+                report.add(Problem {
+                    severity: if torn_tail { Severity::Warning } else { Severity::Error },
+                    check: CheckKind::Wal,
+                    file: Some(file.to_string()),
+                    offset: Some(failure.offset),
+                    message: if torn_tail {
+                        format!("Incomplete record at end of WAL (likely an unclean shutdown): {}", failure.message)
+                    } else {
+                        failure.message
+                    },
+                });
+                return;
+            }
+        };
+
+        report.wal_records_checked += 1;
+        if let Some(previous) = *last_lsn {
+            if lsn <= previous {
+                report.add(Problem {
+                    severity: Severity::Error,
+                    check: CheckKind::Wal,
+                    file: Some(file.to_string()),
+                    offset: Some(offset),
+                    message: format!("LSN {} does not follow LSN {}", lsn, previous),
+                });
+            }
+        }
+        *last_lsn = Some(last_lsn.map_or(lsn, |previous| previous.max(lsn)));
+    }
+}
+
+/// Check one SST file's entries for read failures and key order
+pub fn check_sst_file(report: &mut VerifyReport, file: &str, entries: impl Iterator<Item = ReadResult<Vec<u8>>>) {
+    report.sst_files_checked += 1;
+    let mut previous: Option<Vec<u8>> = None;
+    for entry in entries {
+        let (key, offset) = match entry {
+            Ok(entry) => entry,
+            Err((offset, message)) => {
+                report.add(Problem {
+                    severity: Severity::Error,
+                    check: CheckKind::Sst,
+                    file: Some(file.to_string()),
+                    offset: Some(offset),
+                    message,
+                });
+                return;
+            }
+        };
+
+        report.sst_entries_checked += 1;
+        if let Some(previous) = &previous {
+            if key <= *previous {
+                report.add(Problem {
+                    severity: Severity::Error,
+                    check: CheckKind::Sst,
+                    file: Some(file.to_string()),
+                    offset: Some(offset),
+                    message: format!(
+                        "Key {} is out of order after {}",
+                        String::from_utf8_lossy(&key),
+                        String::from_utf8_lossy(previous)
+                    ),
+                });
+            }
+        }
+        previous = Some(key);
+    }
+}
+
+/// Check that every entry of an index points to an existing row.
+///
+/// `entries` yields `(index key, row key)` pairs.
+pub fn check_index(
+    report: &mut VerifyReport,
+    index: &str,
+    entries: impl Iterator<Item = (Vec<u8>, Vec<u8>)>,
+    row_exists: impl Fn(&[u8]) -> Result<bool, String>,
+) {
+    report.indexes_checked += 1;
+    for (index_key, row_key) in entries {
+        report.index_entries_checked += 1;
+        let message = match row_exists(&row_key) {
+            Ok(true) => continue,
+            Ok(false) => format!(
+                "Entry {} points to missing row {}",
+                String::from_utf8_lossy(&index_key),
+                String::from_utf8_lossy(&row_key)
+            ),
+            Err(e) => format!("Failed to look up row {}: {}", String::from_utf8_lossy(&row_key), e),
+        };
+        report.add(Problem {
+            severity: Severity::Error,
+            check: CheckKind::Index,
+            file: Some(index.to_string()),
+            offset: None,
+            message,
+        });
+    }
+}
+
+/// Verify `session`'s database
+pub fn verify(session: &Session, ctx: &JobContext) -> Result<VerifyReport, String> {
+    let kernel = session.embedded("verify_database")?.connection().kernel();
+    let root = PathBuf::from(&session.path);
+    let wal_dir = kernel.database_info().wal_dir;
+    let start = Instant::now();
+
+    let wal_segments = list_files(&wal_dir, &|_| true)?;
+    let mut sst_files = Vec::new();
+    collect_files(&root, &wal_dir, &mut sst_files)?;
+    sst_files.sort();
+    let indexes = kernel.indexes();
+
+    let total = wal_segments.len() + sst_files.len() + indexes.len();
+    let mut done = 0;
+    let mut step = |what: String| -> Result<(), String> {
+        if ctx.is_cancelled() {
+            return Err(format!("Verification cancelled after {} of {} checks", done, total));
+        }
+        ctx.progress(Some(done as f64 / total.max(1) as f64), what);
+        done += 1;
+        Ok(())
+    };
+
+    let mut report = VerifyReport::default();
+    let mut last_lsn = None;
+    for (i, path) in wal_segments.iter().enumerate() {
+        let name = relative_name(&root, path);
+        step(format!("Checking WAL segment {}", name))?;
+        let newest_segment_len = if i + 1 == wal_segments.len() {
+            let meta = fs::metadata(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            Some(meta.len())
+        } else {
+            None
+        };
+        match WalReader::open(path) {
+            Ok(reader) => check_wal_segment(
+                &mut report,
+                &name,
+                reader.map(|r| {
+                    r.map(|rec| (rec.lsn, rec.offset)).map_err(|e| WalFailure {
+                        offset: e.offset,
+                        record_len: e.record_len,
+                        message: e.message,
+                    })
+                }),
+                &mut last_lsn,
+                newest_segment_len,
+            ),
+            Err(e) => report.add(unreadable(CheckKind::Wal, name, e)),
+        }
+    }
+
+    for path in &sst_files {
+        let name = relative_name(&root, path);
+        step(format!("Checking SST file {}", name))?;
+        match SstReader::open(path) {
+            Ok(reader) => check_sst_file(
+                &mut report,
+                &name,
+                reader.entries().map(|r| r.map(|e| (e.key, e.offset)).map_err(|e| (e.offset, e.message))),
+            ),
+            Err(e) => report.add(unreadable(CheckKind::Sst, name, e)),
+        }
+    }
+
+    // Index entries and rows come from the same snapshot
+    let txn = kernel.begin_read_only_fast();
+    let result = (|| -> Result<(), String> {
+        for index in &indexes {
+            step(format!("Checking index {}", index.name))?;
+            match kernel.scan_index(txn, &index.name) {
+                Ok(entries) => check_index(
+                    &mut report,
+                    &index.name,
+                    entries.into_iter().map(|e| (e.index_key, e.row_key)),
+                    |row_key| {
+                        kernel
+                            .get_unchecked(txn, row_key)
+                            .map(|row| row.is_some())
+                            .map_err(|e| e.to_string())
+                    },
+                ),
+                Err(e) => report.add(unreadable(CheckKind::Index, index.name.clone(), e)),
+            }
+        }
+        Ok(())
+    })();
+    kernel.abort_read_only_fast(txn);
+    result?;
+
+    report.duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+    report.verified_at = now_millis();
+    Ok(report)
+}
+
+fn unreadable(check: CheckKind, file: String, error: impl std::fmt::Display) -> Problem {
+    Problem {
+        severity: Severity::Error,
+        check,
+        file: Some(file),
+        offset: None,
+        message: format!("Cannot be read: {}", error),
+    }
+}
+
+/// Regular files directly in `dir` matching `filter`, sorted by name
+fn list_files(dir: &Path, filter: &dyn Fn(&Path) -> bool) -> Result<Vec<PathBuf>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to list {}: {}", dir.display(), e)),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| format!("Failed to list {}: {}", dir.display(), e))?.path();
+        if path.is_file() && filter(&path) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// SST files under `dir`, skipping the WAL directory
+fn collect_files(dir: &Path, wal_dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), String> {
+    out.extend(list_files(dir, &|path| path.extension().is_some_and(|ext| ext == "sst"))?);
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to list {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| format!("Failed to list {}: {}", dir.display(), e))?.path();
+        if path.is_dir() && path != wal_dir {
+            collect_files(&path, wal_dir, out)?;
+        }
+    }
+    Ok(())
+}
+
+fn relative_name(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).display().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(offset: u64, record_len: Option<u64>) -> WalFailure {
+        WalFailure { offset, record_len, message: "checksum mismatch".to_string() }
+    }
+
+    #[test]
+    fn test_wal_checks() {
+        let mut report = VerifyReport::default();
+        let mut last_lsn = None;
+        check_wal_segment(&mut report, "wal/1.log", [Ok((1, 0)), Ok((2, 40))].into_iter(), &mut last_lsn, None);
+        // LSNs must keep increasing across segments
+        check_wal_segment(&mut report, "wal/2.log", [Ok((2, 0)), Ok((3, 40))].into_iter(), &mut last_lsn, None);
+        assert_eq!(report.error_count, 1);
+        assert_eq!(report.problems[0].file.as_deref(), Some("wal/2.log"));
+        assert_eq!(last_lsn, Some(3));
+
+        // A torn tail in the newest segment is only a warning
+        let torn = [Ok((4, 0)), Err(failure(40, Some(100)))];
+        check_wal_segment(&mut report, "wal/3.log", torn.clone().into_iter(), &mut last_lsn, Some(60));
+        assert_eq!((report.error_count, report.warning_count), (1, 1));
+        assert_eq!(report.problems[1].offset, Some(40));
+
+        // ...as is a header cut off by the end of the file
+        let mut report = VerifyReport::default();
+        let header_cut = [Ok((4, 0)), Err(failure(40, None))];
+        check_wal_segment(&mut report, "wal/3.log", header_cut.into_iter(), &mut None, Some(50));
+        assert_eq!((report.error_count, report.warning_count), (0, 1));
+
+        // Anywhere else it is an error
+        let mut report = VerifyReport::default();
+        check_wal_segment(&mut report, "wal/3.log", torn.into_iter(), &mut None, None);
+        assert!(!report.is_healthy());
+        assert_eq!(report.wal_records_checked, 1);
+    }
+
+    #[test]
+    fn test_wal_corruption_before_end_is_an_error() {
+        // The bad record is whole and more bytes follow it, so the reader
+        // stopping there is not a torn tail
+        let mut report = VerifyReport::default();
+        let records = [Ok((1, 0)), Err(failure(40, Some(24)))];
+        let file_len = 40 + RECORD_HEADER_SIZE + 24 + 200;
+        check_wal_segment(&mut report, "wal/3.log", records.into_iter(), &mut None, Some(file_len));
+        assert!(!report.is_healthy());
+        assert_eq!((report.error_count, report.warning_count), (1, 0));
+        assert_eq!(report.problems[0].message, "checksum mismatch");
+
+        // A whole record that fails its checksum at the very end is too
+        let mut report = VerifyReport::default();
+        let records = [Ok((1, 0)), Err(failure(40, Some(24)))];
+        check_wal_segment(&mut report, "wal/3.log", records.into_iter(), &mut None, Some(40 + RECORD_HEADER_SIZE + 24));
+        assert_eq!(report.error_count, 1);
+    }
+
+    #[test]
+    fn test_sst_key_order() {
+        let mut report = VerifyReport::default();
+        let entries = [
+            Ok((b"a".to_vec(), 0)),
+            Ok((b"c".to_vec(), 10)),
+            Ok((b"b".to_vec(), 20)),
+            Ok((b"d".to_vec(), 30)),
+            Err((40, "block checksum mismatch".to_string())),
+            Ok((b"e".to_vec(), 50)),
+        ];
+        check_sst_file(&mut report, "000001.sst", entries.into_iter());
+
+        assert_eq!(report.sst_entries_checked, 4);
+        assert_eq!(report.error_count, 2);
+        assert_eq!(report.problems[0].offset, Some(20));
+        assert_eq!(report.problems[1].message, "block checksum mismatch");
+    }
+
+    #[test]
+    fn test_dangling_index_entries() {
+        let mut report = VerifyReport::default();
+        let entries = (0..MAX_PROBLEMS + 10).map(|i| (format!("idx/{}", i).into_bytes(), format!("row/{}", i).into_bytes()));
+        check_index(&mut report, "users_by_email", entries, |row| Ok(row == b"row/0"));
+
+        assert_eq!(report.index_entries_checked, MAX_PROBLEMS as u64 + 10);
+        assert_eq!(report.error_count, MAX_PROBLEMS + 9);
+        assert_eq!(report.problems.len(), MAX_PROBLEMS);
+        assert!(report.truncated);
+    }
+}